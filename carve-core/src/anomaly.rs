@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+
+use crate::freq;
+use crate::line::Line;
+
+/// Lines aren't flagged until this many have been seen, as everything is rare at first.
+const MIN_LINES: u64 = 100;
+
+/// How surprising a line has to be, in bits, to be flagged: 8 bits means its kind of message
+/// makes up less than 1 in 256 of the lines seen.
+const THRESHOLD: f64 = 8.0;
+
+/// Spots unusual lines by how rare their kind of message is among all the lines seen so far.
+///
+/// Lines are grouped into messages the same way as the frequency panel, by replacing numbers,
+/// IDs and other variables with `*`. A line's rarity is the information content of its message,
+/// `log2(total / count)`, so a message seen once in a thousand lines scores about 10 bits while
+/// routine output scores close to 0. Scores change as lines arrive: a message stops being
+/// unusual once it's been seen often enough.
+#[derive(Default)]
+pub struct Rarity {
+    counts: HashMap<String, u64>,
+    total: u64,
+    scanned: usize,
+}
+
+impl Rarity {
+    /// Count any lines that have arrived since the last update.
+    pub fn update(&mut self, lines: &[Line]) {
+        for line in &lines[self.scanned.min(lines.len())..] {
+            *self.counts.entry(freq::template(&line.text)).or_default() += 1;
+            self.total += 1;
+        }
+        self.scanned = lines.len();
+    }
+
+    /// Account for lines dropped from the buffer so new lines aren't skipped.
+    pub fn evicted(&mut self, removed: usize) {
+        self.scanned = self.scanned.saturating_sub(removed);
+    }
+
+    /// Account for deleted lines being put back into the buffer, which were already counted.
+    pub fn restored(&mut self, count: usize) {
+        self.scanned += count;
+    }
+
+    /// How surprising a line is, in bits.
+    pub fn score(&self, text: &str) -> f64 {
+        let count = self.counts.get(&freq::template(text)).copied().unwrap_or(0).max(1);
+        (self.total.max(1) as f64 / count as f64).log2()
+    }
+
+    /// Whether a line is rare enough to flag.
+    pub fn is_unusual(&self, text: &str) -> bool {
+        self.total >= MIN_LINES && self.score(text) >= THRESHOLD
+    }
+}
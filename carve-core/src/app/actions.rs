@@ -0,0 +1,121 @@
+use std::backtrace::Backtrace;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::panic::PanicHookInfo;
+use std::path::Path;
+
+use crate::app::key::{KeyCode, KeyEvent, KeyModifiers};
+
+/// The parts of the session's state a key press can change, by name.
+pub type Snapshot = Vec<(&'static str, String)>;
+
+/// A trace of every key pressed and what it changed, written with `--log-actions` so reports of
+/// odd behaviour come with the exact sequence that led to it.
+pub struct ActionLog {
+    out: BufWriter<File>,
+}
+
+impl ActionLog {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            out: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    /// Write a line for a key press handled in `mode`, listing each value that differs between
+    /// the snapshots taken before and after it. Each line is flushed straight away so the trace
+    /// survives a crash.
+    pub fn record(&mut self, key: &KeyEvent, mode: &str, before: &Snapshot, after: &Snapshot) -> io::Result<()> {
+        let time = chrono::Local::now().format("%H:%M:%S%.3f");
+        write!(self.out, "{} key={} mode={}", time, describe(key), mode)?;
+        let mut changed = false;
+        for ((name, old), (_, new)) in before.iter().zip(after) {
+            if old != new {
+                write!(self.out, " {}={:?}->{:?}", name, old, new)?;
+                changed = true;
+            }
+        }
+        if !changed {
+            write!(self.out, " (no change)")?;
+        }
+        writeln!(self.out)?;
+        self.out.flush()
+    }
+}
+
+/// Append a panic and its backtrace to the action log at `path`, so the trace leading up to a
+/// crash ends with the crash itself.
+pub fn record_panic(path: &Path, info: &PanicHookInfo, backtrace: &Backtrace) -> io::Result<()> {
+    let time = chrono::Local::now().format("%H:%M:%S%.3f");
+    let mut out = OpenOptions::new().append(true).open(path)?;
+    writeln!(out, "{} {}\n{}", time, info, backtrace)
+}
+
+/// A key press as it would be written in the docs, e.g. `j`, `Enter` or `ctrl+c`.
+pub fn describe(key: &KeyEvent) -> String {
+    let mut out = String::new();
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        out.push_str("ctrl+");
+    }
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        out.push_str("alt+");
+    }
+    match key.code {
+        KeyCode::Char(' ') => out.push_str("Space"),
+        KeyCode::Char(c) => out.push(c),
+        code => out.push_str(&format!("{:?}", code)),
+    }
+    out
+}
+
+/// Parse a script of key presses written the way the action log writes them, separated by
+/// spaces, e.g. `f timeout Enter n ctrl+d`. Anything that isn't the name of a key is typed a
+/// character at a time, and `Space` presses the space bar.
+pub fn parse_keys(script: &str) -> Result<Vec<KeyEvent>, String> {
+    let mut keys = Vec::new();
+    for word in script.split_whitespace() {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = word;
+        loop {
+            if let Some(after) = rest.strip_prefix("ctrl+").filter(|after| !after.is_empty()) {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("alt+").filter(|after| !after.is_empty()) {
+                modifiers |= KeyModifiers::ALT;
+                rest = after;
+            } else {
+                break;
+            }
+        }
+        let code = match rest {
+            "Space" => Some(KeyCode::Char(' ')),
+            "Enter" => Some(KeyCode::Enter),
+            "Esc" => Some(KeyCode::Esc),
+            "Tab" => Some(KeyCode::Tab),
+            "BackTab" => Some(KeyCode::BackTab),
+            "Backspace" => Some(KeyCode::Backspace),
+            "Delete" => Some(KeyCode::Delete),
+            "Up" => Some(KeyCode::Up),
+            "Down" => Some(KeyCode::Down),
+            "Left" => Some(KeyCode::Left),
+            "Right" => Some(KeyCode::Right),
+            "Home" => Some(KeyCode::Home),
+            "End" => Some(KeyCode::End),
+            "PageUp" => Some(KeyCode::PageUp),
+            "PageDown" => Some(KeyCode::PageDown),
+            _ => None,
+        };
+        match code {
+            Some(code) => keys.push(KeyEvent::new(code, modifiers)),
+            None if modifiers.is_empty() => keys.extend(rest.chars().map(|c| KeyEvent::new(KeyCode::Char(c), modifiers))),
+            None => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => keys.push(KeyEvent::new(KeyCode::Char(c), modifiers)),
+                    _ => return Err(format!("unknown key: {}", word)),
+                }
+            }
+        }
+    }
+    Ok(keys)
+}
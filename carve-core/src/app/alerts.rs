@@ -0,0 +1,212 @@
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::line::Line;
+use crate::pane;
+
+/// How long the status bar flashes for after an alert.
+const FLASH_DURATION: Duration = Duration::from_millis(1500);
+/// The least time between desktop notifications or commands for the same alert, so a crash
+/// logging a burst of matching lines doesn't bury the desktop in notifications or run a command
+/// for every one of them.
+const REPEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A pattern to be told about as soon as a line matching it is read, even with carve in a
+/// background pane, e.g.
+///
+/// ```toml
+/// [[alert]]
+/// pattern = "panicked at"
+/// notify = true
+///
+/// [[alert]]
+/// pattern = '/status=5\d\d'
+/// bell = false
+///
+/// [[alert]]
+/// pattern = "deadlock detected"
+/// jump = true
+///
+/// [[alert]]
+/// pattern = "OutOfMemoryError"
+/// run = "jcmd $(pgrep -f app.jar) GC.heap_dump /tmp/app.hprof"
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertRule {
+    /// Written like a filter, so `/` starts a regular expression.
+    pub pattern: String,
+    /// Ring the terminal bell, unless `false`.
+    pub bell: Option<bool>,
+    /// Flash the status bar, unless `false`.
+    pub flash: Option<bool>,
+    /// Send a desktop notification with the line.
+    #[serde(default)]
+    pub notify: bool,
+    /// A shell command to run, given the line on stdin and as `$1`.
+    pub run: Option<String>,
+    /// Stop following new lines and go to the line, if following them.
+    #[serde(default)]
+    pub jump: bool,
+    /// Mark the line, as with Space.
+    #[serde(default)]
+    pub mark: bool,
+}
+
+/// What the alerts a line set off want done with it in the view.
+#[derive(Default)]
+pub struct Triggered {
+    pub jump: Option<String>, // The pattern of an alert wanting the view stopped at the line
+    pub mark: bool,
+}
+
+/// A pattern to be told about as soon as a line matching it is read.
+struct Alert {
+    pattern: String,
+    bell: bool,
+    flash: bool,
+    notify: bool,
+    run: Option<String>,
+    jump: bool,
+    mark: bool,
+    acted: Option<Instant>, // When the notification was last sent or command last run
+}
+
+/// Alerts for lines matching patterns, so carve can be left in a background pane and still
+/// draw attention to a crash as soon as it's logged.
+///
+/// Every line read is checked, whatever the filter. A burst of matches rings the bell once.
+#[derive(Default)]
+pub struct Alerts {
+    alerts: Vec<Alert>,
+    ring: bool, // Whether to ring the bell on the next frame
+    flash_until: Option<Instant>,
+}
+
+impl Alerts {
+    /// Start alerting to the lines matching a rule's pattern, which is written like a filter.
+    pub fn add(&mut self, rule: &AlertRule) -> Result<(), String> {
+        pane::check_filter(&rule.pattern)?;
+        self.alerts.push(Alert {
+            pattern: rule.pattern.clone(),
+            bell: rule.bell.unwrap_or(true),
+            flash: rule.flash.unwrap_or(true),
+            notify: rule.notify,
+            run: rule.run.clone(),
+            jump: rule.jump,
+            mark: rule.mark,
+            acted: None,
+        });
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.alerts.clear();
+    }
+
+    /// Check a line that has just been read against each alert, setting off those it matches,
+    /// and return what they want done with the line in the view.
+    pub fn check(&mut self, line: &Line) -> Triggered {
+        let mut triggered = Triggered::default();
+        for alert in &mut self.alerts {
+            if !pane::passes_filter(&alert.pattern, line, None) {
+                continue;
+            }
+            if alert.jump && triggered.jump.is_none() {
+                triggered.jump = Some(alert.pattern.clone());
+            }
+            triggered.mark |= alert.mark;
+            self.ring |= alert.bell;
+            if alert.flash {
+                self.flash_until = Some(Instant::now() + FLASH_DURATION);
+            }
+            if alert.acted.is_some_and(|acted| acted.elapsed() < REPEAT_INTERVAL) {
+                continue;
+            }
+            if alert.notify {
+                alert.acted = Some(Instant::now());
+                if let Err(e) = notify(&alert.pattern, &line.text) {
+                    tracing::warn!("failed to send a desktop notification: {}", e);
+                }
+            }
+            if let Some(command) = &alert.run {
+                alert.acted = Some(Instant::now());
+                if let Err(e) = run(command, &line.text) {
+                    tracing::warn!("failed to run '{}': {}", command, e);
+                }
+            }
+        }
+        triggered
+    }
+
+    /// Whether an alert has gone off since the last call that rings the bell.
+    pub fn take_ring(&mut self) -> bool {
+        std::mem::take(&mut self.ring)
+    }
+
+    /// Whether the status bar should be flashing for an alert that went off recently.
+    pub fn flashing(&self) -> bool {
+        self.flash_until.is_some_and(|until| Instant::now() < until)
+    }
+}
+
+/// Run an alert's shell command without waiting for it to finish, giving it the line on stdin
+/// and as `$1`. The line is passed as an argument rather than spliced into the command so it
+/// isn't interpreted by the shell.
+fn run(command: &str, text: &str) -> io::Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .arg("sh")
+        .arg(text)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut stdin = child.stdin.take();
+    let text = format!("{}\n", text);
+    let command = command.to_string();
+    // Feed the line and reap the command away from the UI, which a slow command mustn't hold up
+    std::thread::spawn(move || {
+        // A command that doesn't read its input closes it, which is fine
+        let _ = stdin.as_mut().map(|stdin| stdin.write_all(text.as_bytes()));
+        drop(stdin);
+        match child.wait() {
+            Ok(status) if !status.success() => tracing::warn!("'{}' exited with {}", command, status),
+            Err(e) => tracing::warn!("failed to wait for '{}': {}", command, e),
+            Ok(_) => {}
+        }
+    });
+    Ok(())
+}
+
+/// Send a desktop notification with the platform's notifier, without waiting for it to finish.
+fn notify(pattern: &str, text: &str) -> io::Result<()> {
+    let title = format!("carve: {}", pattern);
+    let mut command = if cfg!(target_os = "macos") {
+        // Passed as arguments rather than spliced into the script so they aren't interpreted
+        let mut command = Command::new("osascript");
+        command.args([
+            "-e",
+            "on run argv\ndisplay notification (item 2 of argv) with title (item 1 of argv)\nend run",
+            &title,
+            text,
+        ]);
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.args(["--", &title, text]);
+        command
+    };
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // Reap the notifier once it exits rather than leaving a zombie behind
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
@@ -0,0 +1,376 @@
+use std::path::PathBuf;
+
+use crate::fields::Fields;
+use crate::sample::Policy;
+use crate::sort::Sort;
+use crate::table::Delimiter;
+
+/// A command entered on the `:` command line.
+pub enum Command {
+    /// Start comparing live counts of two patterns, or stop when no patterns are given.
+    Compare(Option<(String, String)>),
+    /// Show two sources side by side with their differences highlighted, or go back to the
+    /// normal view when no sources are given.
+    Diff(Option<(String, String)>),
+    /// Load a file into the session as an additional source and follow it for new lines.
+    Open(PathBuf),
+    /// Write every line in the buffer to a file with the time it arrived, for `replay`.
+    Record(PathBuf),
+    /// Play back a recording as an additional source, with the lines as far apart as when they
+    /// were recorded, divided by the speed.
+    Replay(PathBuf, f64),
+    /// Run the commands in a file, one command line per line. Blank lines and lines starting
+    /// with `#` are skipped.
+    Source(PathBuf),
+    /// Run a function from the scripts directory, and then the commands it returns.
+    Call(String),
+    /// Run a shell command and add its output to the session as an additional source.
+    Read(String),
+    /// Run a query over the fields of the lines in view, such as
+    /// `sql select level, count(*) group by level`.
+    Sql(String),
+    /// Move to a line, by its number in arrival order, as soon as it has arrived: `:42`.
+    Goto(u64),
+    /// Show where the line under the cursor is in its file, or write it to a file for an editor.
+    Location(Option<PathBuf>),
+    /// Write a quickfix list of the search matches in the view to a file for an editor.
+    Quickfix(PathBuf),
+    /// Keep only some of the lines from a chatty source, or all of them again when no policy is
+    /// given: `sample SOURCE 1/N`, `sample SOURCE N/s` or `sample SOURCE off`.
+    Sample(String, Option<Policy>),
+    /// Hide the lines from a source in the focused pane.
+    Hide(String),
+    /// Show the lines from a hidden source in the focused pane again, or from every source when
+    /// none is given.
+    Show(Option<String>),
+    /// Replace the filter. An empty filter shows every line.
+    Filter(String),
+    /// Apply the filter preset with the given name from the config file.
+    Preset(String),
+    /// Highlight a pattern in the next highlight group, or clear them all when none is given.
+    Highlight(Option<String>),
+    /// Count the lines matching a pattern in the status bar as they're read, or stop counting
+    /// them all when none is given.
+    Count(Option<String>),
+    /// Ring the bell and flash the status bar whenever a line matching a pattern is read, or stop
+    /// alerting to any when none is given.
+    Alert(Option<String>),
+    /// Search for a pattern, as if it had been typed in search mode.
+    Search(String),
+    /// Change a boolean display option, e.g. `set wrap`, `set nowrap` or `set wrap!`.
+    Set(String, Toggle),
+    /// Change a display option that takes a value, e.g. `set tabstop=4`.
+    SetValue(String, String),
+    /// Split the focused pane in two, one above the other or side by side.
+    Split { side_by_side: bool },
+    /// Close every pane but the focused one.
+    Only,
+    /// Lay lines out in columns split on the given delimiter.
+    Table(Delimiter),
+    /// Sort the focused pane's rows, or put them back in the order they arrived when no sort is
+    /// given: `sort number 3`, or `sort! time` for newest first.
+    Sort(Option<Sort>),
+    /// Show only some fields of each line, or whole lines again when no fields are given:
+    /// `fields 1,3,7-` or `fields 2 comma`.
+    Fields(Option<Fields>),
+    /// Save the lines passing the filter to a file.
+    Write(PathBuf, WriteMode),
+}
+
+/// What `:w` does when the file already exists.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum WriteMode {
+    /// Refuse, so a snapshot isn't lost by accident: `:w PATH`.
+    Create,
+    /// Replace the file: `:w! PATH`.
+    Overwrite,
+    /// Add to the end of the file: `:w >>PATH`.
+    Append,
+}
+
+#[derive(Copy, Clone)]
+pub enum Toggle {
+    On,
+    Off,
+    Flip,
+}
+
+impl Toggle {
+    pub fn apply(self, value: &mut bool) {
+        *value = match self {
+            Toggle::On => true,
+            Toggle::Off => false,
+            Toggle::Flip => !*value,
+        }
+    }
+}
+
+impl Command {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        // The shell command after `r !` is passed on as typed, quotes and all
+        let trimmed = input.trim_start();
+        for prefix in ["read", "r"] {
+            if let Some(command) = trimmed.strip_prefix(prefix).and_then(|rest| rest.trim_start().strip_prefix('!')) {
+                return match command.trim() {
+                    "" => Err("usage: r !COMMAND".to_string()),
+                    command => Ok(Command::Read(command.to_string())),
+                };
+            }
+        }
+
+        // So is the query after `sql`, which has quotes of its own
+        if let Some(query) = trimmed.strip_prefix("sql").filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace)) {
+            return match query.trim() {
+                "" => Err("usage: sql select ...".to_string()),
+                query => Ok(Command::Sql(query.to_string())),
+            };
+        }
+
+        if let Ok(line) = input.trim().parse() {
+            return Ok(Command::Goto(line));
+        }
+
+        // Patterns take the rest of the line, so any spaces in them are kept as typed
+        let (name, rest) = trimmed.split_once(char::is_whitespace).unwrap_or((trimmed, ""));
+        let pattern = || match rest.trim() {
+            "" => Ok(None),
+            rest => unquote(rest).map(Some),
+        };
+        match name {
+            "filter" => return Ok(Command::Filter(pattern()?.unwrap_or_default())),
+            "search" => return Ok(Command::Search(pattern()?.unwrap_or_default())),
+            "highlight" | "hi" => return Ok(Command::Highlight(pattern()?)),
+            "alert" => return Ok(Command::Alert(pattern()?)),
+            "count" => return Ok(Command::Count(pattern()?)),
+            _ => {}
+        }
+
+        let args = split_args(input)?;
+        let Some((name, args)) = args.split_first() else {
+            return Err("empty command".to_string());
+        };
+
+        match (name.as_str(), args) {
+            ("compare", []) => Ok(Command::Compare(None)),
+            ("compare", [a, b]) => Ok(Command::Compare(Some((a.clone(), b.clone())))),
+            ("compare", _) => Err("usage: compare PATTERN_A PATTERN_B".to_string()),
+            ("diff", []) => Ok(Command::Diff(None)),
+            ("diff", [a, b]) => Ok(Command::Diff(Some((a.clone(), b.clone())))),
+            ("diff", _) => Err("usage: diff SOURCE_A SOURCE_B".to_string()),
+            ("open" | "o", [path]) => Ok(Command::Open(PathBuf::from(path))),
+            ("open" | "o", _) => Err("usage: open PATH".to_string()),
+            ("record", [path]) => Ok(Command::Record(PathBuf::from(path))),
+            ("record", _) => Err("usage: record PATH".to_string()),
+            ("replay", [path]) => Ok(Command::Replay(PathBuf::from(path), 1.0)),
+            ("replay", [path, speed]) => match speed.strip_suffix('x').unwrap_or(speed).parse::<f64>() {
+                Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(Command::Replay(PathBuf::from(path), speed)),
+                _ => Err(format!("invalid speed '{}', expected e.g. 2x", speed)),
+            },
+            ("replay", _) => Err("usage: replay PATH [SPEED]".to_string()),
+            ("location" | "loc", []) => Ok(Command::Location(None)),
+            ("location" | "loc", [path]) => Ok(Command::Location(Some(PathBuf::from(path)))),
+            ("location" | "loc", _) => Err("usage: location [PATH]".to_string()),
+            ("quickfix" | "qf", [path]) => Ok(Command::Quickfix(PathBuf::from(path))),
+            ("quickfix" | "qf", _) => Err("usage: quickfix PATH".to_string()),
+            ("sample", [source, off]) if off == "off" => Ok(Command::Sample(source.clone(), None)),
+            ("sample", [source, spec]) => Ok(Command::Sample(source.clone(), Some(Policy::parse(spec)?))),
+            ("sample", _) => Err("usage: sample SOURCE 1/N, sample SOURCE N/s or sample SOURCE off".to_string()),
+            ("source" | "so", [path]) => Ok(Command::Source(PathBuf::from(path))),
+            ("source" | "so", _) => Err("usage: source PATH".to_string()),
+            ("call", [name]) => Ok(Command::Call(name.clone())),
+            ("call", _) => Err("usage: call FUNCTION".to_string()),
+            ("hide", [source]) => Ok(Command::Hide(source.clone())),
+            ("hide", _) => Err("usage: hide SOURCE".to_string()),
+            ("show", []) => Ok(Command::Show(None)),
+            ("show", [source]) => Ok(Command::Show(Some(source.clone()))),
+            ("show", _) => Err("usage: show [SOURCE]".to_string()),
+            ("preset", [name]) => Ok(Command::Preset(name.clone())),
+            ("preset", _) => Err("usage: preset NAME".to_string()),
+            ("set", [option]) if option.contains('=') => {
+                let (option, value) = option.split_once('=').unwrap_or_default();
+                Ok(Command::SetValue(option.to_string(), value.to_string()))
+            }
+            ("set", [option]) => {
+                let (option, toggle) = if let Some(option) = option.strip_suffix('!') {
+                    (option, Toggle::Flip)
+                } else if let Some(option) = option.strip_prefix("no") {
+                    (option, Toggle::Off)
+                } else {
+                    (option.as_str(), Toggle::On)
+                };
+                Ok(Command::Set(option.to_string(), toggle))
+            }
+            ("split" | "sp", []) => Ok(Command::Split { side_by_side: false }),
+            ("vsplit" | "vs", []) => Ok(Command::Split { side_by_side: true }),
+            ("only", []) => Ok(Command::Only),
+            ("table", [delimiter]) => Ok(Command::Table(Delimiter::parse(delimiter)?)),
+            ("table", _) => Err("usage: table DELIMITER".to_string()),
+            ("sort" | "sort!", [off]) if off == "off" => Ok(Command::Sort(None)),
+            ("sort" | "sort!", args) => Ok(Command::Sort(Some(Sort::parse(args, name.ends_with('!'))?))),
+            ("fields", []) => Ok(Command::Fields(None)),
+            ("fields", [list]) => Ok(Command::Fields(Some(Fields::parse(list, Delimiter::Whitespace)?))),
+            ("fields", [list, delimiter]) => Ok(Command::Fields(Some(Fields::parse(list, Delimiter::parse(delimiter)?)?))),
+            ("fields", _) => Err("usage: fields [LIST [DELIMITER]]".to_string()),
+            ("w" | "write", [append, path]) if append == ">>" => Ok(Command::Write(PathBuf::from(path), WriteMode::Append)),
+            ("w" | "write", [path]) => Ok(match path.strip_prefix(">>") {
+                Some(path) => Command::Write(PathBuf::from(path), WriteMode::Append),
+                None => Command::Write(PathBuf::from(path), WriteMode::Create),
+            }),
+            ("w!" | "write!", [path]) => Ok(Command::Write(PathBuf::from(path), WriteMode::Overwrite)),
+            ("w" | "write" | "w!" | "write!", _) => Err("usage: w PATH, w! PATH or w >>PATH".to_string()),
+            ("set", _) => Err("usage: set OPTION, set noOPTION, set OPTION! or set OPTION=VALUE".to_string()),
+            _ => Err(format!("unknown command: {}", name)),
+        }
+    }
+}
+
+/// Split a line holding several commands separated by `;` into the individual commands.
+/// Semicolons inside quotes or escaped with a backslash don't separate commands.
+pub fn split_commands(input: &str) -> Vec<&str> {
+    let mut commands = Vec::new();
+    let mut start = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    for (idx, c) in input.char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (_, '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (None, '\'' | '"') => quote = Some(c),
+            (None, ';') => {
+                commands.push(&input[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    commands.push(&input[start..]);
+    commands.into_iter().filter(|c| !c.trim().is_empty()).collect()
+}
+
+/// Split a command line into words on whitespace, honouring single and double quotes so
+/// arguments can contain spaces. A backslash escapes a quote, whitespace or `;` after it, and is
+/// otherwise kept, so regular expressions such as `5\d\d` come through as they were typed.
+fn split_args(input: &str) -> Result<Vec<String>, String> {
+    words(input, true)
+}
+
+/// Take the whole of `input` as one argument, with quotes and escapes dealt with as for
+/// `split_args` but whitespace kept.
+fn unquote(input: &str) -> Result<String, String> {
+    Ok(words(input, false)?.concat())
+}
+
+fn words(input: &str, split_on_whitespace: bool) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    let mut chars = input.chars().peekable();
+    let escapable = |c: &char| matches!(c, '\'' | '"' | ';') || c.is_whitespace();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (Some('\''), c) => current.push(c),
+            (_, '\\') if chars.peek().is_some_and(escapable) => {
+                current.extend(chars.next());
+                in_word = true;
+            }
+            (None, c) if split_on_whitespace && c.is_whitespace() => {
+                if in_word {
+                    args.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            (_, c) => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err("unterminated quote".to_string());
+    }
+    if in_word {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The pattern a command that takes one was given.
+    fn pattern(input: &str) -> Option<String> {
+        match Command::parse(input) {
+            Ok(Command::Filter(pattern) | Command::Search(pattern)) => Some(pattern),
+            Ok(Command::Highlight(pattern) | Command::Alert(pattern) | Command::Count(pattern)) => pattern,
+            Ok(_) => panic!("{} isn't a command taking a pattern", input),
+            Err(e) => panic!("{} didn't parse: {}", input, e),
+        }
+    }
+
+    #[test]
+    fn backslashes_in_regular_expressions_are_kept() {
+        assert_eq!(pattern(r"filter /status=5\d\d").as_deref(), Some(r"/status=5\d\d"));
+        assert_eq!(pattern(r"count /\bERROR\b").as_deref(), Some(r"/\bERROR\b"));
+        assert_eq!(pattern(r"search /C:\\Windows").as_deref(), Some(r"/C:\\Windows"));
+        assert_eq!(pattern(r"hi '\w+'").as_deref(), Some(r"\w+"));
+    }
+
+    #[test]
+    fn spaces_in_patterns_are_kept() {
+        assert_eq!(pattern("filter a  b").as_deref(), Some("a  b"));
+        assert_eq!(pattern("alert   disk   full  ").as_deref(), Some("disk   full"));
+        assert_eq!(pattern(r#"search "  padded ""#).as_deref(), Some("  padded "));
+    }
+
+    #[test]
+    fn backslashes_escape_quotes_spaces_and_semicolons() {
+        assert_eq!(pattern(r#"filter say \"hi\""#).as_deref(), Some(r#"say "hi""#));
+        assert_eq!(pattern(r#"search "a \" b""#).as_deref(), Some(r#"a " b"#));
+        assert_eq!(pattern(r"filter it\'s").as_deref(), Some("it's"));
+        assert_eq!(split_args(r"open my\ file.log").unwrap(), ["open", "my file.log"]);
+        assert_eq!(split_args(r"filter a\;b").unwrap(), ["filter", "a;b"]);
+    }
+
+    #[test]
+    fn single_quotes_keep_everything_inside() {
+        assert_eq!(split_args(r"set 'a\ b'").unwrap(), ["set", r"a\ b"]);
+    }
+
+    #[test]
+    fn missing_patterns_clear() {
+        assert_eq!(pattern("filter"), Some(String::new()));
+        assert_eq!(pattern("count "), None);
+        assert_eq!(pattern("hi ''").as_deref(), Some(""));
+    }
+
+    #[test]
+    fn set_values_keep_backslashes() {
+        match Command::parse(r"set filter=/\d+ms") {
+            Ok(Command::SetValue(option, value)) => assert_eq!((option.as_str(), value.as_str()), ("filter", r"/\d+ms")),
+            _ => panic!("expected set filter=VALUE"),
+        }
+    }
+
+    #[test]
+    fn unterminated_quotes_fail() {
+        assert!(Command::parse(r#"filter "abc"#).is_err());
+        assert!(split_args("open 'abc").is_err());
+    }
+
+    #[test]
+    fn escaped_semicolons_stay_in_one_command() {
+        let commands = split_commands(r"filter a\;b; search c");
+        assert_eq!(commands, [r"filter a\;b", " search c"]);
+        assert_eq!(pattern(commands[0]).as_deref(), Some("a;b"));
+    }
+}
@@ -0,0 +1,126 @@
+use std::io;
+use std::path::Path;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot};
+
+/// A request read from the control socket, answered between frames by the UI.
+pub struct Request {
+    pub query: Query,
+    pub reply: oneshot::Sender<Reply>,
+}
+
+/// The lines of a successful answer, or what went wrong.
+pub type Reply = Result<Vec<String>, String>;
+
+/// What a client of the control socket can ask for, one per line.
+pub enum Query {
+    /// `get counts`: how many lines there are, pass the filter, are marked and match the search.
+    Counts,
+    /// `get state`: the mode, filter, search and position of the focused pane.
+    State,
+    /// `get location`: where the line under the cursor is, as `file:line:column`.
+    Location,
+    /// `get quickfix`: the search matches in the view, in quickfix format.
+    Quickfix,
+    /// `export selection`: the lines that would be printed on exit.
+    Export,
+    /// Anything else is run as if entered on the `:` command line, e.g. `filter error`.
+    /// `set filter X` and `set search X` are accepted too.
+    Command(String),
+}
+
+impl Query {
+    fn parse(input: &str) -> Self {
+        let input = input.trim();
+        match input {
+            "get counts" => Query::Counts,
+            "get state" => Query::State,
+            "get location" => Query::Location,
+            "get quickfix" => Query::Quickfix,
+            "export selection" => Query::Export,
+            _ => match input.strip_prefix("set ").map(str::trim_start) {
+                Some(rest) if rest.starts_with("filter ") || rest.starts_with("search ") => {
+                    Query::Command(rest.to_string())
+                }
+                _ => Query::Command(input.to_string()),
+            },
+        }
+    }
+}
+
+/// Listen on a Unix socket at `path` so other programs can drive carve, returning the requests
+/// as they arrive. A stale socket left by an earlier session is replaced.
+///
+/// Each reply starts with `ok N`, followed by N lines of answer, or is a single `error MESSAGE`
+/// line.
+#[cfg(unix)]
+pub fn listen(path: &Path) -> io::Result<mpsc::UnboundedReceiver<Request>> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        std::fs::remove_file(path)?;
+    }
+    let listener = tokio::net::UnixListener::bind(path)?;
+    let (tx, rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(serve(stream, tx.clone()));
+                }
+                Err(e) => break tracing::warn!("control socket failed: {}", e),
+            }
+        }
+    });
+    Ok(rx)
+}
+
+#[cfg(not(unix))]
+pub fn listen(_path: &Path) -> io::Result<mpsc::UnboundedReceiver<Request>> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "control sockets need a Unix-like system"))
+}
+
+/// Answer the requests of one client until it disconnects.
+#[cfg(unix)]
+async fn serve(stream: tokio::net::UnixStream, tx: mpsc::UnboundedSender<Request>) {
+    let (read, mut write) = stream.into_split();
+    let mut reader = BufReader::new(read);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        if !matches!(reader.read_until(b'\n', &mut buf).await, Ok(n) if n > 0) {
+            return;
+        }
+        // Lossily, so a stray byte that isn't UTF-8 spoils one query rather than ending the
+        // connection
+        let line = String::from_utf8_lossy(&buf);
+        let line = line.trim_end_matches(['\n', '\r']);
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let request = Request {
+            query: Query::parse(line),
+            reply: reply_tx,
+        };
+        if tx.send(request).is_err() {
+            return;
+        }
+        let response = match reply_rx.await {
+            Ok(Ok(lines)) => {
+                let mut response = format!("ok {}\n", lines.len());
+                for line in lines {
+                    response.push_str(&line);
+                    response.push('\n');
+                }
+                response
+            }
+            Ok(Err(e)) => format!("error {}\n", e.replace('\n', " ")),
+            Err(_) => return,
+        };
+        if write.write_all(response.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use crate::line::Line;
+use crate::pane;
+
+use crate::app::rate::Rate;
+
+/// A pattern whose lines are counted as they're read, written like a filter.
+struct Counter {
+    pattern: String,
+    total: u64,
+    rate: Rate,
+}
+
+/// Live counts of the lines matching some patterns, such as `ERROR` and `timeout`, shown in the
+/// status bar to watch how often something happens during a deploy.
+///
+/// Every line read after a counter is added is counted, whatever the filter, and lines evicted
+/// from the buffer stay counted.
+#[derive(Default)]
+pub struct Counters {
+    counters: Vec<Counter>,
+}
+
+impl Counters {
+    /// Start counting the lines matching a pattern, which may be a `/` regular expression, a `~`
+    /// fuzzy pattern or `!` excluded like a filter. Adding a pattern again does nothing.
+    pub fn add(&mut self, pattern: String) -> Result<(), String> {
+        pane::check_filter(&pattern)?;
+        if !self.counters.iter().any(|counter| counter.pattern == pattern) {
+            self.counters.push(Counter { pattern, total: 0, rate: Rate::new() });
+        }
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.counters.clear();
+    }
+
+    /// Count a line that has just been read.
+    pub fn record(&mut self, line: &Line) {
+        for counter in &mut self.counters {
+            if pane::passes_filter(&counter.pattern, line, None) {
+                counter.total += 1;
+                counter.rate.record(1);
+            }
+        }
+    }
+
+    /// Roll each counter's rate forward, whether or not any lines were read.
+    pub fn tick(&mut self) {
+        for counter in &mut self.counters {
+            counter.rate.record(0);
+        }
+    }
+
+    /// Each pattern with its count so far and how many matched in the last second, as in
+    /// `ERROR 42 (3/s)`, or nothing without any counters.
+    pub fn describe(&self) -> String {
+        self.counters
+            .iter()
+            .map(|counter| format!("{} {} ({}/s)", counter.pattern, counter.total, counter.rate.per_second()))
+            .collect::<Vec<_>>()
+            .join("  ")
+    }
+}
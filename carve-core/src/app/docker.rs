@@ -0,0 +1,209 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, FixedOffset};
+use tokio::io::{AsyncRead, AsyncWriteExt, BufReader};
+use crate::decode::Decoder;
+
+use crate::app::http::{self, FIRST_BACKOFF, MAX_BACKOFF};
+use crate::app::source::Sink;
+
+/// Where the Docker daemon listens unless `DOCKER_HOST` says otherwise.
+const DEFAULT_HOST: &str = "unix:///var/run/docker.sock";
+
+/// How often a stopped container is checked to see whether it has started again.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Spawn an async task that follows a container's logs through the Docker API, adding what it
+/// writes to stdout and stderr to separate sources.
+///
+/// When the container stops its logs are followed again once it restarts, carrying on after
+/// the last line shown. If the daemon can't be reached, or the container doesn't exist, it's
+/// tried again with backoff, as for `--url`.
+pub fn spawn(container: String, sink: Sink, stdout: usize, stderr: usize) {
+    tokio::spawn(async move {
+        let mut logs = Logs::new(sink, [stdout, stderr]);
+        let mut backoff = FIRST_BACKOFF;
+        loop {
+            let started = Instant::now();
+            let result = follow(&container, &mut logs).await;
+            logs.finish();
+            if let Err(e) = result {
+                if started.elapsed() > MAX_BACKOFF {
+                    backoff = FIRST_BACKOFF;
+                }
+                tracing::debug!(container, "{}", e);
+                logs.divider(format!("{}: {}, retrying in {}s", container, e, backoff.as_secs()));
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                continue;
+            }
+            backoff = FIRST_BACKOFF;
+            logs.divider(format!("{} stopped, waiting for it to start", container));
+            loop {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                if is_running(&container).await.unwrap_or(false) {
+                    break;
+                }
+            }
+            logs.divider(format!("{} started", container));
+        }
+    });
+}
+
+/// Read the container's logs until it stops, from after the last line shown if any.
+async fn follow(container: &str, logs: &mut Logs) -> io::Result<()> {
+    let mut path = format!("/containers/{}/logs?follow=1&stdout=1&stderr=1&timestamps=1", container);
+    if let Some(last) = logs.last {
+        path.push_str(&format!("&since={}.{:09}", last.timestamp(), last.timestamp_subsec_nanos()));
+    }
+    logs.since = logs.last;
+    let (response, mut reader) = get(&path).await?;
+    http::read_body(&mut reader, response.chunked, |bytes| logs.feed(bytes)).await
+}
+
+/// Whether the container is running, from the state in its details.
+async fn is_running(container: &str) -> io::Result<bool> {
+    let (response, mut reader) = get(&format!("/containers/{}/json", container)).await?;
+    let mut details = Vec::new();
+    http::read_body(&mut reader, response.chunked, |bytes| details.extend_from_slice(bytes)).await?;
+    let details = String::from_utf8_lossy(&details);
+    Ok(details.contains(r#""Running":true"#))
+}
+
+/// Make a request to the Docker API, returning the head of the response, which must be
+/// successful, and a reader for its body.
+async fn get(path: &str) -> io::Result<(http::Response, BufReader<Box<dyn AsyncRead + Unpin + Send>>)> {
+    let host = std::env::var("DOCKER_HOST").unwrap_or_else(|_| DEFAULT_HOST.to_string());
+    let request = format!("GET {} HTTP/1.1\r\nHost: docker\r\nConnection: close\r\n\r\n", path);
+    let stream: Box<dyn AsyncRead + Unpin + Send> = if let Some(address) = host.strip_prefix("tcp://") {
+        let mut stream = tokio::net::TcpStream::connect(address).await?;
+        stream.write_all(request.as_bytes()).await?;
+        Box::new(stream)
+    } else if let Some(path) = host.strip_prefix("unix://") {
+        connect_unix(path, &request).await?
+    } else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("DOCKER_HOST {} isn't a unix:// or tcp:// address", host),
+        ));
+    };
+    let mut reader = BufReader::new(stream);
+    let response = http::read_head(&mut reader).await?;
+    Ok((response, reader))
+}
+
+#[cfg(unix)]
+async fn connect_unix(path: &str, request: &str) -> io::Result<Box<dyn AsyncRead + Unpin + Send>> {
+    let mut stream = tokio::net::UnixStream::connect(path)
+        .await
+        .map_err(|e| io::Error::new(e.kind(), format!("failed to connect to {}: {}", path, e)))?;
+    stream.write_all(request.as_bytes()).await?;
+    Ok(Box::new(stream))
+}
+
+#[cfg(not(unix))]
+async fn connect_unix(_path: &str, _request: &str) -> io::Result<Box<dyn AsyncRead + Unpin + Send>> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "unix:// addresses need a Unix-like system"))
+}
+
+/// Turns the logs the Docker API sends into lines for the stdout and stderr sources.
+///
+/// Unless the container has a TTY, the logs are multiplexed: each frame starts with a byte
+/// saying which stream it's from, three zero bytes, then its length as 32 bits big endian.
+/// Since every line starts with its timestamp, logs from a TTY never start with such a byte.
+struct Logs {
+    sink: Sink,
+    sources: [usize; 2], // The sources for stdout and stderr
+    decoders: [Decoder; 2],
+    multiplexed: Option<bool>, // Whether the logs are multiplexed, once they've started
+    header: [u8; 8],
+    filled: usize, // How much of the header of the next frame has been read
+    stream: usize, // Which stream the current frame is from
+    remaining: usize, // How much of the current frame is yet to be read
+    since: Option<DateTime<FixedOffset>>, // Lines up to this time were shown before a restart
+    last: Option<DateTime<FixedOffset>>, // The time of the last line shown
+}
+
+impl Logs {
+    fn new(sink: Sink, sources: [usize; 2]) -> Self {
+        Self {
+            sink,
+            sources,
+            decoders: Default::default(),
+            multiplexed: None,
+            header: [0; 8],
+            filled: 0,
+            stream: 0,
+            remaining: 0,
+            since: None,
+            last: None,
+        }
+    }
+
+    fn feed(&mut self, mut bytes: &[u8]) {
+        let multiplexed = *self.multiplexed.get_or_insert_with(|| bytes.first().is_some_and(|&b| b <= 2));
+        if !multiplexed {
+            return self.data(0, bytes);
+        }
+        while !bytes.is_empty() {
+            if self.remaining == 0 {
+                let take = (self.header.len() - self.filled).min(bytes.len());
+                self.header[self.filled..self.filled + take].copy_from_slice(&bytes[..take]);
+                self.filled += take;
+                bytes = &bytes[take..];
+                if self.filled == self.header.len() {
+                    self.stream = if self.header[0] == 2 { 1 } else { 0 };
+                    self.remaining = u32::from_be_bytes([self.header[4], self.header[5], self.header[6], self.header[7]]) as usize;
+                    self.filled = 0;
+                }
+                continue;
+            }
+            let take = self.remaining.min(bytes.len());
+            self.data(self.stream, &bytes[..take]);
+            self.remaining -= take;
+            bytes = &bytes[take..];
+        }
+    }
+
+    fn data(&mut self, stream: usize, bytes: &[u8]) {
+        for line in self.decoders[stream].feed(bytes) {
+            self.line(stream, line);
+        }
+    }
+
+    /// Add a line without its timestamp, unless it was shown before the container restarted.
+    fn line(&mut self, stream: usize, line: String) {
+        let (time, text) = match line.split_once(' ') {
+            Some((time, text)) => match DateTime::parse_from_rfc3339(time) {
+                Ok(time) => (Some(time), text.to_string()),
+                Err(_) => (None, line),
+            },
+            None => (None, line),
+        };
+        if let Some(time) = time {
+            if self.since.is_some_and(|since| time <= since) {
+                return;
+            }
+            self.last = Some(time);
+        }
+        self.sink.push(text, self.sources[stream]);
+    }
+
+    /// Add any partial lines left when the logs end, and get ready to read them again.
+    fn finish(&mut self) {
+        for stream in 0..self.decoders.len() {
+            if let Some(line) = self.decoders[stream].finish() {
+                self.line(stream, line);
+            }
+        }
+        self.multiplexed = None;
+        self.filled = 0;
+        self.remaining = 0;
+    }
+
+    /// Add a divider line, which goes with stdout.
+    fn divider(&self, text: String) {
+        self.sink.push(format!("──── {} ────", text), self.sources[0]);
+    }
+}
@@ -0,0 +1,183 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::app::hyperlink;
+
+/// Retention policy for a continuous export.
+pub struct ExportOptions {
+    pub path: PathBuf,
+    pub max_size: Option<u64>,
+    pub max_lines: Option<u64>,
+    /// How many rotated files to keep. Zero means the export is simply truncated on rotation.
+    pub max_files: usize,
+    pub gzip: bool,
+    pub hyperlinks: bool,
+    /// Put the time each line arrived in front of it, as `--annotate time` does.
+    pub timestamps: bool,
+}
+
+/// Appends every ingested line to a file, rotating it out as `<path>.1`, `<path>.2`, ... once the
+/// configured size or line limits are hit so a long-lived capture can't fill the disk.
+pub struct Exporter {
+    opts: ExportOptions,
+    writer: BufWriter<File>,
+    size: u64,
+    lines: u64,
+    // The first write error encountered. Once set the exporter stops writing so we don't spam the
+    // disk (or the user) with the same failure for every line.
+    error: Option<io::Error>,
+    // Set once the export is finished, after which lines still arriving from sources aren't
+    // written, so the export ends with the last line carve took in.
+    finished: bool,
+}
+
+impl Exporter {
+    pub fn open(opts: ExportOptions) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&opts.path)?;
+        let size = file.metadata()?.len();
+        // Only pay for counting existing lines when a line limit is actually in effect.
+        let lines = match opts.max_lines {
+            Some(_) if size > 0 => count_lines(&opts.path)?,
+            _ => 0,
+        };
+        Ok(Self {
+            opts,
+            writer: BufWriter::new(file),
+            size,
+            lines,
+            error: None,
+            finished: false,
+        })
+    }
+
+    pub fn write_line(&mut self, line: &str) {
+        if self.error.is_some() || self.finished {
+            return;
+        }
+        let line = if self.opts.hyperlinks { hyperlink::linkify(line) } else { line.into() };
+        let line = if self.opts.timestamps {
+            let now = chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, false);
+            format!("{}\t{}", now, line).into()
+        } else {
+            line
+        };
+        if let Err(e) = self.try_write_line(&line) {
+            self.error = Some(e);
+        }
+    }
+
+    fn try_write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        let size_exceeded = self
+            .opts
+            .max_size
+            .is_some_and(|max| self.size > 0 && self.size + len > max);
+        let lines_exceeded = self.opts.max_lines.is_some_and(|max| self.lines >= max);
+        if size_exceeded || lines_exceeded {
+            self.rotate()?;
+        }
+
+        writeln!(self.writer, "{}", line)?;
+        self.size += len;
+        self.lines += 1;
+        Ok(())
+    }
+
+    /// Write out the lines buffered so far, so the export keeps up with what's been read. Sources
+    /// call this once per batch of lines rather than after every line.
+    pub fn flush(&mut self) {
+        if self.error.is_some() || self.finished {
+            return;
+        }
+        if let Err(e) = self.writer.flush() {
+            self.error = Some(e);
+        }
+    }
+
+    /// The first error hit while exporting, if any, which `finish` reports.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    /// Flush any buffered output and stop writing lines, returning the first error hit while
+    /// exporting, if any.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.finished = true;
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        self.writer.flush()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+
+        if self.opts.max_files > 0 {
+            // Shift existing archives up by one, dropping whichever falls off the end.
+            for idx in (1..=self.opts.max_files).rev() {
+                for gz in [false, true] {
+                    let from = self.rotated_path(idx, gz);
+                    if !from.exists() {
+                        continue;
+                    }
+                    if idx == self.opts.max_files {
+                        fs::remove_file(&from)?;
+                    } else {
+                        fs::rename(&from, self.rotated_path(idx + 1, gz))?;
+                    }
+                }
+            }
+
+            let first = self.rotated_path(1, false);
+            fs::rename(&self.opts.path, &first)?;
+            if self.opts.gzip {
+                gzip_file(&first, &self.rotated_path(1, true))?;
+                fs::remove_file(&first)?;
+            }
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.opts.path)?;
+        self.writer = BufWriter::new(file);
+        self.size = 0;
+        self.lines = 0;
+        Ok(())
+    }
+
+    fn rotated_path(&self, idx: usize, gz: bool) -> PathBuf {
+        let mut path = self.opts.path.clone().into_os_string();
+        path.push(format!(".{}", idx));
+        if gz {
+            path.push(".gz");
+        }
+        path.into()
+    }
+}
+
+fn count_lines(path: &Path) -> io::Result<u64> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut count = 0;
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(count);
+        }
+        count += buf.iter().filter(|b| **b == b'\n').count() as u64;
+        let len = buf.len();
+        reader.consume(len);
+    }
+}
+
+fn gzip_file(from: &Path, to: &Path) -> io::Result<()> {
+    let mut input = File::open(from)?;
+    let mut encoder = GzEncoder::new(File::create(to)?, Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?.sync_all()
+}
@@ -0,0 +1,45 @@
+/// How many highlight groups can be shown at once.
+pub const MAX_GROUPS: usize = 3;
+
+/// Numbered patterns highlighted wherever they appear, each in its own colour, for following a
+/// few IDs or keywords through a log at once.
+#[derive(Default)]
+pub struct Groups {
+    patterns: [Option<String>; MAX_GROUPS],
+    oldest: usize, // The group replaced when adding to a full set
+}
+
+impl Groups {
+    /// Highlight a pattern in the first free group, or in place of the oldest if they're all in
+    /// use. Returns the group's number, counting from 1.
+    pub fn add(&mut self, pattern: String) -> usize {
+        let slot = match self.patterns.iter().position(Option::is_none) {
+            Some(slot) => slot,
+            None => {
+                let slot = self.oldest;
+                self.oldest = (self.oldest + 1) % MAX_GROUPS;
+                slot
+            }
+        };
+        self.patterns[slot] = Some(pattern);
+        slot + 1
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// The byte ranges of each group's matches in `text`, by the group's slot, for the groups in
+    /// use. Later groups should win where groups overlap.
+    pub fn matches(&self, text: &str) -> Vec<(usize, Vec<(usize, usize)>)> {
+        self.patterns
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, pattern)| Some((slot, pattern.as_ref()?)))
+            .map(|(slot, pattern)| {
+                let ranges = text.match_indices(pattern.as_str()).map(|(start, m)| (start, start + m.len())).collect();
+                (slot, ranges)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,133 @@
+use std::fs::OpenOptions;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::level::{self, Level};
+
+/// The profile sessions without `--profile` are recorded under.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// A summary of one session, kept in the history file so the next run of the same job can be
+/// compared against it.
+pub struct Session {
+    pub profile: String,
+    pub started: chrono::DateTime<chrono::Local>,
+    pub seconds: u64,
+    pub lines: u64,
+    pub counts: [u64; Level::ALL.len()],
+    pub unknown: u64, // Lines with no recognisable level
+    pub peak_rate: u64, // Most lines read in one second
+}
+
+impl Session {
+    /// A line of the history file: tab separated, in the order of the fields.
+    fn to_line(&self) -> String {
+        let mut fields = vec![
+            self.profile.clone(),
+            self.started.to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+            self.seconds.to_string(),
+            self.lines.to_string(),
+        ];
+        fields.extend(self.counts.iter().map(u64::to_string));
+        fields.push(self.unknown.to_string());
+        fields.push(self.peak_rate.to_string());
+        fields.join("\t")
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [profile, started, seconds, lines, rest @ ..] = &fields[..] else {
+            return None;
+        };
+        let numbers: Vec<u64> = rest.iter().map(|field| field.parse().ok()).collect::<Option<_>>()?;
+        let [error, warn, info, debug, trace, unknown, peak_rate] = numbers[..] else {
+            return None;
+        };
+        Some(Self {
+            profile: profile.to_string(),
+            started: chrono::DateTime::parse_from_rfc3339(started).ok()?.with_timezone(&chrono::Local),
+            seconds: seconds.parse().ok()?,
+            lines: lines.parse().ok()?,
+            counts: [error, warn, info, debug, trace],
+            unknown,
+            peak_rate,
+        })
+    }
+}
+
+/// Running totals for the session in progress, counted as lines are read whatever the buffer
+/// keeps.
+pub struct Tally {
+    pub profile: String,
+    pub lines: u64,
+    pub counts: [u64; Level::ALL.len()],
+    pub unknown: u64,
+    pub peak_rate: u64,
+    started: chrono::DateTime<chrono::Local>,
+    clock: Instant,
+}
+
+impl Tally {
+    pub fn new(profile: String) -> Self {
+        Self {
+            profile,
+            lines: 0,
+            counts: [0; Level::ALL.len()],
+            unknown: 0,
+            peak_rate: 0,
+            started: chrono::Local::now(),
+            clock: Instant::now(),
+        }
+    }
+
+    pub fn record(&mut self, text: &str) {
+        self.lines += 1;
+        match level::detect(text) {
+            Some(level) => self.counts[level as usize] += 1,
+            None => self.unknown += 1,
+        }
+    }
+
+    pub fn finish(&self) -> Session {
+        Session {
+            profile: self.profile.clone(),
+            started: self.started,
+            seconds: self.clock.elapsed().as_secs(),
+            lines: self.lines,
+            counts: self.counts,
+            unknown: self.unknown,
+            peak_rate: self.peak_rate,
+        }
+    }
+}
+
+/// Where session history is kept: `carve/history.tsv` under `$XDG_STATE_HOME` or
+/// `~/.local/state`.
+pub fn path() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state")))?;
+    Some(dir.join("carve").join("history.tsv"))
+}
+
+/// The most recent session recorded for a profile. Lines that can't be read, say from a newer
+/// version of carve, are skipped.
+pub fn last(path: &Path, profile: &str) -> Option<Session> {
+    let file = std::fs::File::open(path).ok()?;
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| Session::from_line(&line))
+        .filter(|session| session.profile == profile)
+        .last()
+}
+
+/// Add a session to the end of the history file, creating it if needed.
+pub fn append(path: &Path, session: &Session) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", session.to_line())
+}
@@ -0,0 +1,433 @@
+use std::io;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use crate::decode::Decoder;
+
+use crate::app::source::Sink;
+
+/// How long to wait before the first attempt to reconnect. Each failed attempt doubles it, up
+/// to `MAX_BACKOFF`.
+pub const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+pub const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// How much of the body is read at a time.
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// Spawn an async task that follows a streaming HTTP endpoint, such as a log tail served with
+/// chunked encoding or as Server-Sent Events, reconnecting with backoff whenever it drops.
+///
+/// `http://` URLs are fetched directly. `https://` URLs are fetched with `curl`, which needs to
+/// be installed.
+pub fn spawn(url: String, sink: Sink, source: usize) {
+    tokio::spawn(async move {
+        let mut backoff = FIRST_BACKOFF;
+        let mut body = Body::default();
+        loop {
+            let started = Instant::now();
+            let why = match stream(&url, &mut body, &sink, source).await {
+                Ok(()) => "stream ended".to_string(),
+                Err(e) => e.to_string(),
+            };
+            body.finish(&sink, source);
+            // A stream that stayed up for a while is reconnected to promptly
+            if started.elapsed() > MAX_BACKOFF {
+                backoff = FIRST_BACKOFF;
+            }
+            tracing::debug!(url, "{}", why);
+            sink.push(format!("──── {}: {}, reconnecting in {}s ────", url, why, backoff.as_secs()), source);
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    });
+}
+
+/// Connect to `url` and read its body until the connection closes.
+async fn stream(url: &str, body: &mut Body, sink: &Sink, source: usize) -> io::Result<()> {
+    if let Some(rest) = url.strip_prefix("http://") {
+        let target = Target::parse(rest, 80);
+        let mut stream = tokio::net::TcpStream::connect(&target.address).await?;
+        let mut request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nAccept: text/event-stream, */*\r\nConnection: close\r\n",
+            target.path, target.authority
+        );
+        if let Some(id) = &body.last_event_id {
+            request.push_str(&format!("Last-Event-ID: {}\r\n", id));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).await?;
+        let mut reader = BufReader::new(stream);
+        let response = read_head(&mut reader).await?;
+        body.start(&response);
+        read_body(&mut reader, response.chunked, |bytes| body.feed(bytes, sink, source)).await
+    } else if url.starts_with("https://") {
+        let mut command = tokio::process::Command::new("curl");
+        command.args(["--silent", "--show-error", "--no-buffer", "--include"]);
+        command.args(["--header", "Accept: text/event-stream, */*"]);
+        if let Some(id) = &body.last_event_id {
+            command.arg("--header").arg(format!("Last-Event-ID: {}", id));
+        }
+        let mut child = command
+            .arg("--")
+            .arg(url)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("failed to run curl: {}", e)))?;
+        let mut reader = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        // curl decodes chunked bodies itself, but passes the head through with `--include`
+        let result = match read_head(&mut reader).await {
+            Ok(response) => {
+                body.start(&response);
+                read_body(&mut reader, false, |bytes| body.feed(bytes, sink, source)).await
+            }
+            Err(e) => Err(e),
+        };
+        // curl may still be running, such as after an error response, and its stderr only ends
+        // once it exits
+        if result.is_err() {
+            let _ = child.kill().await;
+        }
+        let mut errors = String::new();
+        if let Some(mut stderr) = child.stderr.take() {
+            let _ = stderr.read_to_string(&mut errors).await;
+        }
+        if !errors.trim().is_empty() {
+            return Err(io::Error::other(errors.trim().to_string()));
+        }
+        result
+    } else {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, "only http:// and https:// URLs can be followed"))
+    }
+}
+
+/// Where a URL points, from the part after its scheme.
+pub struct Target {
+    pub authority: String, // The host and port, if given, as sent in the `Host` header
+    pub host: String,
+    pub address: String, // The host and port to connect to
+    pub path: String,
+}
+
+impl Target {
+    pub fn parse(rest: &str, default_port: u16) -> Self {
+        let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        let (host, address) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) => {
+                (host, authority.to_string())
+            }
+            _ => (authority, format!("{}:{}", authority, default_port)),
+        };
+        Self {
+            authority: authority.to_string(),
+            host: host.to_string(),
+            address,
+            path: if path.is_empty() { "/".to_string() } else { path.to_string() },
+        }
+    }
+}
+
+/// What the head of a response says about its body.
+pub struct Response {
+    pub chunked: bool,
+    event_stream: bool,
+}
+
+/// Read the status line and headers of a response, failing unless it was successful.
+/// Informational responses, such as `100 Continue`, are skipped.
+pub async fn read_head(reader: &mut (impl AsyncBufRead + Unpin)) -> io::Result<Response> {
+    loop {
+        let mut status = String::new();
+        if reader.read_line(&mut status).await? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed without a response"));
+        }
+        let code = status.split_whitespace().nth(1).and_then(|code| code.parse::<u16>().ok());
+        let mut response = Response {
+            chunked: false,
+            event_stream: false,
+        };
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+                break;
+            }
+            let Some((name, value)) = header.split_once(':') else {
+                continue;
+            };
+            let value = value.trim().to_ascii_lowercase();
+            if name.eq_ignore_ascii_case("transfer-encoding") {
+                response.chunked = value.contains("chunked");
+            } else if name.eq_ignore_ascii_case("content-type") {
+                response.event_stream = value.starts_with("text/event-stream");
+            }
+        }
+        match code {
+            Some(100..=199) => continue,
+            Some(200..=299) => return Ok(response),
+            _ => return Err(io::Error::other(format!("server responded {}", status.trim()))),
+        }
+    }
+}
+
+/// Read a body until it ends, as it arrives, undoing chunked transfer encoding if used.
+pub async fn read_body(
+    reader: &mut (impl AsyncBufRead + Unpin),
+    chunked: bool,
+    mut on_data: impl FnMut(&[u8]),
+) -> io::Result<()> {
+    let mut chunk = vec![0; CHUNK_SIZE];
+    if !chunked {
+        loop {
+            let n = reader.read(&mut chunk).await?;
+            if n == 0 {
+                return Ok(());
+            }
+            on_data(&chunk[..n]);
+        }
+    }
+    loop {
+        let mut size = String::new();
+        if reader.read_line(&mut size).await? == 0 {
+            return Ok(());
+        }
+        let size = size.split(';').next().unwrap_or_default().trim();
+        let mut remaining = usize::from_str_radix(size, 16)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("bad chunk size '{}'", size)))?;
+        if remaining == 0 {
+            return Ok(());
+        }
+        while remaining > 0 {
+            let want = remaining.min(chunk.len());
+            let n = reader.read(&mut chunk[..want]).await?;
+            if n == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed mid-chunk"));
+            }
+            on_data(&chunk[..n]);
+            remaining -= n;
+        }
+        let mut end = String::new();
+        reader.read_line(&mut end).await?;
+    }
+}
+
+/// Turns the bytes of a body into lines: as they are for a plain stream, or the data of each
+/// event for Server-Sent Events.
+#[derive(Default)]
+struct Body {
+    decoder: Decoder,
+    event_stream: bool,
+    data: Vec<String>, // The data lines of the event being read
+    last_event_id: Option<String>, // Sent when reconnecting, so the server can resume from it
+}
+
+impl Body {
+    fn start(&mut self, response: &Response) {
+        self.decoder = Decoder::default();
+        self.event_stream = response.event_stream;
+        self.data.clear();
+    }
+
+    fn feed(&mut self, bytes: &[u8], sink: &Sink, source: usize) {
+        for line in self.decoder.feed(bytes) {
+            self.line(line, sink, source);
+        }
+    }
+
+    fn finish(&mut self, sink: &Sink, source: usize) {
+        if let Some(line) = self.decoder.finish() {
+            self.line(line, sink, source);
+        }
+        // An event is only complete once the blank line after it has arrived
+        self.data.clear();
+    }
+
+    fn line(&mut self, line: String, sink: &Sink, source: usize) {
+        if !self.event_stream {
+            return sink.push(line, source);
+        }
+        if line.is_empty() {
+            for data in self.data.drain(..) {
+                sink.push(data, source);
+            }
+            return;
+        }
+        let (field, value) = line.split_once(':').unwrap_or((&line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "data" => self.data.push(value.to_string()),
+            "id" if !value.contains('\0') => self.last_event_id = Some(value.to_string()),
+            // Comments, which servers send to keep the connection alive, event types and retry
+            // hints aren't shown
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::line::Line;
+
+    fn sink() -> (Sink, mpsc::UnboundedReceiver<Line>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (new_sources, _) = mpsc::unbounded_channel();
+        let sink = Sink { tx, exporter: None, next_source: Arc::new(AtomicUsize::new(1)), new_sources };
+        (sink, rx)
+    }
+
+    fn received(rx: &mut mpsc::UnboundedReceiver<Line>) -> Vec<String> {
+        std::iter::from_fn(|| rx.try_recv().ok()).map(|line| line.text).collect()
+    }
+
+    fn events() -> Response {
+        Response { chunked: false, event_stream: true }
+    }
+
+    async fn body(bytes: &[u8], chunked: bool) -> io::Result<String> {
+        let mut data = Vec::new();
+        read_body(&mut &bytes[..], chunked, |bytes| data.extend_from_slice(bytes)).await?;
+        Ok(String::from_utf8(data).unwrap())
+    }
+
+    #[tokio::test]
+    async fn head_skips_informational_responses() {
+        let head = "HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nContent-Type: text/event-stream; charset=utf-8\r\n\r\n";
+        let response = read_head(&mut head.as_bytes()).await.unwrap();
+        assert!(response.chunked);
+        assert!(response.event_stream);
+    }
+
+    #[tokio::test]
+    async fn head_of_an_error_response_fails() {
+        let error = read_head(&mut &b"HTTP/1.1 404 Not Found\r\n\r\n"[..]).await.err().unwrap();
+        assert_eq!(error.to_string(), "server responded HTTP/1.1 404 Not Found");
+    }
+
+    #[tokio::test]
+    async fn chunks_are_joined_whatever_their_boundaries() {
+        let chunked = b"4\r\nfirs\r\n8;ext=1\r\nt\nsecond\r\n1\r\n\n\r\n0\r\n\r\n";
+        assert_eq!(body(chunked, true).await.unwrap(), "first\nsecond\n");
+    }
+
+    #[tokio::test]
+    async fn chunked_body_ends_at_the_last_chunk() {
+        assert_eq!(body(b"3\r\nend\r\n0\r\n\r\nignored", true).await.unwrap(), "end");
+    }
+
+    #[tokio::test]
+    async fn chunked_body_cut_off_mid_chunk_fails() {
+        let error = body(b"a\r\nshort", true).await.err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[tokio::test]
+    async fn bad_chunk_size_fails() {
+        let error = body(b"zz\r\ndata\r\n", true).await.err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn plain_body_is_read_to_the_end() {
+        assert_eq!(body(b"one\ntwo", false).await.unwrap(), "one\ntwo");
+    }
+
+    #[test]
+    fn plain_lines_split_across_reads_come_out_whole() {
+        let (sink, mut rx) = sink();
+        let mut body = Body::default();
+        body.start(&Response { chunked: false, event_stream: false });
+        body.feed(b"par", &sink, 0);
+        body.feed(b"tial\nnext", &sink, 0);
+        assert_eq!(received(&mut rx), ["partial"]);
+        body.finish(&sink, 0);
+        assert_eq!(received(&mut rx), ["next"]);
+    }
+
+    #[test]
+    fn event_data_lines_are_shown_once_the_event_ends() {
+        let (sink, mut rx) = sink();
+        let mut body = Body::default();
+        body.start(&events());
+        body.feed(b": keep-alive\n\nevent: log\ndata: first\nda", &sink, 0);
+        body.feed(b"ta:second\nretry: 5000\n", &sink, 0);
+        assert!(received(&mut rx).is_empty());
+        body.feed(b"\n", &sink, 0);
+        assert_eq!(received(&mut rx), ["first", "second"]);
+    }
+
+    #[test]
+    fn event_lines_may_end_with_crlf() {
+        let (sink, mut rx) = sink();
+        let mut body = Body::default();
+        body.start(&events());
+        body.feed(b"data: one\r\n\r\ndata: two\r\n\r\n", &sink, 0);
+        assert_eq!(received(&mut rx), ["one", "two"]);
+    }
+
+    #[test]
+    fn unfinished_event_is_dropped() {
+        let (sink, mut rx) = sink();
+        let mut body = Body::default();
+        body.start(&events());
+        body.feed(b"data: whole\n\ndata: cut off", &sink, 0);
+        body.finish(&sink, 0);
+        assert_eq!(received(&mut rx), ["whole"]);
+
+        // Nor is it finished by the next connection
+        body.start(&events());
+        body.feed(b"\n", &sink, 0);
+        assert!(received(&mut rx).is_empty());
+    }
+
+    #[test]
+    fn event_ids_are_kept_across_connections() {
+        let (sink, _rx) = sink();
+        let mut body = Body::default();
+        body.start(&events());
+        body.feed(b"id: 41\ndata: a\n\nid: 42\ndata: b\n\n", &sink, 0);
+        assert_eq!(body.last_event_id.as_deref(), Some("42"));
+
+        // An ID with a NUL in it is ignored, as the spec says
+        body.feed(b"id: 4\x003\n\n", &sink, 0);
+        body.start(&events());
+        assert_eq!(body.last_event_id.as_deref(), Some("42"));
+    }
+
+    #[tokio::test]
+    async fn reconnecting_sends_the_last_event_id() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/events", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for response in ["id: 7\ndata: hello\n\n", "data: again\n\n"] {
+                let (socket, _) = listener.accept().await.unwrap();
+                let mut socket = BufReader::new(socket);
+                let mut request = String::new();
+                while socket.read_line(&mut request).await.unwrap() > 2 {}
+                requests.push(request);
+                let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\n";
+                socket.get_mut().write_all(format!("{}{}", head, response).as_bytes()).await.unwrap();
+            }
+            requests
+        });
+
+        let (sink, mut rx) = sink();
+        let mut body = Body::default();
+        stream(&url, &mut body, &sink, 0).await.unwrap();
+        stream(&url, &mut body, &sink, 0).await.unwrap();
+        assert_eq!(received(&mut rx), ["hello", "again"]);
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("GET /events HTTP/1.1\r\n"));
+        assert!(!requests[0].contains("Last-Event-ID"));
+        assert!(requests[1].contains("\r\nLast-Event-ID: 7\r\n"));
+    }
+}
@@ -0,0 +1,62 @@
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::app::url;
+
+/// `path/to/file.ext:LINE` or `path/to/file.ext:LINE:COLUMN`, as printed by compilers, test
+/// runners and stack traces.
+static FILE_REFERENCE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[\w./~-]+\.\w+:\d+(?::\d+)?").unwrap());
+
+/// Wrap the URLs and references to existing files in a line in OSC 8 escape sequences, so they
+/// stay clickable in terminals that support them when the output is saved or passed on.
+pub fn linkify(text: &str) -> Cow<'_, str> {
+    let mut links: Vec<(usize, usize, String)> = url::find(text)
+        .into_iter()
+        .map(|(start, end)| (start, end, text[start..end].to_string()))
+        .collect();
+    for reference in FILE_REFERENCE.find_iter(text) {
+        if links.iter().any(|&(start, end, _)| reference.start() < end && start < reference.end()) {
+            continue;
+        }
+        let path = reference.as_str().split(':').next().unwrap_or_default();
+        if let Some(target) = file_url(Path::new(path)) {
+            links.push((reference.start(), reference.end(), target));
+        }
+    }
+    if links.is_empty() {
+        return Cow::Borrowed(text);
+    }
+
+    links.sort_by_key(|&(start, _, _)| start);
+    let mut out = String::with_capacity(text.len() + links.len() * 32);
+    let mut last = 0;
+    for (start, end, target) in links {
+        out.push_str(&text[last..start]);
+        out.push_str(&format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", target, &text[start..end]));
+        last = end;
+    }
+    out.push_str(&text[last..]);
+    Cow::Owned(out)
+}
+
+/// The first file reference in a line, as its path, line and column if given.
+pub fn file_reference(text: &str) -> Option<(&str, u64, Option<usize>)> {
+    let mut parts = FILE_REFERENCE.find(text)?.as_str().split(':');
+    let path = parts.next()?;
+    let line = parts.next()?.parse().ok()?;
+    let column = parts.next().and_then(|column| column.parse().ok());
+    Some((path, line, column))
+}
+
+/// A `file://` URL for a path relative to the working directory, if the file exists.
+fn file_url(path: &Path) -> Option<String> {
+    if !path.is_file() {
+        return None;
+    }
+    let path = std::path::absolute(path).ok()?;
+    Some(format!("file://{}", path.display()))
+}
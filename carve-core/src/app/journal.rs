@@ -0,0 +1,153 @@
+use std::io;
+use std::process::Stdio;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, BufReader};
+
+use crate::app::source::Sink;
+
+/// Fields that are shown in the line itself, or are only of use to the journal, so aren't
+/// repeated in its fields.
+const SHOWN: [&str; 5] = ["MESSAGE", "PRIORITY", "SYSLOG_IDENTIFIER", "SYSLOG_PID", "SYSLOG_TIMESTAMP"];
+
+/// Spawn `journalctl` to follow the systemd journal, or one unit's entries in it, from its last
+/// few entries on.
+///
+/// Each entry becomes a line like `2024-01-01 12:00:00.000 ERROR sshd[42]: message`, so its
+/// priority is coloured as a level, followed by any fields it was logged with as a JSON object,
+/// such as `{"CODE_FILE":"main.c","CODE_LINE":"12"}`. Fields added by journald itself, whose names
+/// start with `_`, are left out.
+pub fn spawn(unit: Option<String>, sink: Sink, source: usize) {
+    tokio::spawn(async move {
+        let mut command = tokio::process::Command::new("journalctl");
+        command.args(["--follow", "--output=export"]);
+        if let Some(unit) = &unit {
+            command.arg("--unit").arg(unit);
+        }
+        let child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => return sink.push(format!("──── failed to run journalctl: {} ────", e), source),
+        };
+        let mut reader = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        loop {
+            match read_entry(&mut reader).await {
+                Ok(Some(entry)) => sink.push(format_entry(&entry), source),
+                Ok(None) => break,
+                Err(e) => {
+                    sink.push(format!("──── failed to read the journal: {} ────", e), source);
+                    break;
+                }
+            }
+        }
+        let mut errors = String::new();
+        if let Some(mut stderr) = child.stderr.take() {
+            let _ = stderr.read_to_string(&mut errors).await;
+        }
+        for line in errors.lines().filter(|line| !line.trim().is_empty()) {
+            sink.push(format!("──── journalctl: {} ────", line.trim()), source);
+        }
+        match child.wait().await {
+            Ok(status) if status.success() => {}
+            Ok(status) => sink.push(format!("──── journalctl {} ────", status), source),
+            Err(e) => sink.push(format!("──── failed to run journalctl: {} ────", e), source),
+        }
+    });
+}
+
+/// Read the fields of the next entry in journalctl's export format: `NAME=value` lines ending
+/// with a blank line, where a field whose value isn't plain text is written as its name on a
+/// line of its own, its length as 64 bits little endian, then the value and a newline.
+async fn read_entry(reader: &mut (impl AsyncBufRead + Unpin)) -> io::Result<Option<Vec<(String, String)>>> {
+    let mut fields = Vec::new();
+    loop {
+        let mut line = Vec::new();
+        if reader.read_until(b'\n', &mut line).await? == 0 {
+            return Ok((!fields.is_empty()).then_some(fields));
+        }
+        if line.pop() != Some(b'\n') {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "entry cut short"));
+        }
+        if line.is_empty() {
+            if fields.is_empty() {
+                continue;
+            }
+            return Ok(Some(fields));
+        }
+        match line.iter().position(|&b| b == b'=') {
+            Some(equals) => fields.push((
+                String::from_utf8_lossy(&line[..equals]).into_owned(),
+                String::from_utf8_lossy(&line[equals + 1..]).into_owned(),
+            )),
+            None => {
+                let len = reader.read_u64_le().await?;
+                let mut value = vec![0; len as usize + 1];
+                reader.read_exact(&mut value).await?;
+                value.pop();
+                fields.push((String::from_utf8_lossy(&line).into_owned(), String::from_utf8_lossy(&value).into_owned()));
+            }
+        }
+    }
+}
+
+/// The line shown for an entry.
+fn format_entry(fields: &[(String, String)]) -> String {
+    let field = |name: &str| fields.iter().find(|(field, _)| field == name).map(|(_, value)| value.as_str());
+    let mut line = String::new();
+    if let Some(time) = field("__REALTIME_TIMESTAMP")
+        .and_then(|micros| micros.parse::<i64>().ok())
+        .and_then(chrono::DateTime::from_timestamp_micros)
+    {
+        line.push_str(&time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S%.3f ").to_string());
+    }
+    line.push_str(level(field("PRIORITY")));
+    let identifier = field("SYSLOG_IDENTIFIER").or(field("_COMM")).unwrap_or("-");
+    match field("SYSLOG_PID").or(field("_PID")) {
+        Some(pid) => line.push_str(&format!(" {}[{}]: ", identifier, pid)),
+        None => line.push_str(&format!(" {}: ", identifier)),
+    }
+    // A message over several lines is shown on one, as journalctl does
+    line.push_str(&field("MESSAGE").unwrap_or_default().replace('\n', " "));
+
+    let extra: Vec<String> = fields
+        .iter()
+        .filter(|(name, _)| !name.starts_with('_') && !SHOWN.contains(&name.as_str()))
+        .map(|(name, value)| format!("{}:{}", json_string(name), json_string(value)))
+        .collect();
+    if !extra.is_empty() {
+        line.push_str(&format!(" {{{}}}", extra.join(",")));
+    }
+    line
+}
+
+/// The level name for a syslog priority, from 0 for an emergency to 7 for debug messages.
+pub fn level(priority: Option<&str>) -> &'static str {
+    match priority.and_then(|priority| priority.parse::<u8>().ok()) {
+        Some(0..=3) => "ERROR",
+        Some(4) => "WARN",
+        Some(7) => "DEBUG",
+        _ => "INFO",
+    }
+}
+
+/// `value` quoted as a JSON string.
+pub fn json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
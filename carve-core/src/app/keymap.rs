@@ -0,0 +1,125 @@
+use std::time::{Duration, Instant};
+
+use crate::app::actions;
+use crate::app::key::{KeyEvent, KeyModifiers};
+
+/// How long to wait for the next key of a sequence when none is configured.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// What the keys pressed so far add up to.
+pub enum Outcome {
+    /// A bound sequence was completed: run its commands.
+    Run(String),
+    /// The keys are the start of a bound sequence, so wait for the next one.
+    Wait,
+    /// The keys aren't bound, so they keep their built-in meaning, in order.
+    Unbound(Vec<KeyEvent>),
+}
+
+/// Key sequences bound to commands in the config file, such as `g e` or `<leader>e`, and the
+/// keys typed so far towards one.
+///
+/// A sequence that's also the start of a longer one runs once the timeout passes without
+/// another key, as does a built-in key that starts a bound sequence.
+pub struct Keymap {
+    bindings: Vec<(Vec<KeyEvent>, String)>,
+    timeout: Duration,
+    pending: Vec<KeyEvent>,
+    since: Instant, // When the last pending key was pressed
+}
+
+impl Keymap {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            bindings: Vec::new(),
+            timeout,
+            pending: Vec::new(),
+            since: Instant::now(),
+        }
+    }
+
+    /// Bind a sequence written as in the action log, with `<leader>` standing for the leader key,
+    /// e.g. `<leader>e`, `g g` or `ctrl+t`.
+    pub fn bind(&mut self, keys: &str, leader: &str, command: String) -> Result<(), String> {
+        let keys = actions::parse_keys(&keys.replace("<leader>", &format!(" {} ", leader)))?;
+        if keys.is_empty() {
+            return Err("no keys given".to_string());
+        }
+        self.bindings.push((keys, command));
+        Ok(())
+    }
+
+    /// Whether keys have been typed towards a sequence.
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    pub fn press(&mut self, key: KeyEvent) -> Outcome {
+        self.pending.push(key);
+        self.since = Instant::now();
+        let mut continues = false;
+        let mut exact = None;
+        for (keys, command) in &self.bindings {
+            if !starts_with(keys, &self.pending) {
+                continue;
+            }
+            if keys.len() == self.pending.len() {
+                exact.get_or_insert(command);
+            } else {
+                continues = true;
+            }
+        }
+        match (exact, continues) {
+            (_, true) => Outcome::Wait,
+            (Some(command), false) => {
+                let command = command.clone();
+                self.pending.clear();
+                Outcome::Run(command)
+            }
+            (None, false) => Outcome::Unbound(std::mem::take(&mut self.pending)),
+        }
+    }
+
+    /// Settle the keys typed so far if the timeout has passed without another.
+    pub fn expire(&mut self) -> Option<Outcome> {
+        if self.pending.is_empty() || self.since.elapsed() < self.timeout {
+            return None;
+        }
+        let pending = std::mem::take(&mut self.pending);
+        let exact = self.bindings.iter().find(|(keys, _)| keys.len() == pending.len() && starts_with(keys, &pending));
+        Some(match exact {
+            Some((_, command)) => Outcome::Run(command.clone()),
+            None => Outcome::Unbound(pending),
+        })
+    }
+
+    /// The keys that could come next, each with what it runs or `…` if it starts a longer
+    /// sequence, for the hint popup.
+    pub fn hints(&self) -> Vec<(String, String)> {
+        let mut hints: Vec<(String, String)> = Vec::new();
+        for (keys, command) in &self.bindings {
+            if keys.len() <= self.pending.len() || !starts_with(keys, &self.pending) {
+                continue;
+            }
+            let next = actions::describe(&keys[self.pending.len()]);
+            let does = if keys.len() == self.pending.len() + 1 { command.clone() } else { "…".to_string() };
+            if !hints.iter().any(|(key, _)| *key == next) {
+                hints.push((next, does));
+            }
+        }
+        hints
+    }
+
+    /// The keys typed so far, as written in the docs.
+    pub fn typed(&self) -> String {
+        self.pending.iter().map(actions::describe).collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// Whether `keys` starts with `prefix`. Shift is ignored, as it's implied by the character typed.
+fn starts_with(keys: &[KeyEvent], prefix: &[KeyEvent]) -> bool {
+    keys.len() >= prefix.len()
+        && keys.iter().zip(prefix).all(|(bound, typed)| {
+            bound.code == typed.code && bound.modifiers - KeyModifiers::SHIFT == typed.modifiers - KeyModifiers::SHIFT
+        })
+}
@@ -0,0 +1,418 @@
+use crate::app::key::{KeyCode, KeyEvent, KeyModifiers};
+use crate::app::{keymap, selection, App, Mode};
+use crate::{captures, freq, objects, words};
+
+/// The options from the command line that change what keys do.
+pub struct KeyOptions {
+    pub yank_context: usize,
+    pub collapse_duplicates: bool,
+    pub confirm_quit_over: Option<usize>,
+}
+
+impl App {
+    /// Handle a key press, returning whether carve should exit. Keys in normal mode go through
+    /// the keymap first, in case they're part of a bound sequence.
+    pub fn handle_key(&mut self, key: KeyEvent, options: &KeyOptions) -> bool {
+        if !matches!(self.mode, Mode::Normal) || self.pending_key.is_some() {
+            return self.handle_builtin_key(key, options);
+        }
+        let outcome = self.keymap.press(key);
+        self.settle_keys(outcome, options)
+    }
+
+    /// Act on what the keys typed towards a bound sequence came to, returning whether carve
+    /// should exit.
+    pub fn settle_keys(&mut self, outcome: keymap::Outcome, options: &KeyOptions) -> bool {
+        match outcome {
+            keymap::Outcome::Run(command) => {
+                self.message = None;
+                self.execute(&command);
+                false
+            }
+            keymap::Outcome::Wait => false,
+            keymap::Outcome::Unbound(keys) => keys.into_iter().any(|key| self.handle_builtin_key(key, options)),
+        }
+    }
+
+    /// Handle a key press by what it does without any bindings, returning whether carve should
+    /// exit.
+    fn handle_builtin_key(&mut self, key: KeyEvent, options: &KeyOptions) -> bool {
+        self.message = None;
+        let pending = self.pending_key.take();
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match (self.mode, key.code) {
+            // Fold commands
+            (Mode::Normal, KeyCode::Char('a')) if pending == Some('z') => self.pane_mut().toggle_expanded(),
+            (Mode::Normal, KeyCode::Char('M')) if pending == Some('z') => self.set_fold_blocks(true),
+            (Mode::Normal, KeyCode::Char('R')) if pending == Some('z') => self.set_fold_blocks(false),
+            // Yank a JSON object, block or record
+            (Mode::Normal, KeyCode::Char(c)) if pending == Some('Y') => {
+                match objects::Object::from_key(c) {
+                    Some(object) => match self.object_at_cursor(object) {
+                        Ok(text) => self.yank = Some(text),
+                        Err(message) => self.message = Some(message),
+                    },
+                    None => self.message = Some(format!("Unknown object: Y{}", c)),
+                }
+            },
+            (Mode::Normal, KeyCode::Char('z')) => self.pending_key = Some('z'),
+            // Delete lines, and restore them
+            (Mode::Normal, KeyCode::Char('x')) => {
+                let cursor = self.pane().cursor;
+                self.delete_rows(cursor, cursor);
+            },
+            (Mode::Normal, KeyCode::Char('r')) => self.undo_delete(),
+            (Mode::Block, KeyCode::Char('d' | 'x')) => {
+                if let Some(selection) = &self.selection {
+                    let (first, last) = selection.rows(self.pane().cursor);
+                    self.delete_rows(first, last);
+                }
+                self.mode = Mode::Normal;
+            },
+            // Block selection
+            (Mode::Normal, KeyCode::Char('v')) => self.start_selection(),
+            (Mode::Block, KeyCode::Char('j') | KeyCode::Down) => self.pane_mut().cursor_down(1),
+            (Mode::Block, KeyCode::Char('k') | KeyCode::Up) => self.pane_mut().cursor_up(1),
+            (Mode::Block, KeyCode::Char('h') | KeyCode::Left) => self.move_selection(|column, _| column.saturating_sub(1)),
+            (Mode::Block, KeyCode::Char('l') | KeyCode::Right) => {
+                self.move_selection(|column, text| (column + 1).min(selection::width(text).saturating_sub(1)))
+            },
+            (Mode::Block, KeyCode::Char('w')) => self.move_selection(|column, text| {
+                selection::word_starts(text).into_iter().find(|&start| start > column).unwrap_or(column)
+            }),
+            (Mode::Block, KeyCode::Char('b')) => self.move_selection(|column, text| {
+                selection::word_starts(text).into_iter().rev().find(|&start| start < column).unwrap_or(0)
+            }),
+            (Mode::Block, KeyCode::Char('e')) => self.move_selection(|column, text| {
+                selection::word_ends(text).into_iter().find(|&end| end > column).unwrap_or(column)
+            }),
+            (Mode::Block, KeyCode::Char('0')) => self.move_selection(|_, _| 0),
+            (Mode::Block, KeyCode::Char('$')) => self.move_selection(|_, text| selection::width(text).saturating_sub(1)),
+            (Mode::Block, KeyCode::Char('y') | KeyCode::Enter) => {
+                if let Some(text) = self.selection_text() {
+                    self.yank = Some(text);
+                }
+                self.mode = Mode::Normal;
+            },
+            (Mode::Block, KeyCode::Char('v')) => self.anchor_selection(),
+            (Mode::Block, KeyCode::Char('q')) => self.mode = Mode::Normal,
+            (Mode::Normal, KeyCode::Char('Y')) => self.pending_key = Some('Y'),
+
+            // Navigation keys scroll the diff view instead of the panes while it's showing
+            (Mode::Normal, code) if self.scroll_diff(code) => {}
+
+            // Frequency panel
+            (Mode::Normal, KeyCode::Char('F')) => {
+                self.frequencies = Some(freq::Frequencies::default());
+                self.mode = Mode::Frequency;
+            },
+            (Mode::Frequency, KeyCode::Char('j') | KeyCode::Down) => {
+                if let Some(frequencies) = &mut self.frequencies {
+                    frequencies.select_next();
+                }
+            },
+            (Mode::Frequency, KeyCode::Char('k') | KeyCode::Up) => {
+                if let Some(frequencies) = &mut self.frequencies {
+                    frequencies.select_prev();
+                }
+            },
+            (Mode::Frequency, KeyCode::Enter) => self.apply_frequency(),
+            (Mode::Frequency, KeyCode::Esc | KeyCode::Char('F')) => {
+                self.frequencies = None;
+                self.mode = Mode::Normal;
+            },
+
+            // Sources panel
+            (Mode::Normal, KeyCode::Char('L')) => {
+                self.selected_source = self.selected_source.min(self.sources.len().saturating_sub(1));
+                self.mode = Mode::Sources;
+            },
+            (Mode::Sources, KeyCode::Char('j') | KeyCode::Down) => {
+                self.selected_source = (self.selected_source + 1).min(self.sources.len().saturating_sub(1));
+            },
+            (Mode::Sources, KeyCode::Char('k') | KeyCode::Up) => {
+                self.selected_source = self.selected_source.saturating_sub(1);
+            },
+            (Mode::Sources, KeyCode::Char(' ') | KeyCode::Enter) => {
+                let source = self.selected_source;
+                let hidden = self.pane().hidden_sources.contains(&source);
+                self.pane_mut().set_source_hidden(source, !hidden);
+            },
+            (Mode::Sources, KeyCode::Char('o')) => {
+                for source in 0..self.sources.len() {
+                    let hidden = source != self.selected_source;
+                    self.pane_mut().set_source_hidden(source, hidden);
+                }
+            },
+            (Mode::Sources, KeyCode::Char('a')) => {
+                for source in 0..self.sources.len() {
+                    self.pane_mut().set_source_hidden(source, false);
+                }
+            },
+            (Mode::Sources, KeyCode::Esc | KeyCode::Char('L' | 'q')) => self.mode = Mode::Normal,
+
+            // Actions for the line under the cursor
+            (Mode::Normal, KeyCode::Char('.')) => self.open_menu(),
+            (Mode::Menu, KeyCode::Char('j') | KeyCode::Down) => {
+                if let Some(menu) = &mut self.menu {
+                    menu.select_next();
+                }
+            },
+            (Mode::Menu, KeyCode::Char('k') | KeyCode::Up) => {
+                if let Some(menu) = &mut self.menu {
+                    menu.select_prev();
+                }
+            },
+            (Mode::Menu, KeyCode::Enter) => self.apply_menu(),
+            (Mode::Menu, KeyCode::Esc | KeyCode::Char('.' | 'q')) => {
+                self.menu = None;
+                self.mode = Mode::Normal;
+            },
+
+            // Word cloud
+            (Mode::Normal, KeyCode::Char('W')) => {
+                self.words = Some(words::Words::new(&self.stopwords));
+                self.mode = Mode::Words;
+            },
+            (Mode::Words, KeyCode::Char('l' | 'j') | KeyCode::Right | KeyCode::Down) => {
+                if let Some(words) = &mut self.words {
+                    words.select_next();
+                }
+            },
+            (Mode::Words, KeyCode::Char('h' | 'k') | KeyCode::Left | KeyCode::Up) => {
+                if let Some(words) = &mut self.words {
+                    words.select_prev();
+                }
+            },
+            (Mode::Words, KeyCode::Enter) => self.apply_word(false),
+            (Mode::Words, KeyCode::Char('f')) => self.apply_word(true),
+            (Mode::Words, KeyCode::Esc | KeyCode::Char('W')) => {
+                self.words = None;
+                self.mode = Mode::Normal;
+            },
+
+            // Values captured by the search
+            (Mode::Normal, KeyCode::Char('C')) => {
+                if self.pane().search.regex().is_some_and(|regex| regex.captures_len() > 1) {
+                    self.captures = Some(captures::Captures::default());
+                    self.mode = Mode::Captures;
+                } else {
+                    self.message = Some("Search for a regular expression with groups first, such as /user=(\\w+)".to_string());
+                }
+            },
+            (Mode::Captures, KeyCode::Char('j') | KeyCode::Down) => {
+                if let Some(captures) = &mut self.captures {
+                    captures.select_next();
+                }
+            },
+            (Mode::Captures, KeyCode::Char('k') | KeyCode::Up) => {
+                if let Some(captures) = &mut self.captures {
+                    captures.select_prev();
+                }
+            },
+            (Mode::Captures, KeyCode::Enter) => self.apply_captures(),
+            (Mode::Captures, KeyCode::Esc | KeyCode::Char('C')) => {
+                self.captures = None;
+                self.mode = Mode::Normal;
+            },
+
+            // Each distinct line once, and from one to where it occurs among all the lines
+            (Mode::Normal, KeyCode::Char('U')) => {
+                let unique = !self.pane().unique;
+                self.pane_mut().set_unique(unique);
+                self.message = Some(if unique { "Showing each distinct line once" } else { "Showing every line" }.to_string());
+            },
+            (Mode::Normal, KeyCode::Char('{')) if self.pane().unique => self.jump_to_occurrence(false),
+            (Mode::Normal, KeyCode::Char('}')) if self.pane().unique => self.jump_to_occurrence(true),
+
+            // Back from `:sort` to the order lines arrived in
+            (Mode::Normal, KeyCode::Char('A')) if self.pane().sort.is_some() => {
+                self.pane_mut().set_sort(None);
+                self.message = Some("Back in the order lines arrived".to_string());
+            },
+
+            // Query results
+            (Mode::Query, KeyCode::Char('j') | KeyCode::Down) => {
+                if let Some(query) = &mut self.query {
+                    query.results.select_next();
+                }
+            },
+            (Mode::Query, KeyCode::Char('k') | KeyCode::Up) => {
+                if let Some(query) = &mut self.query {
+                    query.results.select_prev();
+                }
+            },
+            (Mode::Query, KeyCode::Esc | KeyCode::Char('q')) => {
+                self.query = None;
+                self.mode = Mode::Normal;
+            },
+
+            // Picking lines prints only those, so there's nothing to confirm
+            (Mode::Normal, KeyCode::Enter) if self.picking => {
+                self.picked = true;
+                return true;
+            },
+            (Mode::Normal, KeyCode::Tab) if self.picking => self.toggle_mark(),
+            (Mode::Normal, KeyCode::Char('q')) if self.picking => return true,
+
+            // Quit only works in normal mode, and asks first before flooding the terminal
+            (Mode::Normal, KeyCode::Char('q')) if self.quit(options.confirm_quit_over, options.collapse_duplicates) => return true,
+            (Mode::Normal, KeyCode::Char('q')) => {}
+            (Mode::ConfirmQuit, KeyCode::Char('y' | 'q') | KeyCode::Enter) => return true,
+            (Mode::ConfirmQuit, _) => self.mode = Mode::Normal,
+
+            // Esc always returns to tail mode
+            (_, KeyCode::Esc) => self.mode = Mode::Normal,
+
+            // Normal mode commands
+            //(Mode::Normal, KeyCode::Char('/')) => {
+            //    self.pane_mut().search_query.clear();
+            //},
+            (Mode::Normal, KeyCode::Char('n')) if !self.pane().search.matches().is_empty() => self.next_match(),
+            (Mode::Normal, KeyCode::Char('N')) if !self.pane().search.matches().is_empty() => self.prev_match(),
+            (Mode::Normal, KeyCode::Char('y')) => {
+                match self.current_match_context(options.yank_context) {
+                    Some(text) => self.yank = Some(text),
+                    None => self.message = Some("No match to yank".to_string()),
+                }
+            },
+            (Mode::Normal, KeyCode::Char('j') | KeyCode::Down) => {
+                self.pane_mut().cursor_down(1);
+                self.pane_mut().tailing = false;
+            },
+            (Mode::Normal, KeyCode::Char('k') | KeyCode::Up) => {
+                self.pane_mut().cursor_up(1);
+                self.pane_mut().tailing = false;
+            },
+            (Mode::Normal, KeyCode::Char('d' | ' ') | KeyCode::PageDown) => {
+                let view_height = self.pane().view_height;
+                let len = self.pane().visible_len();
+                let amount = view_height / 2;
+                if len > view_height {
+                    self.pane_mut().scroll_down(amount, len.saturating_sub(view_height));
+                }
+                self.pane_mut().cursor_down(amount);
+                self.pane_mut().tailing = false;
+            },
+            (Mode::Normal, KeyCode::Char('u' | 'b') | KeyCode::PageUp) => {
+                let view_height = self.pane().view_height;
+                let amount = view_height / 2;
+                if self.pane().visible_len() > view_height {
+                    self.pane_mut().scroll_up(amount);
+                }
+                self.pane_mut().cursor_up(amount);
+                self.pane_mut().tailing = false;
+            },
+            (Mode::Normal, KeyCode::Char('g') | KeyCode::Home) => {
+                self.pane_mut().scroll_to(0);
+                self.pane_mut().cursor = 0;
+                self.pane_mut().tailing = false;
+            },
+            (Mode::Normal, KeyCode::Char('G') | KeyCode::End) => {
+                let view_height = self.pane().view_height;
+                let len = self.pane().visible_len();
+                self.pane_mut().scroll_to(len.saturating_sub(view_height));
+                self.pane_mut().cursor = len.saturating_sub(1);
+                self.pane_mut().tailing = true;
+            },
+            (Mode::Normal, KeyCode::Char('p')) => self.toggle_pin(),
+            (Mode::Normal, KeyCode::Char('o')) => self.open_url(),
+            (Mode::Normal, KeyCode::Char('m')) => self.toggle_mark(),
+            (Mode::Normal, KeyCode::Char('O')) => {
+                self.output = self.output.next();
+                self.message = Some(format!("Printing {} lines on exit", self.output.name()));
+            },
+            (Mode::Normal, KeyCode::Char('H')) => self.toggle_levels(),
+            (Mode::Normal, KeyCode::Char('X')) => self.show_hex = !self.show_hex,
+            (Mode::Normal, KeyCode::Char('S')) => {
+                if let Err(message) = self.snapshot_buffer() {
+                    self.message = Some(message);
+                }
+            }
+            (Mode::Normal, KeyCode::Char('T')) => self.toggle_table(),
+            (Mode::Normal, KeyCode::Char('s')) if ctrl => self.set_paused(!self.paused),
+            (Mode::Normal, KeyCode::Char('s')) => self.cycle_sort(),
+            (Mode::Normal, KeyCode::Char('h') | KeyCode::Left) if self.table.is_some() => {
+                self.table.as_mut().unwrap().scroll_left();
+            }
+            (Mode::Normal, KeyCode::Char('l') | KeyCode::Right) if self.table.is_some() => {
+                self.table.as_mut().unwrap().scroll_right();
+            }
+            (Mode::Normal, KeyCode::Char('=')) => self.toggle_fold_duplicates(),
+            (Mode::Normal, KeyCode::Enter) => self.pane_mut().toggle_expanded(),
+            (Mode::Normal, KeyCode::Char('P')) => self.show_pinned = !self.show_pinned,
+            (Mode::Normal, KeyCode::Tab) => self.focus_next(),
+            // Handle all characters in normal mode (for search)
+            (Mode::Normal, KeyCode::Char('f')) => {
+                self.pane_mut().search_query.clear();
+                self.update_search();
+                self.mode = Mode::Search;
+            },
+            (Mode::Search, KeyCode::Char(c)) => {
+                self.pane_mut().search_query.push(c);
+                self.update_search();
+            },
+            (Mode::Search, KeyCode::Backspace) => {
+                self.pane_mut().search_query.pop();
+                self.update_search();
+            },
+            (Mode::Search, KeyCode::Enter) => {
+                self.pane_mut().jump_to_match();
+                self.pane_mut().search_query.clear();
+                self.mode = Mode::Normal;
+            },
+            (Mode::Normal, KeyCode::Char('/')) => {
+                self.pane_mut().filter.clear();
+                self.mode = Mode::Filter;
+            },
+            (Mode::Normal, KeyCode::Char('+')) => {
+                self.pane_mut().push_filter();
+                self.mode = Mode::Filter;
+            },
+            (Mode::Normal, KeyCode::Char('-')) => self.pane_mut().pop_filter(),
+            (Mode::Filter, KeyCode::Char(c)) => {
+                self.pane_mut().filter.push(c);
+            },
+            (Mode::Filter, KeyCode::Backspace) => {
+                self.pane_mut().filter.pop();
+            },
+            (Mode::Filter, KeyCode::Enter) => {
+                self.mode = Mode::Normal;
+            },
+            (Mode::Normal, KeyCode::Char(c @ '1'..='9')) => self.apply_preset(c as usize - '1' as usize),
+            (Mode::Normal, KeyCode::Char('#')) => {
+                self.command.clear();
+                self.mode = Mode::Highlight;
+            },
+            (Mode::Highlight, KeyCode::Char(c)) => {
+                self.command.push(c);
+            },
+            (Mode::Highlight, KeyCode::Backspace) => {
+                self.command.pop();
+            },
+            (Mode::Highlight, KeyCode::Enter) => {
+                self.mode = Mode::Normal;
+                let pattern = std::mem::take(&mut self.command);
+                self.add_highlight(pattern);
+            },
+            (Mode::Normal, KeyCode::Char(':')) => {
+                self.command.clear();
+                self.mode = Mode::Command;
+            },
+            (Mode::Command, KeyCode::Char(c)) => {
+                self.command.push(c);
+            },
+            (Mode::Command, KeyCode::Backspace) => {
+                self.command.pop();
+            },
+            (Mode::Command, KeyCode::Enter) => {
+                self.mode = Mode::Normal;
+                let command = std::mem::take(&mut self.command);
+                self.execute(&command);
+            },
+            // Handle all characters in normal mode (for search)
+            _ => {}
+        }
+        false
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tokio::io::AsyncReadExt;
+
+use crate::app::source::{self, Sink};
+
+/// How often the pods are listed to find ones that have started.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Lists the names of the running pods, one per line.
+const RUNNING_PODS: &str = r#"{{range .items}}{{if eq .status.phase "Running"}}{{.metadata.name}}{{"\n"}}{{end}}{{end}}"#;
+
+/// Joins a workload's selector labels into a label selector, with a trailing comma.
+const SELECTOR: &str = "{{range $key, $value := .spec.selector.matchLabels}}{{$key}}={{$value}},{{end}}";
+
+/// The pods seen so far, each with when its logs ended, or `None` while they're being followed.
+type Pods = Arc<Mutex<HashMap<String, Option<DateTime<Utc>>>>>;
+
+/// Spawn an async task that follows the logs of every running pod matching `target` with
+/// `kubectl`, each pod as a source of its own, adding pods as they start so a rollout can be
+/// followed from the old pods to the new. `target` is a workload, such as `deploy/api`, whose
+/// selector picks the pods, or else a label selector such as `app=api,tier=web`.
+///
+/// Problems finding pods are noted in `source`.
+pub fn spawn(target: String, sink: Sink, source: usize) {
+    tokio::spawn(async move {
+        let pods: Pods = Default::default();
+        let mut sources = HashMap::new(); // The source of each pod followed so far
+        let mut last_error = None;
+        loop {
+            let running = match selector(&target).await {
+                Ok(selector) => kubectl(&["get", "pods", "--selector", &selector, "-o", &format!("go-template={}", RUNNING_PODS)]).await,
+                Err(e) => Err(e),
+            };
+            match running {
+                Ok(running) => {
+                    last_error = None;
+                    for pod in running.lines().filter(|pod| !pod.is_empty()) {
+                        let since = match pods.lock().unwrap().insert(pod.to_string(), None) {
+                            Some(None) => continue, // Already being followed
+                            Some(ended) => ended,
+                            None => None,
+                        };
+                        let pod_source = *sources.entry(pod.to_string()).or_insert_with(|| sink.add_source(pod.to_string()));
+                        follow(pod.to_string(), since, sink.clone(), pod_source, pods.clone());
+                    }
+                }
+                // The same problem is only noted once, rather than every time the pods are listed
+                Err(e) if last_error.as_ref() != Some(&e) => {
+                    sink.push(format!("──── {}: {} ────", target, e), source);
+                    last_error = Some(e);
+                }
+                Err(_) => {}
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+/// The label selector for `target`, looking up a workload's.
+async fn selector(target: &str) -> Result<String, String> {
+    if !target.contains('/') {
+        return Ok(target.to_string());
+    }
+    let selector = kubectl(&["get", target, "-o", &format!("go-template={}", SELECTOR)]).await?;
+    match selector.trim().trim_end_matches(',') {
+        "" => Err(format!("{} has no selector", target)),
+        selector => Ok(selector.to_string()),
+    }
+}
+
+/// Follow a pod's logs, from all its containers, until it goes away. If the pod was followed
+/// before and its logs ended, such as when a container restarted, they're read from `since`.
+fn follow(pod: String, since: Option<DateTime<Utc>>, sink: Sink, source: usize, pods: Pods) {
+    tokio::spawn(async move {
+        let mut command = tokio::process::Command::new("kubectl");
+        command.args(["logs", "--follow", "--all-containers", "--timestamps=false"]);
+        if let Some(since) = since {
+            command.arg(format!("--since-time={}", since.to_rfc3339_opts(chrono::SecondsFormat::Nanos, true)));
+        }
+        let child = command
+            .arg(format!("pod/{}", pod))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn();
+        match child {
+            Ok(mut child) => {
+                sink.push(format!("──── following {} ────", pod), source);
+                let stdout = child.stdout.take().expect("stdout is piped");
+                if let Err(e) = source::read_to_end(stdout, &sink, source, None, std::future::pending::<()>()).await {
+                    tracing::warn!(pod, "failed to read logs: {}", e);
+                }
+                let mut errors = String::new();
+                if let Some(mut stderr) = child.stderr.take() {
+                    let _ = stderr.read_to_string(&mut errors).await;
+                }
+                let _ = child.wait().await;
+                match errors.lines().find(|line| !line.trim().is_empty()) {
+                    Some(error) => sink.push(format!("──── {}: {} ────", pod, error.trim()), source),
+                    None => sink.push(format!("──── {} ended ────", pod), source),
+                }
+            }
+            Err(e) => sink.push(format!("──── failed to run kubectl: {} ────", e), source),
+        }
+        pods.lock().unwrap().insert(pod, Some(Utc::now()));
+    });
+}
+
+/// Run `kubectl` with `args`, returning what it prints, or the error it gives if it fails.
+async fn kubectl(args: &[&str]) -> Result<String, String> {
+    let output = tokio::process::Command::new("kubectl")
+        .args(args)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| format!("failed to run kubectl: {}", e))?;
+    if !output.status.success() {
+        let errors = String::from_utf8_lossy(&output.stderr);
+        return Err(errors.lines().find(|line| !line.trim().is_empty()).unwrap_or("kubectl failed").trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,104 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::freq;
+
+use crate::app::quickfix::Location;
+use crate::app::url;
+
+/// `key=value` or `"key": "value"` where the key names an ID, such as `request_id`, `traceId`
+/// or `correlation-id`.
+static KEYED_ID: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\b(?:id|Id|ID|[A-Za-z]+[_-](?:id|Id|ID)|[a-z]+(?:Id|ID))"?\s*[=:]\s*"?([\w.:-]+)"#).unwrap()
+});
+
+/// A UUID, which is an ID wherever it appears.
+static UUID: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}\b").unwrap()
+});
+
+/// Something that can be done with the line under the cursor.
+pub enum Action {
+    Copy,
+    /// Filter on the constant part of the line's message, as for the frequency panel.
+    Filter(String),
+    /// Filter out lines with the constant part of the line's message.
+    Exclude(String),
+    /// Filter on an ID in the line, to follow a request through the logs.
+    Trace(String),
+    Edit(Location),
+    OpenUrl(String),
+    Pin { pinned: bool },
+    Mark { marked: bool },
+}
+
+impl Action {
+    pub fn label(&self) -> String {
+        match self {
+            Action::Copy => "Copy line".to_string(),
+            Action::Filter(filter) => format!("Filter on '{}'", filter),
+            Action::Exclude(filter) => format!("Exclude '{}'", filter),
+            Action::Trace(id) => format!("Trace ID {}", id),
+            Action::Edit(location) => format!("Edit {}", location),
+            Action::OpenUrl(url) => format!("Open {}", url),
+            Action::Pin { pinned: false } => "Pin".to_string(),
+            Action::Pin { pinned: true } => "Unpin".to_string(),
+            Action::Mark { marked: false } => "Mark".to_string(),
+            Action::Mark { marked: true } => "Unmark".to_string(),
+        }
+    }
+}
+
+/// The actions for a line, opened with `.`, so what can be done with a line can be found from
+/// the line itself.
+pub struct Menu {
+    pub actions: Vec<Action>,
+    pub selected: usize,
+}
+
+impl Menu {
+    /// The actions that apply to a line, given where it's located, if anywhere, and whether
+    /// it's pinned and marked.
+    pub fn new(text: &str, location: Option<Location>, pinned: bool, marked: bool) -> Self {
+        let mut actions = vec![Action::Copy];
+        let message = freq::filter_for(&freq::template(text));
+        if !message.is_empty() {
+            actions.push(Action::Filter(message.clone()));
+            actions.push(Action::Exclude(message));
+        }
+        if let Some(id) = find_id(text) {
+            actions.push(Action::Trace(id.to_string()));
+        }
+        if let Some(location) = location {
+            actions.push(Action::Edit(location));
+        }
+        if let Some(&(start, end)) = url::find(text).first() {
+            actions.push(Action::OpenUrl(text[start..end].to_string()));
+        }
+        actions.push(Action::Pin { pinned });
+        actions.push(Action::Mark { marked });
+        Self { actions, selected: 0 }
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1).min(self.actions.len() - 1);
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn take_selected(mut self) -> Action {
+        self.actions.swap_remove(self.selected)
+    }
+}
+
+/// The first ID in a line: the value of a field named as an ID, or else a UUID.
+fn find_id(text: &str) -> Option<&str> {
+    KEYED_ID
+        .captures(text)
+        .and_then(|captures| captures.get(1))
+        .or_else(|| UUID.find(text))
+        .map(|id| id.as_str())
+}
@@ -0,0 +1,1670 @@
+pub mod actions;
+pub mod alerts;
+pub mod base64;
+pub mod command;
+pub mod control;
+pub mod counters;
+pub mod docker;
+pub mod export;
+pub mod groups;
+pub mod history;
+pub mod http;
+pub mod hyperlink;
+pub mod journal;
+pub mod key;
+mod keys;
+pub mod keymap;
+pub mod kube;
+pub mod menu;
+pub mod options;
+pub mod plugin;
+pub mod quickfix;
+pub mod rate;
+pub mod replay;
+pub mod script;
+pub mod selection;
+pub mod source;
+pub mod syslog;
+pub mod url;
+pub mod ws;
+#[cfg(test)]
+mod tests;
+
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+use crate::{anomaly, captures, compare, diff, fields, freq, level, line, objects, pane, query, region, sample, table, timestamp, words};
+
+use key::KeyCode;
+
+pub use keys::KeyOptions;
+
+/// Source index of lines read from stdin, which is always the first source.
+pub const STDIN_SOURCE: usize = 0;
+
+/// The most lines moved from reader tasks into the buffer between two frames.
+const MAX_INGEST_PER_FRAME: usize = 100_000;
+
+/// A filter used often enough to name, applied with the number key for its position in the
+/// config file or with `:preset NAME`, e.g.
+///
+/// ```toml
+/// [[filter]]
+/// name = "errors"
+/// filter = "ERROR"
+/// ```
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FilterPreset {
+    pub name: String,
+    pub filter: String,
+}
+
+#[derive(Copy, Clone)]
+pub enum Mode {
+    Normal, // Manual scrolling and searching
+    Search, // Command/search entry
+    Filter, // Filter expression entry
+    Command, // `:` command line entry
+    Highlight, // Highlight group pattern entry
+    Block, // Selecting a rectangle of text to yank
+    Frequency, // Browsing the most frequent messages
+    Words, // Browsing the most frequent words
+    Captures, // Browsing the values captured by the search's groups
+    Query, // Browsing the results of a `:sql` query
+    Menu, // Choosing an action for the line under the cursor
+    Sources, // Choosing which sources to show in the sources panel
+    ConfirmQuit, // Asking whether to print a large amount of output on exit
+}
+
+impl Mode {
+    pub fn status_text(&self) -> &'static str {
+        match self {
+            Mode::Normal => "NORMAL",
+            Mode::Search => "SEARCH",
+            Mode::Filter => "FILTER",
+            Mode::Command => "COMMAND",
+            Mode::Highlight => "HIGHLIGHT",
+            Mode::Block => "BLOCK",
+            Mode::Frequency => "FREQUENCY",
+            Mode::Words => "WORDS",
+            Mode::Captures => "CAPTURES",
+            Mode::Query => "QUERY",
+            Mode::Menu => "MENU",
+            Mode::Sources => "SOURCES",
+            Mode::ConfirmQuit => "QUIT",
+        }
+    }
+}
+
+/// A carve session with no terminal attached: the buffered lines, the panes onto them, and
+/// everything keys and commands change. A frontend feeds it keys and draws its state.
+pub struct App {
+    pub lines: Vec<line::Line>,
+    pub incoming: mpsc::UnboundedReceiver<line::Line>, // Lines sent by reader tasks, drained each frame
+    incoming_tx: mpsc::UnboundedSender<line::Line>,
+    pub next_id: u64,
+    pub join_on: Option<regex::Regex>, // Matches the first line of each multi-line record
+    records: Vec<u64>, // ID of the record currently being read from each source
+    pub numbers: Vec<u64>, // Lines read so far from each source
+    times: Vec<Option<chrono::NaiveDateTime>>, // The time of the last line from each source with a timestamp
+    pub merge: bool, // Order lines from every source by when they were written rather than arrived
+    pub selected_source: usize, // The source under the cursor in the sources panel
+    pub sources: Vec<String>, // Names of the inputs lines have been read from
+    next_source: Arc<AtomicUsize>, // Shared with reader tasks, which can add sources too
+    new_sources: mpsc::UnboundedReceiver<(usize, String)>, // Sources added by reader tasks
+    new_sources_tx: mpsc::UnboundedSender<(usize, String)>,
+    pub files: HashSet<usize>, // Sources opened from files, which are named by their paths
+    pub samplers: HashMap<usize, sample::Sampler>, // How chatty sources are sampled, set with `:sample`
+    pub keymap: keymap::Keymap, // Key sequences bound to commands in the config file
+    pub plugins: Vec<plugin::Plugin>, // Run over each line as it's ingested, in order
+    pub scripts: script::Scripts, // Filter lines after the plugins, colour them and run with `:call`
+    pub tally: Option<history::Tally>, // Totals for the session history, with `--history`
+    pub last_session: Option<history::Session>, // The profile's previous session, to compare with
+    pub mode: Mode,
+    pub panes: Vec<pane::Pane>,
+    pub focus: usize, // Index of the pane keys apply to
+    pub side_by_side: bool, // Whether panes are split side by side rather than one above another
+    pub fold_duplicates: bool,
+    pub fold_blocks: bool,
+    pending_key: Option<char>, // First key of a two key sequence such as `za`
+    goto: Option<u64>, // ID of a line to move to once it has arrived
+    deleted: Vec<(Vec<line::Line>, Vec<u64>)>, // Lines deleted with `x`, a batch per deletion with the IDs of those pinned, to restore with `r`
+    pub wrap: bool,
+    pub message: Option<String>, // One-off feedback shown in the status bar until the next key press
+    pub command: String,
+    pub comparison: Option<compare::Comparison>,
+    pub diff: Option<diff::Diff>,
+    pub frequencies: Option<freq::Frequencies>, // Counted only while the panel is open
+    pub words: Option<words::Words>, // Counted only while the word cloud is open
+    pub captures: Option<captures::Captures>, // Counted only while the panel is open
+    pub query: Option<query::LiveQuery>, // Run again as lines arrive, while the results are shown
+    pub menu: Option<menu::Menu>, // The actions for the line under the cursor, while choosing one
+    pub edit: Option<quickfix::Location>, // A location to open in an editor once the key is handled
+    pub yank: Option<String>, // Text to copy once the key is handled
+    pub stopwords: Vec<String>, // Words left out of the word cloud, from the config file
+    pub levels: Option<level::Histogram>, // Counted only while the panel is showing
+    pub anomalies: Option<anomaly::Rarity>, // Flags unusual lines when set
+    pub table: Option<table::Table>, // Lays lines out in columns when set
+    pub groups: groups::Groups,
+    pub selection: Option<selection::Block>, // The block being selected in the focused pane
+    pub picking: bool, // Whether Enter picks lines to print, for `--pick`
+    pub marks: BTreeSet<u64>, // IDs of the lines marked with `m`, or Tab when picking
+    pub output: options::Output, // Which lines to print on exit
+    pub destination: String, // Where the lines printed on exit go, for asking before printing them
+    pub picked: bool, // Whether lines were picked, rather than quitting without
+    pub presets: Vec<FilterPreset>, // Named filters from the config file
+    pub highlight: bool,
+    pub rank: bool, // Order lines passing fuzzy filters by how well they match
+    pub pinned: BTreeSet<usize>, // Indices of lines exempt from eviction
+    pub show_pinned: bool,
+    pub show_hex: bool, // Show a hex dump of the line under the cursor
+    pub show_scrollbar: bool,
+    pub show_timestamps: bool, // Show when each line arrived in front of it, and write it with `:w`
+    pub gap: Option<Duration>, // Mark where consecutive lines are further apart than this
+    pub only_matching: bool, // Show just the parts of lines matched by the filter or search
+    pub fields: Option<fields::Fields>, // Show just these fields of each line
+    pub tab_width: usize,
+    pub control_chars: options::ControlChars, // How control characters other than tabs are shown
+    pub show_clock: bool,
+    pub show_rate: bool,
+    pub show_rate_chart: bool,
+    pub rate: rate::Rate,
+    pub counters: counters::Counters, // Patterns whose lines are counted as they're read
+    pub alerts: alerts::Alerts,
+    pub show_elapsed: bool,
+    pub session_start: Instant,
+    pub max_lines: Option<usize>,
+    pub paused: bool, // Hold new lines back from the buffer, for reading without them moving things
+    pub held: VecDeque<line::Line>, // Lines read while paused, to add once resumed
+    pub exporter: Option<Arc<Mutex<export::Exporter>>>,
+    pub control: Option<mpsc::UnboundedReceiver<control::Request>>, // Requests from `--control` clients
+}
+
+impl App {
+    pub fn new(max_lines: Option<usize>, exporter: Option<Arc<Mutex<export::Exporter>>>) -> Self {
+        let (incoming_tx, incoming) = mpsc::unbounded_channel();
+        let (new_sources_tx, new_sources) = mpsc::unbounded_channel();
+        Self {
+            lines: Vec::new(),
+            incoming,
+            incoming_tx,
+            next_id: 1,
+            join_on: None,
+            records: Vec::new(),
+            numbers: Vec::new(),
+            times: Vec::new(),
+            merge: false,
+            selected_source: 0,
+            sources: vec!["stdin".to_string()],
+            next_source: Arc::new(AtomicUsize::new(1)),
+            new_sources,
+            new_sources_tx,
+            files: HashSet::new(),
+            samplers: HashMap::new(),
+            keymap: keymap::Keymap::new(keymap::DEFAULT_TIMEOUT),
+            plugins: Vec::new(),
+            scripts: script::Scripts::default(),
+            tally: None,
+            last_session: None,
+            mode: Mode::Normal,
+            panes: vec![pane::Pane::new()],
+            focus: 0,
+            side_by_side: false,
+            fold_duplicates: false,
+            fold_blocks: false,
+            pending_key: None,
+            goto: None,
+            deleted: Vec::new(),
+            wrap: false,
+            message: None,
+            command: String::new(),
+            comparison: None,
+            diff: None,
+            frequencies: None,
+            words: None,
+            captures: None,
+            query: None,
+            menu: None,
+            edit: None,
+            yank: None,
+            stopwords: Vec::new(),
+            levels: None,
+            anomalies: None,
+            table: None,
+            groups: groups::Groups::default(),
+            selection: None,
+            picking: false,
+            marks: BTreeSet::new(),
+            output: options::Output::Filtered,
+            destination: "stdout".to_string(),
+            picked: false,
+            presets: Vec::new(),
+            highlight: true,
+            rank: false,
+            pinned: BTreeSet::new(),
+            show_pinned: true,
+            show_hex: false,
+            show_scrollbar: true,
+            show_timestamps: false,
+            gap: None,
+            only_matching: false,
+            fields: None,
+            tab_width: 8,
+            control_chars: options::ControlChars::Visible,
+            show_clock: false,
+            show_rate: true,
+            show_rate_chart: false,
+            rate: rate::Rate::new(),
+            counters: counters::Counters::default(),
+            alerts: alerts::Alerts::default(),
+            show_elapsed: false,
+            session_start: Instant::now(),
+            max_lines,
+            paused: false,
+            held: VecDeque::new(),
+            exporter,
+            control: None,
+        }
+    }
+
+    /// Move lines sent by reader tasks into the buffer. At most `MAX_INGEST_PER_FRAME` lines are
+    /// taken at once so a flood of input can't stall the UI.
+    pub fn ingest(&mut self) {
+        let mut count = 0;
+        for _ in 0..MAX_INGEST_PER_FRAME {
+            let Ok(line) = self.incoming.try_recv() else {
+                break;
+            };
+            count += 1;
+            if let Some(line) = self.admit(line) {
+                if self.paused {
+                    self.hold(line);
+                } else {
+                    self.push_line(line);
+                }
+            }
+        }
+        if count > 0 {
+            tracing::debug!(count, buffered = self.lines.len(), "ingested");
+        }
+        self.rate.record(count);
+        self.counters.tick();
+        if let Some(tally) = &mut self.tally {
+            tally.peak_rate = tally.peak_rate.max(self.rate.per_second());
+        }
+    }
+
+    /// Keep a line read while paused until ingestion resumes. Once there are more than
+    /// `--max-lines`, the oldest are dropped as they would have been evicted anyway.
+    fn hold(&mut self, line: line::Line) {
+        self.held.push_back(line);
+        if self.max_lines.is_some_and(|max| self.held.len() > max) {
+            self.held.pop_front();
+        }
+    }
+
+    /// Pause or resume adding new lines to the buffer. Lines read while paused are added when
+    /// it resumes.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        if !paused {
+            for line in std::mem::take(&mut self.held) {
+                self.push_line(line);
+            }
+        }
+    }
+
+    /// Sample a line read from a source and run it through the plugins and the scripts'
+    /// filters, returning `None` if it's dropped by any of them.
+    pub fn admit(&mut self, mut line: line::Line) -> Option<line::Line> {
+        while let Ok((source, name)) = self.new_sources.try_recv() {
+            self.name_source(source, name);
+        }
+        if self.samplers.get_mut(&line.source).is_some_and(|sampler| !sampler.keep(&line)) {
+            return None;
+        }
+        for plugin in &self.plugins {
+            match plugin.transform(&line.text) {
+                plugin::Transform::Keep => {}
+                plugin::Transform::Replace(text) => {
+                    line.text = text;
+                    line.raw = None;
+                }
+                plugin::Transform::Drop => return None,
+            }
+        }
+        match self.scripts.filter(&line.text) {
+            script::Verdict::Keep => {}
+            script::Verdict::Replace(text) => {
+                line.text = text;
+                line.raw = None;
+            }
+            script::Verdict::Drop => return None,
+        }
+        Some(line)
+    }
+
+    pub fn push_line(&mut self, mut line: line::Line) {
+        if let Some(tally) = &mut self.tally {
+            tally.record(&line.text);
+        }
+        self.counters.record(&line);
+        let triggered = self.alerts.check(&line);
+        line.id = self.next_id;
+        self.next_id += 1;
+        if triggered.mark {
+            self.marks.insert(line.id);
+        }
+        // Only stop at the first such line, rather than pulling the view away from it to the next
+        if let Some(pattern) = triggered.jump.filter(|_| self.panes[self.focus].tailing) {
+            self.panes[self.focus].tailing = false;
+            self.goto = Some(line.id);
+            self.message = Some(format!("Stopped at a line matching {}", pattern));
+        }
+        line.record = line.id;
+        if self.numbers.len() <= line.source {
+            self.numbers.resize(line.source + 1, 0);
+        }
+        self.numbers[line.source] += 1;
+        line.number = self.numbers[line.source];
+        // A line without a timestamp, such as part of a stack trace, was written along with the
+        // last line from its source that had one
+        if self.times.len() <= line.source {
+            self.times.resize(line.source + 1, None);
+        }
+        line.time = timestamp::parse(&line.text).or(self.times[line.source]);
+        self.times[line.source] = line.time;
+        if let Some(join_on) = &self.join_on {
+            if self.records.len() <= line.source {
+                self.records.resize(line.source + 1, 0);
+            }
+            let record = &mut self.records[line.source];
+            if *record == 0 || join_on.is_match(&line.text) {
+                *record = line.id;
+            }
+            line.record = *record;
+        }
+        self.lines.push(line);
+    }
+
+    /// The lines printed on exit: those passing the focused pane's filter, or whole records with
+    /// a line passing it when lines are joined, unless `--output` or `O` chose marked lines or
+    /// everything instead.
+    pub fn output(&self, collapse_duplicates: bool) -> Vec<&line::Line> {
+        let lines = region::Region::All.lines(&self.lines);
+        let table = self.table.as_ref();
+        let records: HashSet<u64> = match self.join_on.as_ref().filter(|_| self.output == options::Output::Filtered) {
+            Some(_) => lines
+                .iter()
+                .filter(|line| self.pane().passes(line, table))
+                .map(|line| line.record)
+                .collect(),
+            None => HashSet::new(),
+        };
+        let visible = |line: &&line::Line| match (self.output, &self.join_on) {
+            (options::Output::All, _) => true,
+            (options::Output::Marked, _) => self.marks.contains(&line.id),
+            (options::Output::Filtered, Some(_)) => records.contains(&line.record),
+            (options::Output::Filtered, None) => self.pane().passes(line, table),
+        };
+        let mut previous: Option<&str> = None;
+        lines
+            .iter()
+            .filter(visible)
+            .filter(|line| {
+                let duplicate = collapse_duplicates && previous == Some(line.text.as_str());
+                previous = Some(&line.text);
+                !duplicate
+            })
+            .collect()
+    }
+
+    /// Answer any requests from `--control` clients, returning whether there were any so the
+    /// changes they made can be drawn.
+    pub fn serve_control(&mut self) -> bool {
+        let mut requests = Vec::new();
+        if let Some(control) = &mut self.control {
+            while let Ok(request) = control.try_recv() {
+                requests.push(request);
+            }
+        }
+        let served = !requests.is_empty();
+        for request in requests {
+            // Bring the view up to date with any earlier request that changed it
+            self.refresh();
+            let reply = self.answer(request.query);
+            // The client may have gone away while waiting, which is fine
+            let _ = request.reply.send(reply);
+        }
+        served
+    }
+
+    fn answer(&mut self, query: control::Query) -> control::Reply {
+        match query {
+            control::Query::Counts => Ok(self
+                .counts()
+                .into_iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect()),
+            control::Query::State => Ok(self
+                .snapshot()
+                .into_iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect()),
+            control::Query::Location => Ok(vec![self.location()?.to_string()]),
+            control::Query::Quickfix => Ok(self.quickfix()),
+            control::Query::Export => Ok(self.output(false).iter().map(|line| line.text.clone()).collect()),
+            control::Query::Command(input) => {
+                for input in command::split_commands(&input) {
+                    tracing::debug!(input, "control command");
+                    command::Command::parse(input).and_then(|command| self.run(command))?;
+                }
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    /// Whether any line read has passed the focused pane's filter, for the exit status of `-q`.
+    pub fn matched(&self) -> bool {
+        self.lines.iter().any(|line| self.pane().passes(line, self.table.as_ref()))
+    }
+
+    /// Save the lines that would be printed on exit to a file, creating any missing directories.
+    fn write(&mut self, path: &std::path::Path, mode: command::WriteMode) -> Result<(), String> {
+        let fail = |e: io::Error| format!("failed to write {}: {}", path.display(), e);
+        if mode == command::WriteMode::Create && path.exists() {
+            return Err(format!("{} exists, use :w! to overwrite it or :w >>{} to append", path.display(), path.display()));
+        }
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(fail)?;
+        }
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(mode == command::WriteMode::Append)
+            .truncate(mode != command::WriteMode::Append)
+            .open(path)
+            .map_err(fail)?;
+        let output = self.output(false);
+        let mut out = io::BufWriter::new(file);
+        output
+            .iter()
+            .try_for_each(|line| {
+                if self.show_timestamps {
+                    writeln!(out, "{}", self.annotated(line, &[options::Annotation::Time]))
+                } else {
+                    writeln!(out, "{}", line.text)
+                }
+            })
+            .and_then(|_| out.flush())
+            .map_err(fail)?;
+        let verb = if mode == command::WriteMode::Append { "Appended" } else { "Wrote" };
+        self.message = Some(format!("{} {} lines to {}", verb, output.len(), path.display()));
+        Ok(())
+    }
+
+    /// Write every line in the buffer, whatever the filter, to a new file named after the time
+    /// in the working directory. Lines that weren't valid UTF-8 are written as they were read.
+    fn snapshot_buffer(&mut self) -> Result<(), String> {
+        let path = std::path::PathBuf::from(chrono::Local::now().format("carve-snapshot-%Y%m%d-%H%M%S.log").to_string());
+        let fail = |e: io::Error| format!("failed to write {}: {}", path.display(), e);
+        let file = OpenOptions::new().write(true).create_new(true).open(&path).map_err(fail)?;
+        let lines = region::Region::All.lines(&self.lines);
+        let mut out = io::BufWriter::new(file);
+        lines
+            .iter()
+            .try_for_each(|line| {
+                match &line.raw {
+                    Some(raw) => out.write_all(raw),
+                    None => out.write_all(line.text.as_bytes()),
+                }?;
+                out.write_all(b"\n")
+            })
+            .and_then(|_| out.flush())
+            .map_err(fail)?;
+        self.message = Some(format!("Snapshot of {} lines written to {}", lines.len(), path.display()));
+        Ok(())
+    }
+
+    /// Start quitting: check how much will be printed on exit, and whether the export failed.
+    /// Returns whether to quit straight away, or asks for confirmation first if it's more than
+    /// `confirm_over` lines.
+    pub fn quit(&mut self, confirm_over: Option<usize>, collapse_duplicates: bool) -> bool {
+        // Only looked at, so it's still reported once carve has exited
+        let export = match self.exporter.as_ref().map(|exporter| exporter.lock().unwrap()) {
+            Some(exporter) => match exporter.error() {
+                Some(e) => format!(" (export failed: {})", e),
+                None => String::new(),
+            },
+            None => String::new(),
+        };
+        let output = self.output(collapse_duplicates);
+        if confirm_over.is_none_or(|max| output.len() <= max) && export.is_empty() {
+            return true;
+        }
+        let bytes: usize = output.iter().map(|line| line.text.len() + 1).sum();
+        self.message = Some(format!(
+            "Print {} lines ({:.1} MB) to {}{}? y/n",
+            output.len(),
+            bytes as f64 / (1 << 20) as f64,
+            self.destination,
+            export,
+        ));
+        self.mode = Mode::ConfirmQuit;
+        false
+    }
+
+    /// Format a line for the exit output, prefixed with the requested provenance fields.
+    pub fn annotated(&self, line: &line::Line, annotations: &[options::Annotation]) -> String {
+        let mut out = String::new();
+        for annotation in annotations {
+            match annotation {
+                options::Annotation::Id => out.push_str(&line.id.to_string()),
+                options::Annotation::Time => out.push_str(
+                    &chrono::DateTime::<chrono::Local>::from(line.arrived)
+                        .to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+                ),
+                options::Annotation::Source => out.push_str(&self.sources[line.source]),
+            }
+            out.push('\t');
+        }
+        out.push_str(&line.text);
+        out
+    }
+
+    /// A handle reader tasks can use to append lines to the buffer.
+    pub fn sink(&self) -> source::Sink {
+        source::Sink {
+            tx: self.incoming_tx.clone(),
+            exporter: self.exporter.clone(),
+            next_source: self.next_source.clone(),
+            new_sources: self.new_sources_tx.clone(),
+        }
+    }
+
+    pub fn add_source(&mut self, name: String) -> usize {
+        let source = self.next_source.fetch_add(1, Ordering::Relaxed);
+        self.name_source(source, name);
+        source
+    }
+
+    fn name_source(&mut self, source: usize, name: String) {
+        if self.sources.len() <= source {
+            self.sources.resize(source + 1, String::new());
+        }
+        self.sources[source] = name;
+    }
+
+    fn source_named(&self, name: &str) -> Result<usize, String> {
+        self.sources
+            .iter()
+            .position(|source| source == name)
+            .ok_or_else(|| format!("no source named {}", name))
+    }
+
+    /// Start following a file as an additional source, returning its index.
+    pub fn open(&mut self, path: &std::path::Path) -> Result<usize, String> {
+        let file = std::fs::File::open(path)
+            .map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+        let source = self.add_source(path.display().to_string());
+        self.files.insert(source);
+        source::spawn_file(file, self.sink(), source);
+        Ok(source)
+    }
+
+    /// How many lines have been read, are buffered, pass the filter, are marked and match the
+    /// search.
+    pub fn counts(&self) -> Vec<(&'static str, usize)> {
+        let pane = self.pane();
+        vec![
+            ("read", (self.next_id - 1) as usize),
+            ("buffered", self.lines.len()),
+            ("filtered", pane.visible_len()),
+            ("marked", self.marks.len()),
+            ("matches", pane.search.matches().len()),
+        ]
+    }
+
+    /// The summary line printed with `--summary`, filling in the counts, how many lines were
+    /// printed and how long the session lasted. How many lines passed the filter is given, as
+    /// lines aren't kept without a terminal.
+    pub fn summary(&self, format: &str, filtered: usize, printed: usize) -> String {
+        let elapsed = self.session_start.elapsed().as_secs();
+        let mut summary = format
+            .replace("{filtered}", &filtered.to_string())
+            .replace("{printed}", &printed.to_string())
+            .replace("{elapsed}", &format_elapsed(elapsed))
+            .replace("{seconds}", &elapsed.to_string());
+        for (name, count) in self.counts() {
+            summary = summary.replace(&format!("{{{}}}", name), &count.to_string());
+        }
+        summary
+    }
+
+    /// Start following a streaming HTTP endpoint as an additional source, returning its index.
+    pub fn follow_url(&mut self, url: &str) -> usize {
+        let source = self.add_source(url.to_string());
+        http::spawn(url.to_string(), self.sink(), source);
+        source
+    }
+
+    /// Start following a Docker container's logs as two additional sources, for its stdout and
+    /// stderr, returning their indices.
+    pub fn follow_container(&mut self, container: &str) -> (usize, usize) {
+        let stdout = self.add_source(format!("{}:stdout", container));
+        let stderr = self.add_source(format!("{}:stderr", container));
+        docker::spawn(container.to_string(), self.sink(), stdout, stderr);
+        (stdout, stderr)
+    }
+
+    /// Start following the pods matching a workload or label selector, returning the index of
+    /// the source problems finding them are noted in. Each pod is added as a source of its own.
+    pub fn follow_pods(&mut self, target: &str) -> usize {
+        let source = self.add_source(target.to_string());
+        kube::spawn(target.to_string(), self.sink(), source);
+        source
+    }
+
+    /// Start following the systemd journal, or one unit in it, as an additional source,
+    /// returning its index.
+    pub fn follow_journal(&mut self, unit: Option<&str>) -> usize {
+        let source = self.add_source(match unit {
+            Some(unit) => format!("journal:{}", unit),
+            None => "journal".to_string(),
+        });
+        journal::spawn(unit.map(str::to_string), self.sink(), source);
+        source
+    }
+
+    /// Take lines sent to a socket or FIFO as an additional source, returning its index.
+    pub fn listen(&mut self, path: &std::path::Path) -> Result<usize, String> {
+        let source = self.add_source(path.display().to_string());
+        source::listen(path, self.sink(), source).map_err(|e| format!("failed to listen on {}: {}", path.display(), e))?;
+        Ok(source)
+    }
+
+    /// Receive syslog messages as an additional source, returning its index.
+    pub fn receive_syslog(&mut self, address: &str) -> Result<usize, String> {
+        let source = self.add_source(format!("syslog {}", address));
+        syslog::listen(address, self.sink(), source).map_err(|e| format!("failed to listen on {}: {}", address, e))?;
+        Ok(source)
+    }
+
+    /// Start following a WebSocket as an additional source, returning its index.
+    pub fn follow_websocket(&mut self, url: &str) -> usize {
+        let source = self.add_source(url.to_string());
+        ws::spawn(url.to_string(), self.sink(), source);
+        source
+    }
+
+    /// Run the commands in a script file, stopping at the first that fails.
+    fn source(&mut self, path: &std::path::Path) -> Result<(), String> {
+        let script = std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        for (number, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            for input in command::split_commands(line) {
+                command::Command::parse(input)
+                    .and_then(|command| self.run(command))
+                    .map_err(|e| format!("{}:{}: {}", path.display(), number + 1, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Run a script function, as a key binding would, and then the commands it returns. It's
+    /// given the line under the cursor, the filters and the search as a map.
+    fn call(&mut self, name: &str) -> Result<(), String> {
+        let pane = self.pane();
+        let line = pane.cursor_line().map(|idx| &self.lines[idx]);
+        let state = rhai::Map::from_iter([
+            ("line".into(), line.map_or(rhai::Dynamic::UNIT, |line| line.text.clone().into())),
+            ("source".into(), line.map_or(rhai::Dynamic::UNIT, |line| self.sources[line.source].clone().into())),
+            ("filter".into(), pane.describe_filters().into()),
+            ("search".into(), pane.search_query.clone().into()),
+        ]);
+        for input in self.scripts.action(name, state)? {
+            for input in command::split_commands(&input) {
+                command::Command::parse(input)
+                    .and_then(|command| self.run(command))
+                    .map_err(|e| format!("{}: {}", name, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Run a command line, reporting any error in the status bar.
+    pub fn execute(&mut self, input: &str) {
+        for input in command::split_commands(input) {
+            tracing::debug!(input, "command");
+            if let Err(e) = command::Command::parse(input).and_then(|command| self.run(command)) {
+                self.message = Some(e);
+                return;
+            }
+        }
+    }
+
+    pub fn run(&mut self, command: command::Command) -> Result<(), String> {
+        match command {
+            command::Command::Compare(patterns) => {
+                self.comparison = patterns.map(|(a, b)| compare::Comparison::new(a, b));
+            }
+            command::Command::Diff(sources) => {
+                self.diff = match sources {
+                    Some((a, b)) => Some(diff::Diff::new(self.source_named(&a)?, self.source_named(&b)?)),
+                    None => None,
+                };
+            }
+            command::Command::Open(path) => {
+                self.open(&path)?;
+            }
+            command::Command::Record(path) => {
+                let count = replay::record(&path, &self.lines)
+                    .map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+                self.message = Some(format!("Recorded {} lines to {}", count, path.display()));
+            }
+            command::Command::Replay(path, speed) => {
+                let contents = std::fs::read(&path).map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+                let source = self.add_source(format!("replay {}", path.display()));
+                replay::spawn(contents, speed, self.sink(), source);
+            }
+            command::Command::Source(path) => self.source(&path)?,
+            command::Command::Call(name) => self.call(&name)?,
+            command::Command::Read(command) => {
+                let source = self.add_source(format!("!{}", command));
+                source::spawn_command(command, self.sink(), source);
+            }
+            command::Command::Filter(filter) => self.pane_mut().filter = filter,
+            command::Command::Sample(name, policy) => {
+                let source = self.source_named(&name)?;
+                self.message = Some(match policy {
+                    Some(policy) => {
+                        self.samplers.insert(source, sample::Sampler::new(policy));
+                        format!("Sampling {} at {}", name, policy.describe())
+                    }
+                    None => {
+                        self.samplers.remove(&source);
+                        format!("Keeping every line of {}", name)
+                    }
+                });
+            }
+            command::Command::Hide(name) => {
+                let source = self.source_named(&name)?;
+                self.pane_mut().set_source_hidden(source, true);
+            }
+            command::Command::Show(Some(name)) => {
+                let source = self.source_named(&name)?;
+                self.pane_mut().set_source_hidden(source, false);
+            }
+            command::Command::Show(None) => {
+                for source in 0..self.sources.len() {
+                    self.pane_mut().set_source_hidden(source, false);
+                }
+            }
+            command::Command::Preset(name) => {
+                let preset = self.presets.iter().position(|preset| preset.name == name);
+                self.apply_preset(preset.ok_or_else(|| format!("no filter preset named {}", name))?);
+            }
+            command::Command::Highlight(Some(pattern)) => self.add_highlight(pattern),
+            command::Command::Highlight(None) => self.groups.clear(),
+            command::Command::Count(Some(pattern)) => self.counters.add(pattern)?,
+            command::Command::Count(None) => self.counters.clear(),
+            command::Command::Alert(Some(pattern)) => {
+                let rule = alerts::AlertRule {
+                    pattern,
+                    bell: None,
+                    flash: None,
+                    notify: false,
+                    run: None,
+                    jump: false,
+                    mark: false,
+                };
+                self.alerts.add(&rule)?;
+            }
+            command::Command::Alert(None) => self.alerts.clear(),
+            command::Command::Search(query) => {
+                self.pane_mut().search_query = query;
+                self.update_search();
+                self.pane_mut().search_query.clear();
+            }
+            command::Command::Split { side_by_side } => self.split(side_by_side),
+            command::Command::Write(path, mode) => self.write(&path, mode)?,
+            command::Command::Location(None) => self.message = Some(self.location()?.to_string()),
+            command::Command::Location(Some(path)) => {
+                let location = self.location()?;
+                write_lines(&path, &[location.to_string()])?;
+                self.message = Some(format!("Wrote {} to {}", location, path.display()));
+            }
+            command::Command::Quickfix(path) => {
+                let entries = self.quickfix();
+                write_lines(&path, &entries)?;
+                self.message = Some(format!("Wrote {} locations to {}", entries.len(), path.display()));
+            }
+            command::Command::Only => self.only(),
+            command::Command::Goto(line) => {
+                self.goto = Some(line);
+                self.pane_mut().tailing = false;
+            }
+            command::Command::Table(delimiter) => {
+                self.table = Some(table::Table::new(Some(delimiter)));
+                self.panes.iter_mut().for_each(|pane| pane.view.reset());
+            }
+            command::Command::Fields(fields) => self.fields = fields,
+            command::Command::Sort(sort) => {
+                self.message = Some(match &sort {
+                    Some(sort) => format!("Sorted by {}", sort.describe()),
+                    None => "Back in the order lines arrived".to_string(),
+                });
+                self.pane_mut().set_sort(sort);
+            }
+            command::Command::Sql(sql) => {
+                self.query = Some(query::LiveQuery::new(&sql)?);
+                self.mode = Mode::Query;
+            }
+            command::Command::Set(option, toggle) => match option.as_str() {
+                "wrap" => toggle.apply(&mut self.wrap),
+                "tail" => toggle.apply(&mut self.pane_mut().tailing),
+                "pinned" => toggle.apply(&mut self.show_pinned),
+                "hex" => toggle.apply(&mut self.show_hex),
+                "scrollbar" => toggle.apply(&mut self.show_scrollbar),
+                "timestamps" => toggle.apply(&mut self.show_timestamps),
+                "only" => toggle.apply(&mut self.only_matching),
+                "searchall" => {
+                    let mut all = self.pane().search_all;
+                    toggle.apply(&mut all);
+                    self.panes.iter_mut().for_each(|pane| pane.set_search_all(all));
+                }
+                "unique" => {
+                    let mut unique = self.pane().unique;
+                    toggle.apply(&mut unique);
+                    self.pane_mut().set_unique(unique);
+                }
+                "pause" => {
+                    let mut paused = self.paused;
+                    toggle.apply(&mut paused);
+                    self.set_paused(paused);
+                }
+                "clock" => toggle.apply(&mut self.show_clock),
+                "highlight" => toggle.apply(&mut self.highlight),
+                "rank" => {
+                    toggle.apply(&mut self.rank);
+                    self.panes.iter_mut().for_each(|pane| pane.view.reset());
+                }
+                "merge" => {
+                    toggle.apply(&mut self.merge);
+                    self.panes.iter_mut().for_each(|pane| pane.view.reset());
+                }
+                "levels" => {
+                    let mut show = self.levels.is_some();
+                    toggle.apply(&mut show);
+                    if show != self.levels.is_some() {
+                        self.toggle_levels();
+                    }
+                }
+                "anomalies" => {
+                    let mut show = self.anomalies.is_some();
+                    toggle.apply(&mut show);
+                    if show != self.anomalies.is_some() {
+                        self.anomalies = show.then(anomaly::Rarity::default);
+                    }
+                }
+                "table" => {
+                    let mut show = self.table.is_some();
+                    toggle.apply(&mut show);
+                    if show != self.table.is_some() {
+                        self.toggle_table();
+                    }
+                }
+                "rate" => toggle.apply(&mut self.show_rate),
+                "ratechart" => toggle.apply(&mut self.show_rate_chart),
+                "elapsed" => toggle.apply(&mut self.show_elapsed),
+                "fold" => {
+                    let mut fold = self.fold_duplicates;
+                    toggle.apply(&mut fold);
+                    if fold != self.fold_duplicates {
+                        self.toggle_fold_duplicates();
+                    }
+                }
+                "blocks" => {
+                    let mut fold = self.fold_blocks;
+                    toggle.apply(&mut fold);
+                    self.set_fold_blocks(fold);
+                }
+                _ => return Err(format!("unknown option: {}", option)),
+            },
+            command::Command::SetValue(option, value) => match option.as_str() {
+                "tabstop" => match value.parse() {
+                    Ok(width) if width > 0 => self.tab_width = width,
+                    _ => return Err(format!("tabstop must be a number of columns, not {}", value)),
+                },
+                "context" | "before" | "after" => {
+                    let lines = value.parse().map_err(|_| format!("{} must be a number of lines, not {}", option, value))?;
+                    for pane in &mut self.panes {
+                        if option != "after" {
+                            pane.context.before = lines;
+                        }
+                        if option != "before" {
+                            pane.context.after = lines;
+                        }
+                    }
+                }
+                "gap" if value == "off" => self.gap = None,
+                "gap" => self.gap = Some(options::parse_duration(&value)?),
+                "control" => {
+                    self.control_chars = <options::ControlChars as clap::ValueEnum>::from_str(&value, false)
+                        .map_err(|_| format!("control must be visible or strip, not {}", value))?;
+                }
+                _ => return Err(format!("unknown option: {}", option)),
+            },
+        }
+        Ok(())
+    }
+
+    /// The state a key press can change, for the action log.
+    pub fn snapshot(&self) -> actions::Snapshot {
+        let pane = self.pane();
+        vec![
+            ("mode", self.mode.status_text().to_string()),
+            ("focus", self.focus.to_string()),
+            ("panes", self.panes.len().to_string()),
+            ("cursor", pane.cursor.to_string()),
+            ("scroll", pane.scroll.to_string()),
+            ("tailing", pane.tailing.to_string()),
+            ("paused", self.paused.to_string()),
+            ("visible", pane.visible_len().to_string()),
+            ("filter", pane.describe_filters()),
+            ("search", pane.search_query.clone()),
+            ("match", format!("{}/{}", pane.current_match, pane.search.matches().len())),
+            ("command", self.command.clone()),
+            ("message", self.message.clone().unwrap_or_default()),
+        ]
+    }
+
+    /// The pane keys apply to.
+    pub fn pane(&self) -> &pane::Pane {
+        &self.panes[self.focus]
+    }
+
+    pub fn pane_mut(&mut self) -> &mut pane::Pane {
+        &mut self.panes[self.focus]
+    }
+
+    /// Split the focused pane in two, giving the new pane focus. The screen is divided in one
+    /// direction only, so splitting again changes the direction for every pane.
+    fn split(&mut self, side_by_side: bool) {
+        let pane = self.pane().split();
+        self.side_by_side = side_by_side;
+        self.focus += 1;
+        self.panes.insert(self.focus, pane);
+    }
+
+    /// Close every pane but the focused one.
+    fn only(&mut self) {
+        self.panes.swap(0, self.focus);
+        self.panes.truncate(1);
+        self.focus = 0;
+    }
+
+    /// Scroll the diff view, if it's showing, for a navigation key. Returns whether the key was
+    /// used.
+    fn scroll_diff(&mut self, code: KeyCode) -> bool {
+        let Some(diff) = &mut self.diff else {
+            return false;
+        };
+        let page = diff.height / 2;
+        match code {
+            KeyCode::Char('j') | KeyCode::Down => diff.scroll_down(1),
+            KeyCode::Char('k') | KeyCode::Up => diff.scroll_up(1),
+            KeyCode::Char('d' | ' ') | KeyCode::PageDown => diff.scroll_down(page),
+            KeyCode::Char('u' | 'b') | KeyCode::PageUp => diff.scroll_up(page),
+            KeyCode::Char('g') | KeyCode::Home => diff.scroll = 0,
+            KeyCode::Char('G') | KeyCode::End => diff.scroll_down(usize::MAX),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Filter the focused pane on the message selected in the frequency panel and close it.
+    fn apply_frequency(&mut self) {
+        if let Some(frequencies) = self.frequencies.take() {
+            if let Some((template, _)) = frequencies.top(freq::MAX_SHOWN).get(frequencies.selected) {
+                self.pane_mut().filter = freq::filter_for(template);
+            }
+        }
+        self.mode = Mode::Normal;
+    }
+
+    /// Filter the focused pane on the word selected in the word cloud, or search for it, and
+    /// close the cloud.
+    fn apply_word(&mut self, search: bool) {
+        if let Some(word) = self.words.take().and_then(|words| words.selected_word()) {
+            if search {
+                self.pane_mut().search_query = word;
+                self.update_search();
+                self.pane_mut().search_query.clear();
+                self.next_match();
+            } else {
+                self.pane_mut().filter = word;
+            }
+        }
+        self.mode = Mode::Normal;
+    }
+
+    /// Show the actions for the line under the cursor.
+    fn open_menu(&mut self) {
+        let Some(idx) = self.pane().cursor_line() else {
+            return;
+        };
+        let line = &self.lines[idx];
+        self.menu = Some(menu::Menu::new(
+            &line.text,
+            self.location().ok(),
+            self.pinned.contains(&idx),
+            self.marks.contains(&line.id),
+        ));
+        self.mode = Mode::Menu;
+    }
+
+    /// Do the action chosen from the menu and close it.
+    fn apply_menu(&mut self) {
+        self.mode = Mode::Normal;
+        let Some(action) = self.menu.take().map(menu::Menu::take_selected) else {
+            return;
+        };
+        match action {
+            menu::Action::Copy => {
+                if let Some(idx) = self.pane().cursor_line() {
+                    self.yank = Some(format!("{}\n", self.lines[idx].text));
+                }
+            }
+            menu::Action::Filter(filter) | menu::Action::Trace(filter) => self.pane_mut().filter = filter,
+            menu::Action::Exclude(filter) => {
+                let pane = self.pane_mut();
+                if !pane.filter.is_empty() {
+                    pane.push_filter();
+                }
+                pane.filter = format!("!{}", filter);
+            }
+            menu::Action::Edit(location) => self.edit = Some(location),
+            menu::Action::OpenUrl(url) => {
+                self.message = Some(match url::open(&url) {
+                    Ok(()) => format!("Opened {}", url),
+                    Err(e) => format!("Failed to open {}: {}", url, e),
+                });
+            }
+            menu::Action::Pin { .. } => self.toggle_pin(),
+            menu::Action::Mark { .. } => self.toggle_mark(),
+        }
+    }
+
+    /// Open the first URL on the line under the cursor in the browser.
+    fn open_url(&mut self) {
+        let line = self.pane().cursor_line().map(|idx| &self.lines[idx].text);
+        let Some((text, (start, end))) = line.and_then(|text| Some((text, *url::find(text).first()?))) else {
+            self.message = Some("No URL on this line".to_string());
+            return;
+        };
+        let url = &text[start..end];
+        self.message = Some(match url::open(url) {
+            Ok(()) => format!("Opened {}", url),
+            Err(e) => format!("Failed to open {}: {}", url, e),
+        });
+    }
+
+    /// Filter the focused pane with a preset from the config file, or go back to showing
+    /// everything if it's already applied.
+    fn apply_preset(&mut self, preset: usize) {
+        let Some(preset) = self.presets.get(preset) else {
+            self.message = Some(format!("No filter preset {}", preset + 1));
+            return;
+        };
+        let (filter, message) = if self.pane().filter == preset.filter {
+            (String::new(), format!("Cleared filter {}", preset.name))
+        } else {
+            (preset.filter.clone(), format!("Filter {}: {}", preset.name, preset.filter))
+        };
+        self.pane_mut().filter = filter;
+        self.message = Some(message);
+    }
+
+    /// Highlight a pattern in the next highlight group.
+    fn add_highlight(&mut self, pattern: String) {
+        if pattern.is_empty() {
+            return;
+        }
+        let group = self.groups.add(pattern.clone());
+        self.message = Some(format!("Highlighting {} in group {}", pattern, group));
+    }
+
+    /// Show or hide the panel counting lines at each log level.
+    fn toggle_levels(&mut self) {
+        self.levels = match self.levels {
+            Some(_) => None,
+            None => Some(level::Histogram::default()),
+        };
+    }
+
+    /// Switch between showing lines as they are and laid out in columns, guessing the delimiter
+    /// from the first line.
+    fn toggle_table(&mut self) {
+        self.table = match self.table {
+            Some(_) => None,
+            None => Some(table::Table::new(None)),
+        };
+        // Filters and sorting work differently on tables
+        self.panes.iter_mut().for_each(|pane| pane.view.reset());
+    }
+
+    /// Sort the table by the leftmost column in view, ascending, then descending, then not at all.
+    fn cycle_sort(&mut self) {
+        let Some(table) = &mut self.table else {
+            return;
+        };
+        if self.join_on.is_some() {
+            self.message = Some("Sorting isn't supported with --join-on".to_string());
+            return;
+        }
+        table.cycle_sort();
+        self.panes.iter_mut().for_each(|pane| pane.view.reset());
+    }
+
+    fn focus_next(&mut self) {
+        self.focus = (self.focus + 1) % self.panes.len();
+    }
+
+    /// Leave the unique view for the first or last line with the same text as the one under the
+    /// cursor.
+    fn jump_to_occurrence(&mut self, last: bool) {
+        let pane = self.pane();
+        let Some(occurrences) = pane.view.line_index(pane.cursor).and_then(|idx| pane.view.occurrences(idx)) else {
+            return;
+        };
+        let idx = if last { occurrences.last } else { occurrences.first };
+        self.goto = Some(self.lines[idx].id);
+        let pane = self.pane_mut();
+        pane.set_unique(false);
+        pane.tailing = false;
+        self.message = Some(format!(
+            "{} of {} occurrences",
+            if last { "Last" } else { "First" },
+            occurrences.count
+        ));
+    }
+
+    /// Catch derived state up with new lines and any change to the filter.
+    pub fn refresh(&mut self) {
+        let records = self.join_on.is_some();
+        let order = pane::Order { rank: self.rank, merge: self.merge };
+        for pane in &mut self.panes {
+            pane.refresh(&self.lines, self.fold_duplicates, self.fold_blocks, records, self.table.as_ref(), order);
+        }
+        if let Some(id) = self.goto.filter(|id| self.lines.last().is_some_and(|line| line.id >= *id)) {
+            // Land on the line, or the next one passing the filter if it doesn't
+            let pane = &mut self.panes[self.focus];
+            let row = pane.view.rows.iter().position(|row| self.lines[row.idx].id >= id);
+            pane.cursor = row.unwrap_or(pane.view.len().saturating_sub(1));
+            pane.scroll = pane.cursor;
+            self.goto = None;
+        }
+        if let Some(comparison) = &mut self.comparison {
+            comparison.update(&self.lines);
+        }
+        if let Some(diff) = &mut self.diff {
+            diff.update(&self.lines);
+        }
+        if let Some(frequencies) = &mut self.frequencies {
+            frequencies.update(region::Region::All.lines(&self.lines));
+        }
+        if let Some(words) = &mut self.words {
+            let pane = &self.panes[self.focus];
+            let view = format!("{} {} {}", self.focus, pane.view.resets(), pane.describe_filters());
+            let reordered = self.rank || self.table.as_ref().is_some_and(|table| table.sort.is_some());
+            words.update(&view, &pane.view.rows, &self.lines, reordered);
+        }
+        if let Some(captures) = &mut self.captures {
+            let pane = &self.panes[self.focus];
+            let search = format!("{} {} {} {}", self.focus, pane.view.resets(), pane.describe_filters(), pane.search.query());
+            captures.update(&search, pane.search.regex(), pane.search.matches(), &self.lines);
+        }
+        if let Some(query) = &mut self.query {
+            let pane = &self.panes[self.focus];
+            let view = format!("{} {} {}", self.focus, pane.view.resets(), pane.describe_filters());
+            query.update(&view, pane.view.rows.iter().map(|row| self.lines[row.idx].text.as_str()));
+        }
+        if let Some(levels) = &mut self.levels {
+            levels.update(region::Region::All.lines(&self.lines));
+        }
+        if let Some(anomalies) = &mut self.anomalies {
+            anomalies.update(&self.lines);
+        }
+        if let Some(table) = &mut self.table {
+            table.update(&self.lines);
+        }
+    }
+
+    /// Append text pasted into the terminal to the buffer, tagged as coming from a "paste" source.
+    /// Returns the number of lines added.
+    pub fn append_paste(&mut self, text: &str) -> usize {
+        let source = match self.sources.iter().position(|name| name == "paste") {
+            Some(source) => source,
+            None => self.add_source("paste".to_string()),
+        };
+        // Terminals commonly turn newlines into carriage returns in bracketed pastes.
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        let sink = self.sink();
+        text.lines().map(|line| sink.push(line.to_string(), source)).count()
+    }
+
+    /// Type pasted text, such as the primary selection pasted with a middle click, into the
+    /// search, filter or command being entered. Only the first line is used, as a pattern can't
+    /// span lines.
+    pub fn paste_into_prompt(&mut self, text: &str) {
+        let text = text.split(['\r', '\n']).find(|line| !line.is_empty()).unwrap_or_default();
+        match self.mode {
+            Mode::Search => {
+                self.pane_mut().search_query.push_str(text);
+                self.update_search();
+            }
+            Mode::Filter => self.pane_mut().filter.push_str(text),
+            Mode::Command | Mode::Highlight => self.command.push_str(text),
+            _ => {}
+        }
+    }
+
+    fn toggle_fold_duplicates(&mut self) {
+        self.fold_duplicates = !self.fold_duplicates;
+        self.panes.iter_mut().for_each(pane::Pane::refold);
+    }
+
+    /// Turn folding of stack traces and other multi-line blocks on or off.
+    fn set_fold_blocks(&mut self, fold: bool) {
+        if fold != self.fold_blocks {
+            self.fold_blocks = fold;
+            self.panes.iter_mut().for_each(pane::Pane::refold);
+        }
+    }
+
+    fn toggle_pin(&mut self) {
+        let Some(idx) = self.pane().cursor_line() else {
+            return;
+        };
+        if !self.pinned.remove(&idx) {
+            self.pinned.insert(idx);
+        }
+    }
+
+    /// Drop the oldest lines once the buffer grows past `max_lines`. Pinned lines are kept,
+    /// gathered at the front of the buffer in their original order.
+    pub fn evict(&mut self) {
+        let Some(max_lines) = self.max_lines else {
+            return;
+        };
+        if self.lines.len() <= max_lines {
+            return;
+        }
+
+        let excess = self.lines.len() - max_lines;
+        tracing::debug!(excess, pinned = self.pinned.len(), "evicting");
+        // Deleted lines as old as those evicted would have gone with them, unless pinned
+        let oldest = self.lines.get(excess).map_or(u64::MAX, |line| line.id);
+        for (lines, pinned) in &mut self.deleted {
+            lines.retain(|line| line.id >= oldest || pinned.contains(&line.id));
+        }
+        self.deleted.retain(|(lines, _)| !lines.is_empty());
+        let mut kept = Vec::new();
+        for (idx, line) in self.lines.drain(..excess).enumerate() {
+            if self.pinned.contains(&idx) {
+                kept.push(line);
+            }
+        }
+        let survivors = kept.len();
+        let removed = excess - survivors;
+        self.lines.splice(0..0, kept);
+
+        // Everything after the evicted range moves down by however many lines were dropped,
+        // pinned survivors land at the front in order.
+        let remap = |idx: usize| -> Option<usize> {
+            if idx >= excess {
+                Some(idx - removed)
+            } else {
+                self.pinned.range(..excess).position(|pinned| *pinned == idx)
+            }
+        };
+        let mut reset = false;
+        for pane in &mut self.panes {
+            reset |= pane.evicted(removed, remap);
+        }
+        self.pinned = (0..survivors).chain(self.pinned.range(excess..).map(|idx| idx - removed)).collect();
+
+        if let Some(comparison) = &mut self.comparison {
+            comparison.evicted(removed);
+        }
+        if let Some(diff) = &mut self.diff {
+            diff.evicted();
+        }
+        if let Some(frequencies) = &mut self.frequencies {
+            frequencies.evicted(removed);
+        }
+        if let Some(captures) = &mut self.captures {
+            captures.rescan();
+        }
+        if let Some(levels) = &mut self.levels {
+            levels.evicted(removed);
+        }
+        if let Some(anomalies) = &mut self.anomalies {
+            anomalies.evicted(removed);
+        }
+        if let Some(table) = &mut self.table {
+            table.evicted(removed);
+        }
+        // A folded view may have been reset by the eviction and need rebuilding before it's drawn
+        if reset {
+            self.refresh();
+        }
+    }
+
+    /// Delete the lines shown on rows `first` to `last` of the focused pane from the buffer,
+    /// keeping them so they can be put back with `r`.
+    fn delete_rows(&mut self, first: usize, last: usize) {
+        let shown: HashSet<usize> = (first..=last).filter_map(|row| self.pane().view.line_index(row)).collect();
+        let ids = || shown.iter().map(|&idx| self.lines[idx].id);
+        let (Some(start), Some(end)) = (ids().min(), ids().max()) else {
+            return;
+        };
+        // The rows may be sorted or filtered, so take the lines they span and keep those shown
+        let region = region::Region::Selection(start, end);
+        let indices: Vec<usize> = region.indices(&self.lines).filter(|idx| shown.contains(idx)).collect();
+
+        let pinned: Vec<u64> =
+            indices.iter().filter(|idx| self.pinned.contains(idx)).map(|&idx| self.lines[idx].id).collect();
+        let mut deleted = Vec::new();
+        let mut next = indices.iter().peekable();
+        for (idx, line) in std::mem::take(&mut self.lines).into_iter().enumerate() {
+            if next.next_if_eq(&&idx).is_some() {
+                deleted.push(line);
+            } else {
+                self.lines.push(line);
+            }
+        }
+        let removed = deleted.len();
+        let remap = |idx: usize| indices.binary_search(&idx).err().map(|before| idx - before);
+        for (pane_idx, pane) in self.panes.iter_mut().enumerate() {
+            // Unlike eviction, lines go from the middle of the view, so it stays where it was
+            let (scroll, cursor) = (pane.scroll, pane.cursor);
+            pane.evicted(removed, remap);
+            pane.scroll = scroll;
+            pane.cursor = if pane_idx == self.focus { first } else { cursor };
+        }
+        self.pinned = self.pinned.iter().filter_map(|&idx| remap(idx)).collect();
+
+        if let Some(comparison) = &mut self.comparison {
+            comparison.evicted(removed);
+        }
+        if let Some(diff) = &mut self.diff {
+            diff.evicted();
+        }
+        if let Some(frequencies) = &mut self.frequencies {
+            frequencies.evicted(removed);
+        }
+        if let Some(captures) = &mut self.captures {
+            captures.rescan();
+        }
+        if let Some(levels) = &mut self.levels {
+            levels.evicted(removed);
+        }
+        if let Some(anomalies) = &mut self.anomalies {
+            anomalies.evicted(removed);
+        }
+        if let Some(table) = &mut self.table {
+            table.evicted(removed);
+        }
+        self.deleted.push((deleted, pinned));
+        self.message = Some(format!("Deleted {} lines, r to restore", removed));
+        self.refresh();
+    }
+
+    /// Put back the lines deleted most recently, where they were in the buffer, and move the
+    /// cursor to them.
+    fn undo_delete(&mut self) {
+        let Some((deleted, mut pinned)) = self.deleted.pop() else {
+            self.message = Some("Nothing to restore".to_string());
+            return;
+        };
+        let restored = deleted.len();
+        let first_id = deleted[0].id;
+        // Lines that were pinned when they were deleted are pinned again
+        pinned.extend(self.pinned.iter().map(|&idx| self.lines[idx].id));
+
+        // Both are in arrival order, so merging them puts each line back in its place
+        let mut lines = Vec::with_capacity(self.lines.len() + restored);
+        let mut deleted = deleted.into_iter().peekable();
+        for line in std::mem::take(&mut self.lines) {
+            while let Some(earlier) = deleted.next_if(|earlier| earlier.id < line.id) {
+                lines.push(earlier);
+            }
+            lines.push(line);
+        }
+        lines.extend(deleted);
+        self.lines = lines;
+        self.pinned = pinned.iter().map(|id| self.lines.partition_point(|line| line.id < *id)).collect();
+
+        for pane in &mut self.panes {
+            pane.restored();
+        }
+        if let Some(comparison) = &mut self.comparison {
+            comparison.restored(restored);
+        }
+        if let Some(diff) = &mut self.diff {
+            diff.evicted();
+        }
+        if let Some(frequencies) = &mut self.frequencies {
+            frequencies.restored(restored);
+        }
+        if let Some(captures) = &mut self.captures {
+            captures.rescan();
+        }
+        if let Some(levels) = &mut self.levels {
+            levels.restored(restored);
+        }
+        if let Some(anomalies) = &mut self.anomalies {
+            anomalies.restored(restored);
+        }
+        if let Some(table) = &mut self.table {
+            table.restored(restored);
+        }
+        self.refresh();
+
+        let first = self.lines.partition_point(|line| line.id < first_id);
+        let pane = self.pane_mut();
+        pane.cursor = pane.view.rows.iter().position(|row| row.idx >= first).unwrap_or(pane.cursor);
+        pane.tailing = false;
+        self.message = Some(format!("Restored {} lines", restored));
+    }
+
+    /// Filter the focused pane on the values selected in the captures panel, a filter for each
+    /// value pushed onto the stack, and close the panel.
+    fn apply_captures(&mut self) {
+        if let Some(values) = self.captures.take().and_then(|captures| captures.selected_values()) {
+            let pane = self.pane_mut();
+            for value in values.into_iter().filter(|value| !value.is_empty()) {
+                pane.push_filter();
+                pane.filter = value;
+            }
+        }
+        self.mode = Mode::Normal;
+    }
+
+    fn update_search(&mut self) {
+        let pane = &mut self.panes[self.focus];
+        pane.update_search(&self.lines, self.table.as_ref());
+    }
+
+    fn next_match(&mut self) {
+        if self.pane_mut().next_match() {
+            self.mode = Mode::Normal;
+        }
+    }
+
+    /// Format the line of the current match with `context` lines either side, each prefixed with
+    /// its line number and the match line marked with `>`, ready for pasting into a chat or ticket.
+    fn current_match_context(&self, context: usize) -> Option<String> {
+        let pane = self.pane();
+        let (line_idx, _, _) = *pane.search.matches().get(pane.current_match)?;
+        let lines = &self.lines;
+        let range = region::Region::Around(lines[line_idx].id, context).indices(lines);
+        let start = range.start;
+        // Numbered by position in their source, as `:location` numbers them, which the buffer
+        // index isn't once lines have been evicted or sources interleaved
+        let width = lines[range.clone()].iter().map(|line| line.number).max().unwrap_or(0).to_string().len();
+
+        let mut out = String::new();
+        for (idx, line) in lines[range].iter().enumerate().map(|(i, l)| (start + i, l)) {
+            let marker = if idx == line_idx { '>' } else { ' ' };
+            out.push_str(&format!("{}{:>width$}  {}\n", marker, line.number, line.text));
+        }
+        Some(out)
+    }
+
+    /// Mark the line under the cursor to be printed on exit or picked, or unmark it, and move on
+    /// to the next.
+    fn toggle_mark(&mut self) {
+        let Some(idx) = self.pane().cursor_line() else {
+            return;
+        };
+        let id = self.lines[idx].id;
+        if !self.marks.remove(&id) {
+            self.marks.insert(id);
+        }
+        self.pane_mut().cursor_down(1);
+        self.pane_mut().tailing = false;
+    }
+
+    /// The lines printed on exit with `--pick`: those marked, or the one under the cursor.
+    pub fn picks(&self) -> Vec<&line::Line> {
+        if self.marks.is_empty() {
+            return self.pane().cursor_line().map(|idx| &self.lines[idx]).into_iter().collect();
+        }
+        region::Region::All.lines(&self.lines).iter().filter(|line| self.marks.contains(&line.id)).collect()
+    }
+
+    /// Start selecting a block from the line under the cursor.
+    fn start_selection(&mut self) {
+        self.selection = Some(selection::Block::new(self.pane().cursor));
+        self.pane_mut().tailing = false;
+        self.mode = Mode::Block;
+        self.message = Some("Move to a corner and press v to start selecting".to_string());
+    }
+
+    /// Fix the corner of the selection at the cursor, or stop selecting if it's already fixed.
+    fn anchor_selection(&mut self) {
+        let cursor = self.pane().cursor;
+        match &mut self.selection {
+            Some(selection) if !selection.anchored => {
+                selection.anchor = cursor;
+                selection.anchored = true;
+            }
+            _ => self.mode = Mode::Normal,
+        }
+    }
+
+    /// Move the column end of the selection, given its current column and the cursor line as
+    /// displayed.
+    fn move_selection(&mut self, motion: impl FnOnce(usize, &str) -> usize) {
+        let text = self.pane().cursor_line().map(|idx| self.display_text(idx)).unwrap_or_default();
+        if let Some(selection) = &mut self.selection {
+            selection.move_to(motion(selection.column, &text));
+        }
+    }
+
+    /// The selected block, one row per line with the trailing space trimmed.
+    fn selection_text(&self) -> Option<String> {
+        let selection = self.selection.as_ref()?;
+        let (first, last) = selection.rows(self.pane().cursor);
+        let rows = (first..=last).filter_map(|row| self.pane().view.line_index(row));
+        Some(rows.map(|idx| format!("{}\n", selection.cut(&self.display_text(idx)))).collect())
+    }
+
+    /// The text of a line as it's displayed, laid out in columns when the table is showing.
+    fn display_text(&self, idx: usize) -> String {
+        let text = &self.lines[idx].text;
+        match &self.table {
+            Some(table) => table.layout(text, &[ratatui::text::Span::raw(text.clone())]).iter().map(|span| span.content.as_ref()).collect(),
+            None => text.clone(),
+        }
+    }
+
+    /// The path of a source opened from a file.
+    fn file_path(&self, source: usize) -> Option<&str> {
+        self.files.contains(&source).then(|| self.sources[source].as_str())
+    }
+
+    /// Where the line under the cursor is, or refers to, for an editor to jump to: at the first
+    /// search match on it, if there is one.
+    fn location(&self) -> Result<quickfix::Location, String> {
+        let pane = self.pane();
+        let idx = pane.cursor_line().ok_or("No line under the cursor")?;
+        let line = &self.lines[idx];
+        let matches = pane.search.matches();
+        let first = matches.partition_point(|&(match_idx, _, _)| match_idx < idx);
+        let column = matches.get(first).filter(|&&(match_idx, _, _)| match_idx == idx).map(|&(_, start, _)| start + 1);
+        quickfix::Location::of(line, self.file_path(line.source), column)
+            .ok_or_else(|| "The line under the cursor isn't from a file and doesn't refer to one".to_string())
+    }
+
+    /// A quickfix entry for each search match in the focused pane's view or, without a search,
+    /// each line in it, leaving out those without a location.
+    fn quickfix(&self) -> Vec<String> {
+        let pane = self.pane();
+        let entry = |idx: usize, column: Option<usize>| {
+            let line = &self.lines[idx];
+            quickfix::Location::of(line, self.file_path(line.source), column).map(|location| location.entry(&line.text))
+        };
+        if pane.search.query().is_empty() {
+            return pane.view.rows.iter().filter_map(|row| entry(row.idx, None)).collect();
+        }
+        let shown: HashSet<usize> = pane.view.rows.iter().map(|row| row.idx).collect();
+        pane.search
+            .matches()
+            .iter()
+            .filter(|(idx, _, _)| shown.contains(idx))
+            .filter_map(|&(idx, start, _)| entry(idx, Some(start + 1)))
+            .collect()
+    }
+
+    /// The text of a JSON object, block or record around the line under the cursor.
+    fn object_at_cursor(&self, object: objects::Object) -> Result<String, String> {
+        if object == objects::Object::Record && self.join_on.is_none() {
+            return Err("Records need --join-on".to_string());
+        }
+        let idx = self.pane().cursor_line().ok_or("No line under the cursor")?;
+        object.find(&self.lines, idx).ok_or_else(|| format!("No {} under the cursor", object.name()))
+    }
+
+    fn prev_match(&mut self) {
+        if self.pane_mut().prev_match() {
+            self.mode = Mode::Normal;
+        }
+    }
+}
+
+/// A number of seconds as `HH:MM:SS`.
+pub fn format_elapsed(seconds: u64) -> String {
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// Replace the file at `path` with `lines`, for an editor to pick up.
+fn write_lines(path: &std::path::Path, lines: &[String]) -> Result<(), String> {
+    let mut text = lines.join("\n");
+    text.push('\n');
+    std::fs::write(path, text).map_err(|e| format!("failed to write {}: {}", path.display(), e))
+}
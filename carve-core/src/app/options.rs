@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use clap::ValueEnum;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Annotation {
+    /// The line's global ID, counting every line read from any source from 1
+    Id,
+    /// The local time the line was read, in RFC 3339 format
+    Time,
+    /// The name of the source the line was read from
+    Source,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Output {
+    /// The lines passing the focused pane's filter
+    Filtered,
+    /// The lines marked with Space
+    Marked,
+    /// Every line in the buffer
+    All,
+}
+
+impl Output {
+    pub fn name(self) -> &'static str {
+        match self {
+            Output::Filtered => "filtered",
+            Output::Marked => "marked",
+            Output::All => "all",
+        }
+    }
+
+    /// The next choice, for switching between them with a key.
+    pub fn next(self) -> Self {
+        match self {
+            Output::Filtered => Output::Marked,
+            Output::Marked => Output::All,
+            Output::All => Output::Filtered,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ControlChars {
+    /// In caret notation, such as `^[` for escape and `^M` for a carriage return
+    Visible,
+    /// Not at all
+    Strip,
+}
+
+/// Parse a duration with a unit of `ms`, `s`, `m` or `h`, e.g. `500ms` or `2.5s`. A bare number
+/// is in seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().map_err(|_| format!("invalid duration '{}'", s))?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return Err(format!("invalid duration '{}', expected a unit of ms, s, m or h", s)),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|_| format!("invalid duration '{}'", s))
+}
@@ -0,0 +1,117 @@
+use std::ffi::{c_int, CStr, CString};
+use std::path::{Path, PathBuf};
+
+/// The C signature of the function a plugin exports as `carve_transform`.
+///
+/// It's called with each line as it's read, as `len` bytes of UTF-8 without a line ending, and
+/// returns `KEEP` to leave the line alone, `DROP` to drop it or `REPLACE` to replace its text
+/// with the `*out_len` bytes at `*out`. The replacement stays owned by the plugin and only needs
+/// to last until the next call. Calls come from one thread, one line at a time.
+type TransformFn = unsafe extern "C" fn(line: *const u8, len: usize, out: *mut *const u8, out_len: *mut usize) -> c_int;
+
+const KEEP: c_int = 0;
+const REPLACE: c_int = 1;
+const DROP: c_int = 2;
+
+/// What a plugin did with a line.
+pub enum Transform {
+    Keep,
+    Replace(String),
+    Drop,
+}
+
+/// A dynamic library loaded from the plugins directory which transforms, annotates or filters
+/// lines as they're read, e.g. to decode fields in an in-house log format.
+pub struct Plugin {
+    pub name: String,
+    handle: *mut libc::c_void,
+    transform: TransformFn,
+}
+
+impl Plugin {
+    #[cfg(unix)]
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        let c_path = CString::new(path.as_os_str().as_encoded_bytes()).map_err(|e| e.to_string())?;
+        // SAFETY: dlopen runs the library's initialisers, which is what loading a plugin means,
+        // and is given a valid NUL-terminated path
+        let handle = unsafe { libc::dlopen(c_path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if handle.is_null() {
+            return Err(format!("failed to load plugin {}: {}", path.display(), dlerror()));
+        }
+        // SAFETY: the handle was just opened and the symbol name is NUL-terminated
+        let symbol = unsafe { libc::dlsym(handle, c"carve_transform".as_ptr()) };
+        if symbol.is_null() {
+            // SAFETY: nothing from the library is in use yet
+            unsafe { libc::dlclose(handle) };
+            return Err(format!("plugin {} doesn't export carve_transform", path.display()));
+        }
+        Ok(Self {
+            name,
+            handle,
+            // SAFETY: plugins promise `carve_transform` has the documented signature
+            transform: unsafe { std::mem::transmute::<*mut libc::c_void, TransformFn>(symbol) },
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn load(path: &Path) -> Result<Self, String> {
+        Err(format!("failed to load plugin {}: plugins need a Unix-like system", path.display()))
+    }
+
+    pub fn transform(&self, text: &str) -> Transform {
+        let mut out: *const u8 = std::ptr::null();
+        let mut out_len = 0;
+        // SAFETY: the line is valid for `len` bytes for the duration of the call, and the out
+        // pointers point at locals
+        let result = unsafe { (self.transform)(text.as_ptr(), text.len(), &mut out, &mut out_len) };
+        match result {
+            REPLACE if !out.is_null() => {
+                // SAFETY: the plugin promises `out` holds `out_len` bytes until its next call
+                let bytes = unsafe { std::slice::from_raw_parts(out, out_len) };
+                Transform::Replace(String::from_utf8_lossy(bytes).into_owned())
+            }
+            DROP => Transform::Drop,
+            KEEP | REPLACE => Transform::Keep,
+            other => {
+                tracing::warn!(plugin = self.name, result = other, "unknown result from plugin");
+                Transform::Keep
+            }
+        }
+    }
+}
+
+impl Drop for Plugin {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        // SAFETY: the plugin's function isn't called again once it's dropped
+        unsafe {
+            libc::dlclose(self.handle);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn dlerror() -> String {
+    // SAFETY: dlerror returns either null or a NUL-terminated message
+    let message = unsafe { libc::dlerror() };
+    if message.is_null() {
+        return "unknown error".to_string();
+    }
+    // SAFETY: checked for null above
+    unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned()
+}
+
+/// Load every plugin in `dir`, in name order so the order they run in is predictable. Having no
+/// plugins directory is the same as having an empty one.
+pub fn load_all(dir: &Path) -> Result<Vec<Plugin>, String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Ok(Vec::new());
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == std::env::consts::DLL_EXTENSION))
+        .collect();
+    paths.sort();
+    paths.iter().map(|path| Plugin::load(path)).collect()
+}
@@ -0,0 +1,45 @@
+use std::fmt;
+
+use crate::line::Line;
+
+use crate::app::hyperlink;
+
+/// A place in a file an editor can jump to, with the line and column counting from 1.
+pub struct Location {
+    pub path: String,
+    pub line: u64,
+    pub column: usize,
+}
+
+impl Location {
+    /// Where a line points to. A line read from a file is located in that file, at `column` if
+    /// given. Any other line is located by the first `file:line` reference in it, such as in
+    /// compiler output, if it has one.
+    pub fn of(line: &Line, file: Option<&str>, column: Option<usize>) -> Option<Self> {
+        if let Some(path) = file {
+            return Some(Location {
+                path: path.to_string(),
+                line: line.number,
+                column: column.unwrap_or(1),
+            });
+        }
+        let (path, number, referenced) = hyperlink::file_reference(&line.text)?;
+        Some(Location {
+            path: path.to_string(),
+            line: number,
+            column: referenced.unwrap_or(1),
+        })
+    }
+
+    /// The location followed by the text found there, as one entry of a list in the quickfix
+    /// format understood by `:cfile` in Vim and problem matchers in VS Code.
+    pub fn entry(&self, text: &str) -> String {
+        format!("{}: {}", self, text)
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.path, self.line, self.column)
+    }
+}
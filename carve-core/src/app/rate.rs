@@ -0,0 +1,54 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How often a new point is added to the rate history.
+const BUCKET_DURATION: Duration = Duration::from_secs(1);
+/// How many seconds of history are kept for the chart.
+const MAX_HISTORY: usize = 60;
+
+/// The rate lines are being read at, per second, so it's obvious when the upstream process
+/// stalls or starts flooding.
+pub struct Rate {
+    bucket: u64, // Lines read so far in the current second
+    bucket_start: Instant,
+    history: VecDeque<u64>, // Lines read in each of the last few complete seconds, oldest first
+}
+
+impl Default for Rate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rate {
+    pub fn new() -> Self {
+        Self {
+            bucket: 0,
+            bucket_start: Instant::now(),
+            history: VecDeque::new(),
+        }
+    }
+
+    /// Count lines that have just been read and roll the history forward.
+    pub fn record(&mut self, lines: usize) {
+        while self.bucket_start.elapsed() >= BUCKET_DURATION {
+            self.history.push_back(std::mem::take(&mut self.bucket));
+            if self.history.len() > MAX_HISTORY {
+                self.history.pop_front();
+            }
+            self.bucket_start += BUCKET_DURATION;
+        }
+        self.bucket += lines as u64;
+    }
+
+    /// Lines read in the last complete second.
+    pub fn per_second(&self) -> u64 {
+        self.history.back().copied().unwrap_or_default()
+    }
+
+    /// Lines read in each of the last `width` complete seconds, most recent last.
+    pub fn history(&self, width: usize) -> Vec<u64> {
+        let skip = self.history.len().saturating_sub(width);
+        self.history.iter().skip(skip).copied().collect()
+    }
+}
@@ -0,0 +1,51 @@
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{DateTime, FixedOffset};
+use crate::decode::Decoder;
+use crate::line::Line;
+
+use crate::app::source::Sink;
+
+/// Write lines to `path` as a recording to replay: each line's arrival time in RFC 3339 format,
+/// a tab, then the line, as printed on exit with `--annotate time`.
+pub fn record<'a>(path: &Path, lines: impl IntoIterator<Item = &'a Line>) -> std::io::Result<usize> {
+    let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let mut count = 0;
+    for line in lines {
+        let arrived = DateTime::<chrono::Local>::from(line.arrived).to_rfc3339_opts(chrono::SecondsFormat::Millis, false);
+        writeln!(out, "{}\t{}", arrived, line.text)?;
+        count += 1;
+    }
+    out.flush()?;
+    Ok(count)
+}
+
+/// Spawn an async task that plays back the contents of a recording, adding each line after the time that
+/// passed between it and the line before it when it was recorded, divided by `speed`. Lines
+/// without a time are added straight away.
+pub fn spawn(contents: Vec<u8>, speed: f64, sink: Sink, source: usize) {
+    tokio::spawn(async move {
+        let mut decoder = Decoder::default();
+        let mut lines = decoder.feed_raw(&contents);
+        lines.extend(decoder.finish_raw());
+        let mut last: Option<DateTime<FixedOffset>> = None;
+        for mut line in lines {
+            if let Some((time, text)) = line.text.split_once('\t') {
+                if let Ok(time) = DateTime::parse_from_rfc3339(time) {
+                    if let Some(gap) = last.and_then(|last| (time - last).to_std().ok()) {
+                        tokio::time::sleep(Duration::from_secs_f64(gap.as_secs_f64() / speed)).await;
+                    }
+                    last = Some(time);
+                    // The time is ASCII, so it's as long in the bytes read as in the text
+                    let start = line.text.len() - text.len();
+                    line.raw = line.raw.map(|raw| raw[start..].to_vec());
+                    line.text = text.to_string();
+                }
+            }
+            sink.push_decoded(line, source);
+        }
+        sink.push("──── replay finished ────".to_string(), source);
+    });
+}
@@ -0,0 +1,216 @@
+use std::path::{Path, PathBuf};
+
+use rhai::{Array, CallFnOptions, Dynamic, Engine, Map, Scope, AST};
+
+/// How many operations a single call into a script may take before it's stopped, so a script
+/// that loops forever can't hang carve.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// What a script's `filter` did with a line.
+pub enum Verdict {
+    Keep,
+    Replace(String),
+    Drop,
+}
+
+/// A style a script's `colorize` gave part of a line, as a byte range of the line, with colours
+/// named as in highlight rules.
+pub struct Paint {
+    pub range: (usize, usize),
+    pub fg: Option<String>,
+    pub bg: Option<String>,
+    pub bold: bool,
+    pub underline: bool,
+}
+
+/// A script loaded from the scripts directory, and which of the special functions it defines.
+struct Script {
+    name: String,
+    ast: AST,
+    filter: bool,
+    colorize: bool,
+}
+
+/// The Rhai scripts loaded from the scripts directory, and the engine that runs them. Any
+/// function taking one argument can be run with `:call`, and two names are special:
+///
+/// ```rhai
+/// // Called with each line as it's read: `false` drops it, a string replaces it and anything
+/// // else keeps it
+/// fn filter(line) {
+///     if line.contains("healthcheck") { return false; }
+///     line.replace("password=hunter2", "password=***");
+///     line
+/// }
+///
+/// // Called with each line as it's drawn: a map styles the whole line, and a map with `start`
+/// // and `end` character offsets, or an array of them, styles parts of it
+/// fn colorize(line) {
+///     let at = line.index_of("WARN");
+///     if at >= 0 { #{ start: at, end: at + 4, fg: "yellow", bold: true } }
+/// }
+/// ```
+pub struct Scripts {
+    engine: Engine,
+    scripts: Vec<Script>,
+}
+
+impl Default for Scripts {
+    fn default() -> Self {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        // The terminal belongs to carve, so anything a script prints goes to the log
+        engine.on_print(|text| tracing::info!(text, "script"));
+        engine.on_debug(|text, _, position| tracing::debug!(text, %position, "script"));
+        Self {
+            engine,
+            scripts: Vec::new(),
+        }
+    }
+}
+
+impl Scripts {
+    /// Load every script in `dir`, in name order so the order their filters run in is
+    /// predictable. Having no scripts directory is the same as having an empty one.
+    pub fn load_all(dir: &Path) -> Result<Self, String> {
+        let mut scripts = Self::default();
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Ok(scripts);
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "rhai"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            let source = std::fs::read_to_string(&path).map_err(|e| format!("failed to read script {}: {}", path.display(), e))?;
+            scripts.add(&path.file_stem().unwrap_or_default().to_string_lossy(), &source)
+                .map_err(|e| format!("script {}: {}", path.display(), e))?;
+        }
+        Ok(scripts)
+    }
+
+    /// Compile a script and add it after those already loaded.
+    pub fn add(&mut self, name: &str, source: &str) -> Result<(), String> {
+        let ast = self.engine.compile(source).map_err(|e| e.to_string())?;
+        let defines = |name: &str| ast.iter_functions().any(|f| f.name == name && f.params.len() == 1);
+        let (filter, colorize) = (defines("filter"), defines("colorize"));
+        self.scripts.push(Script {
+            name: name.to_string(),
+            ast,
+            filter,
+            colorize,
+        });
+        Ok(())
+    }
+
+    /// Run a line through each script's `filter`, in order, stopping if one drops it. A script
+    /// that fails leaves the line as it was.
+    pub fn filter(&self, text: &str) -> Verdict {
+        let mut replaced: Option<String> = None;
+        for script in self.scripts.iter().filter(|script| script.filter) {
+            let line = replaced.as_deref().unwrap_or(text).to_string();
+            match self.call(script, "filter", (line,)) {
+                Ok(result) if result.as_bool() == Ok(false) => return Verdict::Drop,
+                Ok(result) if result.is_string() => replaced = Some(result.into_string().unwrap_or_default()),
+                Ok(_) => {}
+                Err(e) => tracing::warn!(script = script.name, error = e, "filter failed"),
+            }
+        }
+        match replaced {
+            Some(text) => Verdict::Replace(text),
+            None => Verdict::Keep,
+        }
+    }
+
+    /// The styles each script's `colorize` gives a line, earlier scripts first. A script that
+    /// fails, or returns something that isn't a style, adds nothing.
+    pub fn colorize(&self, text: &str) -> Vec<Paint> {
+        let mut paints = Vec::new();
+        for script in self.scripts.iter().filter(|script| script.colorize) {
+            let result = self.call(script, "colorize", (text.to_string(),));
+            match result.and_then(|result| paints_from(result, text)) {
+                Ok(found) => paints.extend(found),
+                Err(e) => tracing::warn!(script = script.name, error = e, "colorize failed"),
+            }
+        }
+        paints
+    }
+
+    /// Call a function taking the session's state, as for `:call`, returning the command lines
+    /// it asked for: none for `()`, one for a string, or one for each string in an array.
+    pub fn action(&self, name: &str, state: Map) -> Result<Vec<String>, String> {
+        let script = self
+            .scripts
+            .iter()
+            .rev()
+            .find(|script| script.ast.iter_functions().any(|f| f.name == name && f.params.len() == 1))
+            .ok_or_else(|| format!("no script defines {}(state)", name))?;
+        let result = self.call(script, name, (state,)).map_err(|e| format!("{}: {}", name, e))?;
+        if result.is_unit() {
+            return Ok(Vec::new());
+        }
+        if result.is_string() {
+            return Ok(vec![result.into_string().unwrap_or_default()]);
+        }
+        let invalid = |got: &str| format!("{}: expected commands to run, got {}", name, got);
+        let commands = result.try_cast_result::<Array>().map_err(|result| invalid(result.type_name()))?;
+        commands
+            .into_iter()
+            .map(|command| command.into_string().map_err(invalid))
+            .collect()
+    }
+
+    fn call(&self, script: &Script, name: &str, args: impl rhai::FuncArgs) -> Result<Dynamic, String> {
+        // Just the function, not the statements at the top level of the script around it
+        let options = CallFnOptions::new().eval_ast(false);
+        self.engine
+            .call_fn_with_options(options, &mut Scope::new(), &script.ast, name, args)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Read the styles `colorize` returned for a line.
+fn paints_from(result: Dynamic, text: &str) -> Result<Vec<Paint>, String> {
+    if result.is_unit() {
+        return Ok(Vec::new());
+    }
+    if result.is_map() {
+        return paint_from(result.cast::<Map>(), text).map(|paint| vec![paint]);
+    }
+    let styles = result
+        .try_cast_result::<Array>()
+        .map_err(|result| format!("expected a map or an array of maps, got {}", result.type_name()))?;
+    styles
+        .into_iter()
+        .map(|style| {
+            let style = style.try_cast_result::<Map>().map_err(|style| format!("expected a map, got {}", style.type_name()))?;
+            paint_from(style, text)
+        })
+        .collect()
+}
+
+fn paint_from(style: Map, text: &str) -> Result<Paint, String> {
+    let offset = |key: &str| -> Result<Option<usize>, String> {
+        match style.get(key) {
+            None => Ok(None),
+            Some(value) => value
+                .as_int()
+                .map(|at| Some(at.max(0) as usize))
+                .map_err(|got| format!("expected {} to be a number, got {}", key, got)),
+        }
+    };
+    let color = |key: &str| style.get(key).filter(|value| !value.is_unit()).map(|value| value.to_string());
+    let flag = |key: &str| style.get(key).is_some_and(|value| value.as_bool() == Ok(true));
+    // Scripts count characters, as Rhai's string functions do, rather than bytes
+    let byte = |at: usize| text.char_indices().nth(at).map_or(text.len(), |(byte, _)| byte);
+    let start = byte(offset("start")?.unwrap_or(0));
+    let end = offset("end")?.map_or(text.len(), byte).max(start);
+    Ok(Paint {
+        range: (start, end),
+        fg: color("fg"),
+        bg: color("bg"),
+        bold: flag("bold"),
+        underline: flag("underline"),
+    })
+}
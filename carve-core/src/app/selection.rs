@@ -0,0 +1,131 @@
+use unicode_width::UnicodeWidthChar;
+
+/// A rectangle of text picked out with `v`, from the row and screen column it was anchored at to
+/// the cursor row and current column. Columns count display width rather than characters, so
+/// the rectangle stays square over wide characters such as CJK text.
+///
+/// Until it's anchored, the selection is a single character which moves with the cursor, for
+/// picking the corner to start from.
+pub struct Block {
+    pub anchor: usize, // Row of the view the selection started on
+    pub anchor_column: usize,
+    pub column: usize,
+    pub anchored: bool,
+}
+
+impl Block {
+    pub fn new(anchor: usize) -> Self {
+        Self {
+            anchor,
+            anchor_column: 0,
+            column: 0,
+            anchored: false,
+        }
+    }
+
+    /// Move the column end of the selection, dragging the anchor along if it isn't anchored yet.
+    pub fn move_to(&mut self, column: usize) {
+        self.column = column;
+        if !self.anchored {
+            self.anchor_column = column;
+        }
+    }
+
+    /// The first and last rows of the view selected, given the row the cursor is on.
+    pub fn rows(&self, cursor: usize) -> (usize, usize) {
+        if !self.anchored {
+            return (cursor, cursor);
+        }
+        (self.anchor.min(cursor), self.anchor.max(cursor))
+    }
+
+    /// The first and last display columns selected.
+    pub fn columns(&self) -> (usize, usize) {
+        (self.anchor_column.min(self.column), self.anchor_column.max(self.column))
+    }
+
+    /// The byte range of the selected columns of a row as displayed, for highlighting them.
+    pub fn range(&self, text: &str) -> (usize, usize) {
+        let (left, right) = self.columns();
+        byte_range(text, left, right)
+    }
+
+    /// The selected columns of a row as displayed, with any wide character cut in half by the
+    /// edge of the selection replaced by spaces so the rows of a yanked block line up.
+    pub fn cut(&self, text: &str) -> String {
+        let (left, right) = self.columns();
+        let mut out = String::new();
+        let mut column = 0;
+        for c in text.chars() {
+            let width = c.width().unwrap_or(0);
+            let (start, end) = (column, column + width);
+            column = end;
+            if end <= left || start > right {
+                continue;
+            }
+            if start < left || end > right + 1 {
+                // Only the part of the character inside the selection is kept, as blank space
+                let inside = end.min(right + 1) - start.max(left);
+                out.extend(std::iter::repeat_n(' ', inside));
+            } else {
+                out.push(c);
+            }
+        }
+        out.trim_end().to_string()
+    }
+}
+
+/// The byte range of the characters of `text` which are at least partly within display columns
+/// `left` to `right`, inclusive.
+fn byte_range(text: &str, left: usize, right: usize) -> (usize, usize) {
+    let mut column = 0;
+    let (mut start, mut end) = (text.len(), text.len());
+    for (idx, c) in text.char_indices() {
+        if column > right {
+            end = idx;
+            break;
+        }
+        let next = column + c.width().unwrap_or(0);
+        if next > left && start == text.len() {
+            start = idx;
+        }
+        column = next;
+    }
+    (start, end.max(start))
+}
+
+/// The display column of each word start in `text`, for moving the selection a word at a time.
+pub fn word_starts(text: &str) -> Vec<usize> {
+    let mut starts = Vec::new();
+    let mut column = 0;
+    let mut previous_blank = true;
+    for c in text.chars() {
+        let blank = c.is_whitespace();
+        if previous_blank && !blank {
+            starts.push(column);
+        }
+        previous_blank = blank;
+        column += c.width().unwrap_or(0);
+    }
+    starts
+}
+
+/// The display column of the last character of each word in `text`.
+pub fn word_ends(text: &str) -> Vec<usize> {
+    let mut ends = Vec::new();
+    let mut column = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let width = c.width().unwrap_or(0);
+        if !c.is_whitespace() && chars.peek().is_none_or(|next| next.is_whitespace()) {
+            ends.push(column + width.saturating_sub(1));
+        }
+        column += width;
+    }
+    ends
+}
+
+/// The display width of `text`.
+pub fn width(text: &str) -> usize {
+    text.chars().map(|c| c.width().unwrap_or(0)).sum()
+}
@@ -0,0 +1,366 @@
+use std::fs::File;
+use std::future::Future;
+use std::io::SeekFrom;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot};
+use crate::decode::{Decoded, Decoder};
+use crate::line::Line;
+
+use crate::app::export::Exporter;
+
+/// How often a followed file is checked for new data once we've caught up with it.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Where reader tasks deliver the lines they read: the channel the UI drains into its buffer
+/// between frames and, when enabled, the continuous export.
+#[derive(Clone)]
+pub struct Sink {
+    pub tx: mpsc::UnboundedSender<Line>,
+    pub exporter: Option<Arc<Mutex<Exporter>>>,
+    pub next_source: Arc<AtomicUsize>, // The index the next source added will have
+    pub new_sources: mpsc::UnboundedSender<(usize, String)>, // Sources added by reader tasks
+}
+
+impl Sink {
+    /// Add a source from a reader task, such as for a pod that has just started, returning its
+    /// index. Its name reaches the UI before any of its lines.
+    pub fn add_source(&self, name: String) -> usize {
+        let source = self.next_source.fetch_add(1, Ordering::Relaxed);
+        let _ = self.new_sources.send((source, name));
+        source
+    }
+
+    pub fn push(&self, text: String, source: usize) {
+        self.push_decoded(Decoded { text, raw: None }, source);
+    }
+
+    /// Push a line as decoded, keeping the bytes it was read from if they weren't valid UTF-8.
+    pub fn push_decoded(&self, decoded: Decoded, source: usize) {
+        self.push_all([decoded], source);
+    }
+
+    /// Push the lines read in one go, such as from a chunk of input, flushing the export once
+    /// they're all written rather than after each one.
+    pub fn push_all(&self, lines: impl IntoIterator<Item = Decoded>, source: usize) {
+        let mut exporter = self.exporter.as_ref().and_then(|exporter| exporter.lock().ok());
+        for decoded in lines {
+            if let Some(exporter) = &mut exporter {
+                exporter.write_line(&decoded.text);
+            }
+            let mut line = Line::new(decoded.text, source);
+            line.raw = decoded.raw.map(Vec::into_boxed_slice);
+            // The receiver only goes away as carve exits, at which point the line isn't needed.
+            let _ = self.tx.send(line);
+        }
+        if let Some(exporter) = &mut exporter {
+            exporter.flush();
+        }
+    }
+}
+
+/// How much is read from an input at a time.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The task reading stdin.
+pub struct Stdin {
+    pub task: tokio::task::JoinHandle<()>,
+    stop: oneshot::Sender<()>,
+}
+
+impl Stdin {
+    /// Stop reading stdin, waiting until what's been read so far has been delivered and copied
+    /// to stdout.
+    pub async fn stop(self) {
+        let _ = self.stop.send(());
+        let _ = self.task.await;
+    }
+}
+
+/// Spawn an async task to read from stdin continuously, which finishes once stdin is closed or
+/// it's stopped. With `tee`, what's read is also copied to stdout byte for byte as it arrives.
+pub fn spawn_stdin(sink: Sink, source: usize, tee: bool) -> Stdin {
+    let (stop, stopped) = oneshot::channel();
+    let task = tokio::spawn(async move {
+        let tee = tee.then(tokio::io::stdout);
+        match read_to_end(tokio::io::stdin(), &sink, source, tee, stopped).await {
+            Ok(()) => tracing::debug!("stdin closed"),
+            Err(e) => tracing::warn!("failed to read stdin: {}", e),
+        }
+    });
+    Stdin { task, stop }
+}
+
+/// Read lines from `reader` until it ends or `stop` resolves, decoding them whatever their
+/// encoding, and copying them unchanged to `tee` if given. If whatever reads from `tee` goes
+/// away, reading carries on without it.
+pub async fn read_to_end(
+    mut reader: impl AsyncRead + Unpin,
+    sink: &Sink,
+    source: usize,
+    mut tee: Option<tokio::io::Stdout>,
+    mut stop: impl Future + Unpin,
+) -> std::io::Result<()> {
+    let mut decoder = Decoder::default();
+    let mut chunk = vec![0; CHUNK_SIZE];
+    loop {
+        let n = tokio::select! {
+            n = reader.read(&mut chunk) => n?,
+            _ = &mut stop => return Ok(()),
+        };
+        if let Some(out) = &mut tee {
+            if let Err(e) = out.write_all(&chunk[..n]).await.and(out.flush().await) {
+                tracing::warn!("stopped copying input to stdout: {}", e);
+                tee = None;
+            }
+        }
+        if n == 0 {
+            if let Some(line) = decoder.finish_raw() {
+                sink.push_decoded(line, source);
+            }
+            return Ok(());
+        }
+        sink.push_all(decoder.feed_raw(&chunk[..n]), source);
+    }
+}
+
+/// Collect lines sent by other local processes at `path`, so several can feed one session.
+///
+/// An existing FIFO is read from each writer in turn, being opened again whenever one closes it.
+/// Otherwise a Unix socket is created at `path`, replacing a stale one left by an earlier
+/// session, and each client's lines are read as they arrive, e.g. from `nc -U PATH`.
+#[cfg(unix)]
+pub fn listen(path: &Path, sink: Sink, source: usize) -> std::io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    let file_type = std::fs::symlink_metadata(path).map(|metadata| metadata.file_type());
+    if file_type.as_ref().is_ok_and(FileTypeExt::is_fifo) {
+        let path = path.to_path_buf();
+        tokio::spawn(async move {
+            loop {
+                // Opening waits for a writer
+                let file = match tokio::fs::File::open(&path).await {
+                    Ok(file) => file,
+                    Err(e) => break tracing::warn!(source, "failed to open FIFO: {}", e),
+                };
+                if let Err(e) = read_to_end(file, &sink, source, None, std::future::pending::<()>()).await {
+                    tracing::warn!(source, "failed to read FIFO: {}", e);
+                }
+            }
+        });
+        return Ok(());
+    }
+    if file_type.is_ok_and(|file_type| file_type.is_socket()) {
+        std::fs::remove_file(path)?;
+    }
+    let listener = tokio::net::UnixListener::bind(path)?;
+    tokio::spawn(async move {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => break tracing::warn!(source, "listening failed: {}", e),
+            };
+            let sink = sink.clone();
+            tokio::spawn(async move {
+                if let Err(e) = read_to_end(stream, &sink, source, None, std::future::pending::<()>()).await {
+                    tracing::warn!(source, "failed to read from client: {}", e);
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Remove the socket made by `listen`, leaving a FIFO, which was there before, alone.
+#[cfg(unix)]
+pub fn stop_listening(path: &Path) {
+    use std::os::unix::fs::FileTypeExt;
+
+    if std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn stop_listening(_path: &Path) {}
+
+#[cfg(not(unix))]
+pub fn listen(_path: &Path, _sink: Sink, _source: usize) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "listening needs a Unix-like system"))
+}
+
+/// Spawn a shell command and read what it prints, stdout and stderr together, under a divider
+/// line naming the command. A failure is noted in a final line.
+pub fn spawn_command(command: String, sink: Sink, source: usize) {
+    sink.push(format!("──── !{} ────", command), source);
+    tokio::spawn(async move {
+        let child = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("exec 2>&1; {}", command))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => return sink.push(format!("──── failed to run: {} ────", e), source),
+        };
+        // Read to the end whatever the output's encoding, so the command is never left blocked
+        // on a full pipe
+        let stdout = child.stdout.take().expect("stdout is piped");
+        if let Err(e) = read_to_end(stdout, &sink, source, None, std::future::pending::<()>()).await {
+            sink.push(format!("──── failed to read output: {} ────", e), source);
+        }
+        match child.wait().await {
+            Ok(status) if status.success() => {}
+            Ok(status) => sink.push(format!("──── {} ────", status), source),
+            Err(e) => sink.push(format!("──── failed to run: {} ────", e), source),
+        }
+    });
+}
+
+/// Spawn an async task that reads a file from the start and then keeps following it for new
+/// lines, like `tail -f`. If the file is truncated it is read again from the beginning.
+///
+/// Pipes, such as a FIFO or a Windows named pipe (`\\.\pipe\name`), are read until the writer
+/// closes them, as there's nothing to follow after that. So are compressed files, such as
+/// rotated logs, which are decompressed as they're read.
+pub fn spawn_file(mut file: File, sink: Sink, source: usize) {
+    if let Some(compression) = Compression::detect(&mut file) {
+        return spawn_decompressed(file, compression, sink, source);
+    }
+    tokio::spawn(async move {
+        let file = tokio::fs::File::from_std(file);
+        if file.metadata().await.is_ok_and(|metadata| !metadata.is_file()) {
+            if let Err(e) = read_to_end(file, &sink, source, None, std::future::pending::<()>()).await {
+                tracing::warn!(source, "failed to read pipe: {}", e);
+            }
+            return;
+        }
+        let mut reader = BufReader::new(file);
+        let mut decoder = Decoder::default();
+        let mut chunk = vec![0; CHUNK_SIZE];
+        let mut position = 0;
+
+        loop {
+            match reader.read(&mut chunk).await {
+                Ok(0) | Err(_) => {
+                    tokio::time::sleep(FOLLOW_INTERVAL).await;
+                    let len = match reader.get_ref().metadata().await {
+                        Ok(metadata) => metadata.len(),
+                        Err(_) => return,
+                    };
+                    if len < position {
+                        tracing::debug!(source, "file truncated, reading from the start");
+                        decoder = Decoder::default();
+                        position = 0;
+                        if reader.seek(SeekFrom::Start(0)).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                Ok(n) => {
+                    position += n as u64;
+                    // The decoder holds on to partial lines until the rest has been written
+                    sink.push_all(decoder.feed_raw(&chunk[..n]), source);
+                }
+            }
+        }
+    });
+}
+
+/// How a file is compressed.
+#[derive(Copy, Clone)]
+enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+impl Compression {
+    /// Tell how a file is compressed, if it is, from its first few bytes, leaving it to be read
+    /// from the start. Only regular files are checked, since what's read from a pipe can't be
+    /// put back.
+    fn detect(file: &mut File) -> Option<Self> {
+        use std::io::{Read, Seek};
+
+        if !file.metadata().is_ok_and(|metadata| metadata.is_file()) {
+            return None;
+        }
+        let mut magic = Vec::with_capacity(4);
+        file.by_ref().take(4).read_to_end(&mut magic).ok()?;
+        file.rewind().ok()?;
+        match magic[..] {
+            [0x1f, 0x8b, ..] => Some(Compression::Gzip),
+            [0x28, 0xb5, 0x2f, 0xfd] => Some(Compression::Zstd),
+            [b'B', b'Z', b'h', _] => Some(Compression::Bzip2),
+            _ => None,
+        }
+    }
+}
+
+/// Spawn a task that decompresses a file and reads the lines in it. A gzipped file is
+/// decompressed directly, while `zstd` and `bzip2` are run for the others, so need to be
+/// installed. A failure is noted in a final line.
+fn spawn_decompressed(file: File, compression: Compression, sink: Sink, source: usize) {
+    let program = match compression {
+        Compression::Gzip => {
+            tokio::task::spawn_blocking(move || {
+                use std::io::Read;
+
+                let mut reader = flate2::read::MultiGzDecoder::new(std::io::BufReader::new(file));
+                let mut decoder = Decoder::default();
+                let mut chunk = vec![0; CHUNK_SIZE];
+                loop {
+                    match reader.read(&mut chunk) {
+                        Ok(0) => break,
+                        Ok(n) => sink.push_all(decoder.feed_raw(&chunk[..n]), source),
+                        Err(e) => {
+                            decoder.finish_raw().into_iter().for_each(|line| sink.push_decoded(line, source));
+                            return sink.push(format!("──── failed to decompress: {} ────", e), source);
+                        }
+                    }
+                }
+                decoder.finish_raw().into_iter().for_each(|line| sink.push_decoded(line, source));
+            });
+            return;
+        }
+        Compression::Zstd => "zstd",
+        Compression::Bzip2 => "bzip2",
+    };
+    tokio::spawn(async move {
+        let child = tokio::process::Command::new(program)
+            .arg("--decompress")
+            .arg("--stdout")
+            .stdin(file)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => return sink.push(format!("──── failed to run {}: {} ────", program, e), source),
+        };
+        let stdout = child.stdout.take().expect("stdout is piped");
+        if let Err(e) = read_to_end(stdout, &sink, source, None, std::future::pending::<()>()).await {
+            tracing::warn!(source, "failed to read from {}: {}", program, e);
+        }
+        let mut errors = String::new();
+        if let Some(mut stderr) = child.stderr.take() {
+            let _ = stderr.read_to_string(&mut errors).await;
+        }
+        match child.wait().await {
+            Ok(status) if status.success() => {}
+            Ok(status) => {
+                let error = errors.lines().find(|line| !line.trim().is_empty()).unwrap_or_default().trim();
+                sink.push(format!("──── failed to decompress: {} {} ────", program, error), source);
+                tracing::debug!(source, "{} {}", program, status);
+            }
+            Err(e) => sink.push(format!("──── failed to run {}: {} ────", program, e), source),
+        }
+    });
+}
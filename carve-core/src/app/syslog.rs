@@ -0,0 +1,360 @@
+use std::io;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, BufReader};
+
+use crate::app::journal::{json_string, level};
+use crate::app::source::Sink;
+
+/// The largest message accepted over TCP, so a broken sender can't exhaust memory.
+const MAX_MESSAGE: usize = 64 * 1024;
+
+/// The names of the facilities, by number.
+const FACILITIES: [&str; 24] = [
+    "kern", "user", "mail", "daemon", "auth", "syslog", "lpr", "news", "uucp", "cron", "authpriv", "ftp", "ntp",
+    "security", "console", "solaris-cron", "local0", "local1", "local2", "local3", "local4", "local5", "local6",
+    "local7",
+];
+
+/// Receive syslog messages at `address`, written as `udp:PORT` or `tcp:PORT`, or with a host to
+/// bind to, as in `udp:127.0.0.1:5514`. A bare port listens on all interfaces.
+///
+/// Each message, in either the BSD (RFC 3164) or the newer (RFC 5424) format, becomes a line
+/// like `2024-01-01 12:00:00.000 ERROR host app[42]: message`, so its severity is coloured as a
+/// level, followed by its facility and any structured data as a JSON object. Over TCP, messages
+/// may be separated by newlines or prefixed with their length (RFC 6587).
+pub fn listen(address: &str, sink: Sink, source: usize) -> io::Result<()> {
+    let (protocol, address) = address.split_once(':').unwrap_or((address, ""));
+    let address = match address.parse::<u16>() {
+        Ok(port) => format!("0.0.0.0:{}", port),
+        Err(_) => address.to_string(),
+    };
+    match protocol {
+        "udp" => {
+            let socket = std::net::UdpSocket::bind(&address)?;
+            socket.set_nonblocking(true)?;
+            let socket = tokio::net::UdpSocket::from_std(socket)?;
+            tokio::spawn(async move {
+                let mut datagram = vec![0; MAX_MESSAGE];
+                loop {
+                    match socket.recv(&mut datagram).await {
+                        Ok(n) => sink.push(format_message(&datagram[..n]), source),
+                        Err(e) => break tracing::warn!(source, "receiving syslog failed: {}", e),
+                    }
+                }
+            });
+        }
+        "tcp" => {
+            let listener = std::net::TcpListener::bind(&address)?;
+            listener.set_nonblocking(true)?;
+            let listener = tokio::net::TcpListener::from_std(listener)?;
+            tokio::spawn(async move {
+                loop {
+                    let stream = match listener.accept().await {
+                        Ok((stream, _)) => stream,
+                        Err(e) => break tracing::warn!(source, "listening for syslog failed: {}", e),
+                    };
+                    let sink = sink.clone();
+                    tokio::spawn(async move {
+                        let mut reader = BufReader::new(stream);
+                        loop {
+                            match read_frame(&mut reader).await {
+                                Ok(Some(message)) => sink.push(format_message(&message), source),
+                                Ok(None) => break,
+                                Err(e) => break tracing::warn!(source, "failed to read syslog: {}", e),
+                            }
+                        }
+                    });
+                }
+            });
+        }
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("expected udp:PORT or tcp:PORT, not {}", protocol),
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// Read the next message sent over TCP: the number of bytes it has, a space and the message,
+/// or else the message up to a newline.
+async fn read_frame(reader: &mut (impl AsyncBufRead + Unpin)) -> io::Result<Option<Vec<u8>>> {
+    let starts_with_length = match reader.fill_buf().await?.first() {
+        None => return Ok(None),
+        Some(b) => b.is_ascii_digit(),
+    };
+    let mut message = Vec::new();
+    if starts_with_length {
+        let mut length = Vec::new();
+        reader.read_until(b' ', &mut length).await?;
+        let length = std::str::from_utf8(&length)
+            .ok()
+            .and_then(|length| length.trim().parse::<usize>().ok())
+            .filter(|&length| length <= MAX_MESSAGE)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "bad message length"))?;
+        message.resize(length, 0);
+        reader.read_exact(&mut message).await?;
+    } else {
+        reader.take(MAX_MESSAGE as u64).read_until(b'\n', &mut message).await?;
+    }
+    Ok(Some(message))
+}
+
+/// The line shown for a message. A message that can't be parsed is shown as it is.
+fn format_message(message: &[u8]) -> String {
+    let message = String::from_utf8_lossy(message);
+    let message = message.trim_end_matches(['\r', '\n', '\0']);
+    let Some((priority, rest)) = message
+        .strip_prefix('<')
+        .and_then(|rest| rest.split_once('>'))
+        .and_then(|(priority, rest)| Some((priority.parse::<u8>().ok()?, rest)))
+    else {
+        return message.replace('\n', " ");
+    };
+    let mut fields = vec![("facility".to_string(), json_string(FACILITIES.get(priority as usize / 8).unwrap_or(&"unknown")))];
+    let header = match rest.strip_prefix("1 ") {
+        Some(rest) => rfc5424(rest, &mut fields),
+        None => rfc3164(rest),
+    };
+
+    let mut line = String::new();
+    if let Some(time) = &header.time {
+        line.push_str(time);
+        line.push(' ');
+    }
+    line.push_str(level(Some(&(priority % 8).to_string())));
+    if let Some(host) = header.host {
+        line.push(' ');
+        line.push_str(host);
+    }
+    match (header.app, header.pid) {
+        (Some(app), Some(pid)) => line.push_str(&format!(" {}[{}]:", app, pid)),
+        (Some(app), None) => line.push_str(&format!(" {}:", app)),
+        (None, _) => line.push(':'),
+    }
+    line.push(' ');
+    // A message over several lines is shown on one, as for the journal
+    line.push_str(&header.message.replace('\n', " "));
+    let fields: Vec<String> = fields.iter().map(|(name, value)| format!("{}:{}", json_string(name), value)).collect();
+    line.push_str(&format!(" {{{}}}", fields.join(",")));
+    line
+}
+
+/// The parts of a message's header that are shown in its line, and the message itself.
+struct Header<'a> {
+    time: Option<String>,
+    host: Option<&'a str>,
+    app: Option<&'a str>,
+    pid: Option<&'a str>,
+    message: &'a str,
+}
+
+/// Parse the rest of an RFC 5424 message after its version, as in `2024-01-01T12:00:00Z host
+/// app 42 ID47 [origin ip="10.0.0.1"] message`, where `-` stands for a missing field. Its
+/// message ID and structured data are added to `fields`, with their values as JSON.
+fn rfc5424<'a>(rest: &'a str, fields: &mut Vec<(String, String)>) -> Header<'a> {
+    let mut parts = rest.splitn(6, ' ');
+    let mut next = || parts.next().filter(|part| *part != "-");
+    let time = next().map(|time| match chrono::DateTime::parse_from_rfc3339(time) {
+        Ok(time) => time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+        Err(_) => time.to_string(),
+    });
+    let (host, app, pid) = (next(), next(), next());
+    if let Some(id) = next() {
+        fields.push(("msgid".to_string(), json_string(id)));
+    }
+    let rest = parts.next().unwrap_or_default();
+    let (elements, message) = structured_data(rest);
+    fields.extend(elements);
+    Header {
+        time,
+        host,
+        app,
+        pid,
+        message: message.trim_start_matches('\u{feff}'),
+    }
+}
+
+/// Split RFC 5424 structured data, such as `[origin ip="10.0.0.1"][meta seq="4"]`, from the
+/// message after it, returning each element's ID with its parameters as a JSON object.
+fn structured_data(rest: &str) -> (Vec<(String, String)>, &str) {
+    if let Some(message) = rest.strip_prefix('-') {
+        return (Vec::new(), message.strip_prefix(' ').unwrap_or(message));
+    }
+    let mut elements = Vec::new();
+    let mut chars = rest.char_indices().peekable();
+    while let Some((_, '[')) = chars.peek() {
+        chars.next();
+        let mut id = String::new();
+        let mut params = Vec::new();
+        // The element's ID, then its parameters, each `name="value"` with `"`, `\` and `]`
+        // escaped by a backslash
+        while let Some((_, c)) = chars.next_if(|&(_, c)| c != ' ' && c != ']') {
+            id.push(c);
+        }
+        loop {
+            match chars.next() {
+                Some((_, ' ')) => {
+                    let mut name = String::new();
+                    while let Some((_, c)) = chars.next_if(|&(_, c)| c != '=' && c != ']') {
+                        name.push(c);
+                    }
+                    chars.next_if(|&(_, c)| c == '=');
+                    chars.next_if(|&(_, c)| c == '"');
+                    let mut value = String::new();
+                    while let Some((_, c)) = chars.next() {
+                        match c {
+                            '\\' => value.extend(chars.next().map(|(_, c)| c)),
+                            '"' => break,
+                            c => value.push(c),
+                        }
+                    }
+                    params.push(format!("{}:{}", json_string(&name), json_string(&value)));
+                }
+                Some((_, ']')) | None => break,
+                Some(_) => {}
+            }
+        }
+        elements.push((id, format!("{{{}}}", params.join(","))));
+    }
+    let message = match chars.next() {
+        Some((i, ' ')) => &rest[i + 1..],
+        Some((i, _)) => &rest[i..],
+        None => "",
+    };
+    (elements, message)
+}
+
+/// Parse the rest of a BSD syslog message after its priority, as in `Oct 11 22:14:15 host
+/// app[42]: message`. Its timestamp is shown as sent, as it has no year or time zone. Senders
+/// often leave out the host, or everything but the message, so the header is only taken apart
+/// as far as it can be.
+fn rfc3164(rest: &str) -> Header<'_> {
+    let mut header = Header {
+        time: None,
+        host: None,
+        app: None,
+        pid: None,
+        message: rest,
+    };
+    let is_time = |time: &str| {
+        let bytes = time.as_bytes();
+        bytes.len() == 15 && bytes[3] == b' ' && bytes[6] == b' ' && bytes[9] == b':' && bytes[12] == b':'
+    };
+    let Some(rest) = rest.get(..15).filter(|time| is_time(time)).map(|time| {
+        header.time = Some(time.to_string());
+        rest[15..].trim_start()
+    }) else {
+        return header;
+    };
+    header.message = rest;
+    // The tag is the word ending with `:` or `[pid]:`, which may come after the host
+    let mut words = rest.splitn(3, ' ');
+    let (first, second) = (words.next().unwrap_or_default(), words.next());
+    let (host, word, message) = match tag(first) {
+        Some(_) => (None, first, rest[first.len()..].trim_start()),
+        None => match second.and_then(tag) {
+            Some(_) => (Some(first), second.unwrap_or_default(), words.next().unwrap_or_default()),
+            None => return header,
+        },
+    };
+    let (app, pid) = tag(word).unwrap_or_default();
+    header.host = host;
+    header.app = Some(app);
+    header.pid = pid;
+    header.message = message;
+    header
+}
+
+/// The program and process ID in a BSD syslog tag, `app[42]:` or `app:`.
+fn tag(word: &str) -> Option<(&str, Option<&str>)> {
+    let tag = word.strip_suffix(':')?;
+    Some(match tag.strip_suffix(']').and_then(|tag| tag.split_once('[')) {
+        Some((app, pid)) => (app, Some(pid)),
+        None => (tag, None),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nil_fields_are_left_out() {
+        assert_eq!(format_message(b"<165>1 - - - - - - hello\n"), r#"INFO: hello {"facility":"local4"}"#);
+    }
+
+    #[test]
+    fn rfc5424_header_and_structured_data_become_fields() {
+        let message = br#"<11>1 - web api 42 ID7 [ex@1 a="x\"y" b="p\]q"][meta seq="4"] disk full"#;
+        assert_eq!(
+            format_message(message),
+            r#"ERROR web api[42]: disk full {"facility":"user","msgid":"ID7","ex@1":{"a":"x\"y","b":"p]q"},"meta":{"seq":"4"}}"#,
+        );
+    }
+
+    #[test]
+    fn rfc5424_keeps_a_time_it_cannot_parse() {
+        let mut fields = Vec::new();
+        let header = rfc5424("yesterday host - - - - hi", &mut fields);
+        assert_eq!(header.time.as_deref(), Some("yesterday"));
+        assert_eq!((header.host, header.app, header.pid, header.message), (Some("host"), None, None, "hi"));
+        assert!(fields.is_empty());
+    }
+
+    #[test]
+    fn structured_data_unescapes_parameter_values() {
+        let (elements, message) = structured_data(r#"[a b="1\\2" c="[x\]"] rest"#);
+        assert_eq!(elements, [("a".to_string(), r#"{"b":"1\\2","c":"[x]"}"#.to_string())]);
+        assert_eq!(message, "rest");
+
+        let (elements, message) = structured_data("[id]");
+        assert_eq!(elements, [("id".to_string(), "{}".to_string())]);
+        assert_eq!(message, "");
+    }
+
+    #[test]
+    fn rfc3164_with_and_without_a_host() {
+        assert_eq!(
+            format_message(b"<13>Oct 11 22:14:15 db1 cron[7]: job done"),
+            r#"Oct 11 22:14:15 INFO db1 cron[7]: job done {"facility":"user"}"#,
+        );
+        let header = rfc3164("Oct 11 22:14:15 app[42]: hello world");
+        assert_eq!((header.host, header.app, header.pid, header.message), (None, Some("app"), Some("42"), "hello world"));
+        let header = rfc3164("Oct  1 02:00:00 just a message");
+        assert_eq!(header.time.as_deref(), Some("Oct  1 02:00:00"));
+        assert_eq!((header.host, header.app, header.message), (None, None, "just a message"));
+    }
+
+    #[test]
+    fn rfc3164_without_a_header_is_all_message() {
+        assert_eq!(format_message(b"<4>link down"), r#"WARN: link down {"facility":"kern"}"#);
+    }
+
+    #[test]
+    fn unparseable_messages_are_shown_as_they_are() {
+        assert_eq!(format_message(b"no priority\r\n"), "no priority");
+        assert_eq!(format_message(b"<x>1 - - - - - - hi"), "<x>1 - - - - - - hi");
+    }
+
+    #[tokio::test]
+    async fn tcp_frames_are_counted_or_end_at_newlines() {
+        let mut stream = &b"9 <13>hello9 <14>bye\nx<15>last line\n<16>unterminated"[..];
+        assert_eq!(read_frame(&mut stream).await.unwrap().unwrap(), b"<13>hello");
+        assert_eq!(read_frame(&mut stream).await.unwrap().unwrap(), b"<14>bye\nx");
+        assert_eq!(read_frame(&mut stream).await.unwrap().unwrap(), b"<15>last line\n");
+        assert_eq!(read_frame(&mut stream).await.unwrap().unwrap(), b"<16>unterminated");
+        assert!(read_frame(&mut stream).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn oversized_or_bad_lengths_fail() {
+        let error = read_frame(&mut &b"99999999 <13>hi"[..]).await.err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let error = read_frame(&mut &b"12x <13>hi"[..]).await.err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let error = read_frame(&mut &b"20 <13>cut off"[..]).await.err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
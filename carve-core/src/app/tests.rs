@@ -0,0 +1,297 @@
+use super::key::{KeyCode, KeyEvent, KeyModifiers};
+use super::{actions, App, KeyOptions, Mode};
+use crate::line::Line;
+
+const HEIGHT: usize = 10;
+
+/// A session that's read `lines` from stdin and drawn them in a pane `HEIGHT` rows tall.
+fn app(lines: &[&str]) -> App {
+    let mut app = App::new(None, None);
+    read(&mut app, lines);
+    app
+}
+
+/// Read more lines, then draw them.
+fn read(app: &mut App, lines: &[&str]) {
+    for text in lines {
+        app.push_line(Line::new(text.to_string(), 0));
+    }
+    frame(app);
+}
+
+/// Catch the panes up with any changes, as a frontend does before drawing each frame.
+fn frame(app: &mut App) {
+    app.refresh();
+    app.pane_mut().fit_viewport(HEIGHT);
+}
+
+/// Press each key in `keys`, written as for `--headless`, drawing a frame after each.
+fn press(app: &mut App, keys: &str) {
+    let options = KeyOptions { yank_context: 0, collapse_duplicates: false, confirm_quit_over: None };
+    for key in actions::parse_keys(keys).unwrap() {
+        app.handle_key(key, &options);
+        frame(app);
+    }
+}
+
+fn numbered(count: usize) -> Vec<String> {
+    (0..count).map(|n| format!("line {n}")).collect()
+}
+
+fn refs(lines: &[String]) -> Vec<&str> {
+    lines.iter().map(String::as_str).collect()
+}
+
+/// The text of each row the focused pane shows, whether or not it's scrolled into view.
+fn shown(app: &App) -> Vec<&str> {
+    app.pane().view.rows.iter().map(|row| app.lines[row.idx].text.as_str()).collect()
+}
+
+fn cursor_text(app: &App) -> &str {
+    &app.lines[app.pane().cursor_line().unwrap()].text
+}
+
+#[test]
+fn follows_new_lines_until_scrolled_away() {
+    let mut app = app(&refs(&numbered(20)));
+    assert!(app.pane().tailing);
+    assert_eq!(cursor_text(&app), "line 19");
+    assert_eq!(app.pane().scroll, 10);
+
+    press(&mut app, "k");
+    assert!(!app.pane().tailing);
+    read(&mut app, &["line 20", "line 21"]);
+    assert_eq!(cursor_text(&app), "line 18");
+
+    press(&mut app, "G");
+    assert!(app.pane().tailing);
+    assert_eq!(cursor_text(&app), "line 21");
+    read(&mut app, &["line 22"]);
+    assert_eq!(cursor_text(&app), "line 22");
+    assert_eq!(app.pane().scroll, 13);
+}
+
+#[test]
+fn half_pages_move_the_cursor_and_the_view() {
+    let mut app = app(&refs(&numbered(100)));
+    press(&mut app, "g");
+    assert_eq!((app.pane().cursor, app.pane().scroll), (0, 0));
+
+    press(&mut app, "d");
+    assert_eq!((app.pane().cursor, app.pane().scroll), (5, 5));
+    press(&mut app, "PageDown ctrl+d");
+    assert_eq!((app.pane().cursor, app.pane().scroll), (15, 15));
+
+    press(&mut app, "u");
+    assert_eq!((app.pane().cursor, app.pane().scroll), (10, 10));
+    press(&mut app, "PageUp ctrl+u b");
+    assert_eq!((app.pane().cursor, app.pane().scroll), (0, 0));
+}
+
+#[test]
+fn m_marks_lines_while_space_pages_down() {
+    let mut app = app(&refs(&numbered(30)));
+    press(&mut app, "g m m");
+    assert_eq!(app.marks.iter().copied().collect::<Vec<_>>(), [app.lines[0].id, app.lines[1].id]);
+    assert_eq!(app.pane().cursor, 2);
+    press(&mut app, "Space");
+    assert_eq!(app.pane().cursor, 7);
+}
+
+#[test]
+fn cursor_stops_at_either_end() {
+    let mut app = app(&refs(&numbered(3)));
+    press(&mut app, "g k k");
+    assert_eq!(cursor_text(&app), "line 0");
+
+    press(&mut app, "j j j j");
+    assert_eq!(cursor_text(&app), "line 2");
+    assert_eq!(app.pane().scroll, 0);
+}
+
+#[test]
+fn scrolling_up_keeps_the_cursor_in_view() {
+    let mut app = app(&refs(&numbered(30)));
+    press(&mut app, &["k"; 12].join(" "));
+    assert_eq!(cursor_text(&app), "line 17");
+    assert_eq!(app.pane().scroll, 17);
+}
+
+#[test]
+fn filter_shows_only_matching_lines() {
+    let mut app = app(&["INFO start", "ERROR disk full", "INFO ok", "ERROR net down"]);
+    press(&mut app, "/ERROR");
+    assert!(matches!(app.mode, Mode::Filter));
+    assert_eq!(shown(&app), ["ERROR disk full", "ERROR net down"]);
+
+    press(&mut app, "Enter");
+    assert!(matches!(app.mode, Mode::Normal));
+    read(&mut app, &["INFO later", "ERROR later"]);
+    assert_eq!(shown(&app), ["ERROR disk full", "ERROR net down", "ERROR later"]);
+}
+
+#[test]
+fn filters_stack_and_pop() {
+    let mut app = app(&["INFO start", "ERROR disk full", "INFO ok", "ERROR net down"]);
+    press(&mut app, "/ERROR Enter +!net Enter");
+    assert_eq!(shown(&app), ["ERROR disk full"]);
+    assert_eq!(app.pane().describe_filters(), "ERROR › !net");
+
+    press(&mut app, "-");
+    assert_eq!(shown(&app), ["ERROR disk full", "ERROR net down"]);
+    press(&mut app, "-");
+    assert_eq!(shown(&app).len(), 4);
+}
+
+#[test]
+fn filter_matches_regular_expressions() {
+    let mut app = app(&["GET /a 200", "GET /b 404", "POST /c 500", "GET /d 503"]);
+    press(&mut app, "/ / GET.*5\\d\\d$ Enter");
+    assert_eq!(shown(&app), ["GET /d 503"]);
+}
+
+#[test]
+fn block_delete_leaves_the_lines_a_filter_hides() {
+    let mut app = app(&["ERROR a", "INFO b", "ERROR c", "INFO d", "ERROR e"]);
+    press(&mut app, "/ERROR Enter g v v j d");
+    assert_eq!(shown(&app), ["ERROR e"]);
+    press(&mut app, "-");
+    assert_eq!(shown(&app), ["INFO b", "INFO d", "ERROR e"]);
+
+    press(&mut app, "r");
+    assert_eq!(shown(&app), ["ERROR a", "INFO b", "ERROR c", "INFO d", "ERROR e"]);
+}
+
+#[test]
+fn deleted_lines_are_restored_in_place() {
+    let mut app = app(&refs(&numbered(5)));
+    press(&mut app, "g j x");
+    assert_eq!(shown(&app), ["line 0", "line 2", "line 3", "line 4"]);
+    press(&mut app, "j x");
+    assert_eq!(shown(&app), ["line 0", "line 2", "line 4"]);
+
+    press(&mut app, "r");
+    assert_eq!(shown(&app), ["line 0", "line 2", "line 3", "line 4"]);
+    assert_eq!(cursor_text(&app), "line 3");
+    press(&mut app, "r r");
+    assert_eq!(shown(&app), ["line 0", "line 1", "line 2", "line 3", "line 4"]);
+    assert_eq!(app.message.as_deref(), Some("Nothing to restore"));
+}
+
+#[test]
+fn restored_lines_are_pinned_again() {
+    let mut app = app(&refs(&numbered(5)));
+    press(&mut app, "g j p x");
+    assert!(app.pinned.is_empty());
+    press(&mut app, "r");
+    assert_eq!(cursor_text(&app), "line 1");
+    assert_eq!(app.pinned.iter().copied().collect::<Vec<_>>(), [1]);
+}
+
+#[test]
+fn deleted_lines_are_forgotten_once_they_would_have_been_evicted() {
+    let mut app = app(&refs(&numbered(5)));
+    app.max_lines = Some(5);
+    press(&mut app, "g j x j j x");
+    assert_eq!(shown(&app), ["line 0", "line 2", "line 3"]);
+    read(&mut app, &["line 5", "line 6", "line 7", "line 8"]);
+    app.evict();
+    frame(&mut app);
+    assert_eq!(shown(&app), ["line 3", "line 5", "line 6", "line 7", "line 8"]);
+
+    press(&mut app, "r");
+    assert_eq!(shown(&app), ["line 3", "line 4", "line 5", "line 6", "line 7", "line 8"]);
+    press(&mut app, "r");
+    assert_eq!(app.message.as_deref(), Some("Nothing to restore"));
+}
+
+#[test]
+fn filter_command_sets_the_filter() {
+    let mut app = app(&["INFO start", "ERROR disk full"]);
+    app.execute("filter ERROR");
+    frame(&mut app);
+    assert_eq!(shown(&app), ["ERROR disk full"]);
+}
+
+#[test]
+fn search_jumps_to_matches_and_cycles_through_them() {
+    let mut lines = numbered(30);
+    for n in [4, 12, 25] {
+        lines[n] = format!("line {n} timeout");
+    }
+    let mut app = app(&refs(&lines));
+    press(&mut app, "f timeout");
+    assert!(matches!(app.mode, Mode::Search));
+    assert_eq!(app.pane().search.matches().len(), 3);
+
+    press(&mut app, "Enter");
+    assert!(matches!(app.mode, Mode::Normal));
+    assert!(!app.pane().tailing);
+    assert_eq!(cursor_text(&app), "line 4 timeout");
+
+    press(&mut app, "n");
+    assert_eq!(cursor_text(&app), "line 12 timeout");
+    press(&mut app, "n n");
+    assert_eq!(cursor_text(&app), "line 4 timeout");
+    press(&mut app, "N");
+    assert_eq!(cursor_text(&app), "line 25 timeout");
+}
+
+#[test]
+fn search_looks_only_in_filtered_lines() {
+    let mut app = app(&["ERROR disk", "INFO disk", "ERROR net", "INFO disk again"]);
+    press(&mut app, "/ERROR Enter f disk");
+    assert_eq!(app.pane().search.matches().len(), 1);
+    press(&mut app, "Enter");
+    assert_eq!(cursor_text(&app), "ERROR disk");
+}
+
+#[test]
+fn search_with_no_match_leaves_the_cursor() {
+    let mut app = app(&refs(&numbered(20)));
+    press(&mut app, "g j f nowhere Enter");
+    assert!(app.pane().search.matches().is_empty());
+    assert_eq!(cursor_text(&app), "line 1");
+    assert_eq!(app.pane().scroll, 0);
+}
+
+#[test]
+fn keys_convert_one_for_one() {
+    let keys = actions::parse_keys("ctrl+d G Enter").unwrap();
+    assert_eq!(keys, [
+        KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL),
+        KeyEvent::new(KeyCode::Char('G'), KeyModifiers::NONE),
+        KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+    ]);
+}
+
+#[test]
+fn quitting_names_where_a_large_output_would_go() {
+    let mut app = app(&["a", "b", "c"]);
+    assert!(!app.quit(Some(2), false));
+    assert_eq!(app.message.as_deref(), Some("Print 3 lines (0.0 MB) to stdout? y/n"));
+
+    app.destination = "fd 3".to_string();
+    app.quit(Some(2), false);
+    assert_eq!(app.message.as_deref(), Some("Print 3 lines (0.0 MB) to fd 3? y/n"));
+    assert!(app.quit(Some(3), false));
+}
+
+#[test]
+fn reordered_rows_take_in_context_in_arrival_order() {
+    let mut app = app(&[]);
+    let read_from = |app: &mut App, lines: &[(&str, usize)]| {
+        for (text, source) in lines {
+            app.push_line(Line::new(text.to_string(), *source));
+        }
+        frame(app);
+    };
+    app.execute("filter ~ERROR; set rank; set before=1");
+    read_from(&mut app, &[("ERROR", 0), ("quiet a", 0), ("quiet b", 1), ("ERROR", 1)]);
+    assert_eq!(shown(&app), ["quiet b", "ERROR", "ERROR"]);
+
+    // The line before the next error from the first source is shown as context, and ranks the
+    // same as the other context, so goes by when it arrived
+    read_from(&mut app, &[("ERROR", 0)]);
+    assert_eq!(shown(&app), ["quiet a", "quiet b", "ERROR", "ERROR", "ERROR"]);
+}
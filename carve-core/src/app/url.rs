@@ -0,0 +1,48 @@
+use std::io;
+use std::process::{Command, Stdio};
+
+/// The byte ranges of the `http://` and `https://` URLs in a line.
+///
+/// A URL runs until whitespace or a quote or angle bracket, less any trailing punctuation that's
+/// more likely to end the sentence than the URL, e.g. the `.` in `see https://example.com.`.
+pub fn find(text: &str) -> Vec<(usize, usize)> {
+    let mut urls = Vec::new();
+    let mut from = 0;
+    while let Some(pos) = text[from..].find("http") {
+        let start = from + pos;
+        let rest = &text[start..];
+        if !rest.starts_with("http://") && !rest.starts_with("https://") {
+            from = start + "http".len();
+            continue;
+        }
+        let len = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '<' | '>' | '`'))
+            .unwrap_or(rest.len());
+        let url = rest[..len].trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']', '}']);
+        urls.push((start, start + url.len()));
+        from = start + len;
+    }
+    urls
+}
+
+/// Open a URL in the browser with the platform's opener, without waiting for it to finish.
+pub fn open(url: &str) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    let mut child = command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // Reap the opener once it exits rather than leaving a zombie behind
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
@@ -0,0 +1,367 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::process::Stdio;
+use std::time::{Instant, SystemTime};
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+
+use crate::app::base64;
+use crate::app::http::{Target, FIRST_BACKOFF, MAX_BACKOFF};
+use crate::app::source::Sink;
+
+/// The largest message accepted, so a broken server can't exhaust memory.
+const MAX_MESSAGE: usize = 64 * 1024 * 1024;
+
+const CONTINUATION: u8 = 0x0;
+const TEXT: u8 = 0x1;
+const BINARY: u8 = 0x2;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xa;
+
+/// Spawn an async task that follows a WebSocket, adding each line of each message it receives,
+/// and reconnecting with backoff whenever it drops, as for `--url`.
+///
+/// `wss://` connections are made through `openssl s_client`, which needs to be installed.
+pub fn spawn(url: String, sink: Sink, source: usize) {
+    tokio::spawn(async move {
+        let mut backoff = FIRST_BACKOFF;
+        loop {
+            let started = Instant::now();
+            let why = match connect(&url, &sink, source).await {
+                Ok(()) => "connection closed".to_string(),
+                Err(e) => e.to_string(),
+            };
+            if started.elapsed() > MAX_BACKOFF {
+                backoff = FIRST_BACKOFF;
+            }
+            tracing::debug!(url, "{}", why);
+            sink.push(format!("──── {}: {}, reconnecting in {}s ────", url, why, backoff.as_secs()), source);
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    });
+}
+
+async fn connect(url: &str, sink: &Sink, source: usize) -> io::Result<()> {
+    if let Some(rest) = url.strip_prefix("ws://") {
+        let target = Target::parse(rest, 80);
+        let stream = tokio::net::TcpStream::connect(&target.address).await?;
+        let (read, write) = stream.into_split();
+        session(&target, read, write, sink, source).await
+    } else if let Some(rest) = url.strip_prefix("wss://") {
+        let target = Target::parse(rest, 443);
+        // s_client only checks the certificate is for the host when asked to, and without that
+        // a certificate for any other domain would do
+        let mut child = tokio::process::Command::new("openssl")
+            .args(["s_client", "-quiet", "-verify_return_error", "-connect", &target.address])
+            .args(["-servername", &target.host])
+            .args(["-verify_hostname", &target.host])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("failed to run openssl: {}", e)))?;
+        let read = child.stdout.take().expect("stdout is piped");
+        let write = child.stdin.take().expect("stdin is piped");
+        let mut stderr = BufReader::new(child.stderr.take().expect("stderr is piped")).lines();
+        let session = session(&target, read, write, sink, source);
+        tokio::pin!(session);
+        // Watch what openssl reports while the session runs, giving up on the connection as soon
+        // as it reports a certificate it couldn't verify
+        let mut errors = Vec::new();
+        let result = loop {
+            tokio::select! {
+                result = &mut session => break result,
+                line = stderr.next_line() => match line {
+                    Ok(Some(line)) if line.starts_with("verify error") => {
+                        break Err(io::Error::new(io::ErrorKind::InvalidData, line));
+                    }
+                    Ok(Some(line)) => errors.push(line),
+                    Ok(None) | Err(_) => break (&mut session).await,
+                },
+            }
+        };
+        if result.is_err() {
+            // openssl explains a failed connection, such as a bad certificate, on stderr
+            let _ = child.start_kill();
+            let mut rest = String::new();
+            let _ = stderr.into_inner().read_to_string(&mut rest).await;
+            errors.extend(rest.lines().map(str::to_string));
+            if let Some(error) = errors.iter().find_map(|line| openssl_error(line)) {
+                return Err(io::Error::other(error));
+            }
+        }
+        result
+    } else {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, "only ws:// and wss:// URLs can be followed"))
+    }
+}
+
+/// The reason given in one of openssl's error lines, such as `certificate verify failed`, which
+/// are written as `ID:error:CODE:LIBRARY:FUNCTION:REASON:FILE:LINE:DETAIL`.
+fn openssl_error(line: &str) -> Option<String> {
+    let fields: Vec<&str> = line.splitn(9, ':').collect();
+    match fields[..] {
+        [_, "error", _, _, _, reason, _, _, detail] if !detail.is_empty() => Some(format!("{}: {}", reason, detail)),
+        [_, "error", _, _, _, reason, ..] => Some(reason.to_string()),
+        _ => None,
+    }
+}
+
+/// Open the WebSocket with the HTTP upgrade handshake, then read messages until it's closed.
+async fn session(
+    target: &Target,
+    read: impl AsyncRead + Unpin,
+    mut write: impl AsyncWrite + Unpin,
+    sink: &Sink,
+    source: usize,
+) -> io::Result<()> {
+    let key = base64::encode(&random_bytes::<16>());
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        target.path, target.authority, key
+    );
+    write.write_all(request.as_bytes()).await?;
+    write.flush().await?;
+
+    let mut reader = BufReader::new(read);
+    let mut status = String::new();
+    reader.read_line(&mut status).await?;
+    if status.split_whitespace().nth(1) != Some("101") {
+        let status = status.trim();
+        return Err(io::Error::other(match status {
+            "" => "connection closed during the handshake".to_string(),
+            status => format!("server responded {}", status),
+        }));
+    }
+    // The headers aren't needed: a 101 response means the server switched to WebSocket
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut message = Vec::new();
+    loop {
+        let (fin, opcode, payload) = read_frame(&mut reader).await?;
+        match opcode {
+            CONTINUATION | TEXT | BINARY => {
+                if message.len() + payload.len() > MAX_MESSAGE {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "message too large"));
+                }
+                message.extend_from_slice(&payload);
+                if fin {
+                    for line in String::from_utf8_lossy(&message).lines() {
+                        sink.push(line.to_string(), source);
+                    }
+                    message.clear();
+                }
+            }
+            PING => write_frame(&mut write, PONG, &payload).await?,
+            CLOSE => {
+                // Echo the status code back to complete the closing handshake
+                let _ = write_frame(&mut write, CLOSE, &payload[..payload.len().min(2)]).await;
+                return Ok(());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Read one frame, returning whether it's the last of its message, its opcode and its payload.
+async fn read_frame(reader: &mut (impl AsyncRead + Unpin)) -> io::Result<(bool, u8, Vec<u8>)> {
+    let mut head = [0; 2];
+    reader.read_exact(&mut head).await?;
+    let fin = head[0] & 0x80 != 0;
+    let opcode = head[0] & 0x0f;
+    let len = match head[1] & 0x7f {
+        126 => reader.read_u16().await? as u64,
+        127 => reader.read_u64().await?,
+        len => len as u64,
+    };
+    if len > MAX_MESSAGE as u64 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "message too large"));
+    }
+    let mask = if head[1] & 0x80 != 0 {
+        let mut mask = [0; 4];
+        reader.read_exact(&mut mask).await?;
+        Some(mask)
+    } else {
+        None
+    };
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload).await?;
+    if let Some(mask) = mask {
+        payload.iter_mut().enumerate().for_each(|(i, byte)| *byte ^= mask[i % 4]);
+    }
+    Ok((fin, opcode, payload))
+}
+
+/// Write a frame as a client must: masked, and in one piece.
+async fn write_frame(writer: &mut (impl AsyncWrite + Unpin), opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => frame.push(0x80 | len as u8),
+        len @ 126..=0xffff => {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(0x80 | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    let mask = random_bytes::<4>();
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().enumerate().map(|(i, byte)| byte ^ mask[i % 4]));
+    writer.write_all(&frame).await?;
+    writer.flush().await
+}
+
+/// Bytes for the handshake key and frame masks, which only need to be unpredictable to
+/// intermediaries, not cryptographically random.
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0; N];
+    for chunk in bytes.chunks_mut(8) {
+        // Each RandomState is keyed from the OS's random source, so its hashes can't be predicted
+        // even though the time hashed can. Masks only stop whoever picks a frame's payload from
+        // predicting the bytes sent for it, which doesn't call for a cryptographic generator.
+        let mut hasher = RandomState::new().build_hasher();
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+        hasher.write_u128(now.as_nanos());
+        chunk.copy_from_slice(&hasher.finish().to_le_bytes()[..chunk.len()]);
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    async fn written(opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = Vec::new();
+        write_frame(&mut frame, opcode, payload).await.unwrap();
+        frame
+    }
+
+    /// A frame as a server sends it: unmasked, with the length in as few bytes as it fits.
+    fn server_frame(fin: bool, opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![if fin { 0x80 } else { 0 } | opcode];
+        match payload.len() {
+            len @ 0..=125 => frame.push(len as u8),
+            len @ 126..=0xffff => {
+                frame.push(126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    #[tokio::test]
+    async fn client_frames_are_masked() {
+        let frame = written(TEXT, b"hello").await;
+        assert_eq!(frame[0], 0x80 | TEXT);
+        assert_eq!(frame[1], 0x80 | 5);
+        let mask = &frame[2..6];
+        let unmasked: Vec<u8> = frame[6..].iter().enumerate().map(|(i, byte)| byte ^ mask[i % 4]).collect();
+        assert_eq!(unmasked, b"hello");
+    }
+
+    #[tokio::test]
+    async fn masked_frames_read_back_as_written() {
+        for len in [0, 1, 125, 126, 0xffff, 0x10000] {
+            let payload: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let frame = written(BINARY, &payload).await;
+            let (fin, opcode, read) = read_frame(&mut &frame[..]).await.unwrap();
+            assert!(fin);
+            assert_eq!(opcode, BINARY);
+            assert_eq!(read, payload, "payload of {} bytes", len);
+        }
+    }
+
+    #[tokio::test]
+    async fn long_payloads_take_extended_lengths() {
+        let frame = written(TEXT, &[b'x'; 126]).await;
+        assert_eq!(frame[1], 0x80 | 126);
+        assert_eq!(frame[2..4], 126u16.to_be_bytes());
+        assert_eq!(frame.len(), 2 + 2 + 4 + 126);
+
+        let frame = written(TEXT, &[b'x'; 0x10000]).await;
+        assert_eq!(frame[1], 0x80 | 127);
+        assert_eq!(frame[2..10], 0x10000u64.to_be_bytes());
+        assert_eq!(frame.len(), 2 + 8 + 4 + 0x10000);
+    }
+
+    #[tokio::test]
+    async fn server_frames_with_extended_lengths_are_read() {
+        for len in [126, 300, 0x10000] {
+            let payload = vec![b'y'; len];
+            let frame = server_frame(false, CONTINUATION, &payload);
+            let (fin, opcode, read) = read_frame(&mut &frame[..]).await.unwrap();
+            assert!(!fin);
+            assert_eq!(opcode, CONTINUATION);
+            assert_eq!(read.len(), len);
+        }
+    }
+
+    #[tokio::test]
+    async fn oversized_frames_are_refused() {
+        let mut frame = vec![0x80 | BINARY, 127];
+        frame.extend_from_slice(&(MAX_MESSAGE as u64 + 1).to_be_bytes());
+        let error = read_frame(&mut &frame[..]).await.err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn fragmented_messages_are_joined_around_control_frames() {
+        let (client, server) = tokio::io::duplex(1 << 20);
+        let (read, write) = tokio::io::split(client);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (new_sources, _) = mpsc::unbounded_channel();
+        let sink = Sink { tx, exporter: None, next_source: Arc::new(AtomicUsize::new(1)), new_sources };
+        let target = Target::parse("example.com/feed", 80);
+        let client = tokio::spawn(async move { session(&target, read, write, &sink, 0).await });
+
+        let (server_read, mut server_write) = tokio::io::split(server);
+        let mut server_read = BufReader::new(server_read);
+        let mut request = String::new();
+        while server_read.read_line(&mut request).await.unwrap() > 2 {}
+        assert!(request.starts_with("GET /feed HTTP/1.1\r\nHost: example.com\r\n"));
+        assert!(request.contains("Sec-WebSocket-Key: "));
+
+        let mut frames = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n".to_vec();
+        frames.extend(server_frame(false, TEXT, b"hel"));
+        frames.extend(server_frame(true, PING, b"are you there"));
+        frames.extend(server_frame(false, CONTINUATION, b"lo\nwor"));
+        frames.extend(server_frame(true, CONTINUATION, b"ld"));
+        frames.extend(server_frame(true, TEXT, b"next"));
+        frames.extend(server_frame(true, CLOSE, &1000u16.to_be_bytes()));
+        server_write.write_all(&frames).await.unwrap();
+
+        assert_eq!(read_frame(&mut server_read).await.unwrap(), (true, PONG, b"are you there".to_vec()));
+        assert_eq!(read_frame(&mut server_read).await.unwrap(), (true, CLOSE, 1000u16.to_be_bytes().to_vec()));
+        client.await.unwrap().unwrap();
+        let lines: Vec<String> = std::iter::from_fn(|| rx.try_recv().ok()).map(|line| line.text).collect();
+        assert_eq!(lines, ["hello", "world", "next"]);
+    }
+
+    #[test]
+    fn random_bytes_differ_between_calls() {
+        assert_ne!(random_bytes::<16>(), random_bytes::<16>());
+    }
+}
@@ -0,0 +1,52 @@
+/// Tracks a multi-line block, such as a stack trace, as its lines arrive so continuation lines
+/// can be folded under the line that started it.
+pub struct Block {
+    pub head: usize, // Buffer index of the first line of the block
+    python: bool,
+    indented: bool,
+    finished: bool,
+}
+
+impl Block {
+    pub fn new(head: usize, text: &str) -> Self {
+        Self {
+            head,
+            python: text.starts_with("Traceback (most recent call last)"),
+            indented: false,
+            finished: false,
+        }
+    }
+
+    /// Whether `line` continues the block, updating the block's state if it does.
+    ///
+    /// Indented lines always continue a block, which covers Java's `\tat ...` frames and most
+    /// pretty-printed output. Java's `Caused by:` lines do too, and a Python traceback runs up to
+    /// and including the unindented exception line that follows its frames.
+    pub fn continues(&mut self, line: &str) -> bool {
+        if self.finished {
+            return false;
+        }
+        if line.starts_with([' ', '\t']) {
+            self.indented = true;
+            return true;
+        }
+        if line.starts_with("Caused by: ") {
+            return true;
+        }
+        if self.python && self.indented && is_exception_line(line) {
+            self.finished = true;
+            return true;
+        }
+        false
+    }
+}
+
+/// Whether a line looks like the final line of a Python traceback, e.g. `ValueError: bad value`
+/// or `requests.exceptions.Timeout`.
+fn is_exception_line(line: &str) -> bool {
+    let name = line.split(':').next().unwrap_or_default();
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+
+use crate::line::Line;
+
+/// The most rows of values listed in the panel.
+pub const MAX_SHOWN: usize = 100;
+
+/// Live counts of the values captured by the groups of a regular expression search, like
+/// `grep -oP | sort | uniq -c | sort -rn`, with a column for each group.
+///
+/// Every match is counted, so a line with two matches counts twice. Counts cover the search's
+/// matches in the buffer, so they drop as lines are evicted.
+#[derive(Default)]
+pub struct Captures {
+    counts: HashMap<Vec<String>, u64>,
+    search: String, // Identifies the search the counts are for
+    counted: usize, // Matches of the search already counted
+    pub selected: usize,
+}
+
+impl Captures {
+    /// Count the values captured in any matches found since the last update, or start again if
+    /// it's a different search, or its matches were dropped.
+    pub fn update(&mut self, search: &str, regex: Option<&Regex>, matches: &[(usize, usize, usize)], lines: &[Line]) {
+        if search != self.search || matches.len() < self.counted {
+            self.rescan();
+            self.selected = 0;
+            self.search = search.to_string();
+        }
+        let Some(regex) = regex.filter(|regex| regex.captures_len() > 1) else {
+            return;
+        };
+        for &(idx, start, _) in &matches[self.counted..] {
+            // The first match at the match's start is the match itself
+            let Some(captures) = regex.captures_at(&lines[idx].text, start) else {
+                continue;
+            };
+            let values = captures
+                .iter()
+                .skip(1)
+                .map(|group| group.map_or_else(String::new, |group| group.as_str().to_string()))
+                .collect();
+            *self.counts.entry(values).or_default() += 1;
+        }
+        self.counted = matches.len();
+    }
+
+    /// Count every match again after lines were evicted or put back, which moves the matches
+    /// already counted.
+    pub fn rescan(&mut self) {
+        self.counts.clear();
+        self.counted = 0;
+    }
+
+    /// The heading of each column: the group's name, or its number if it has none.
+    pub fn headings(regex: &Regex) -> Vec<String> {
+        regex
+            .capture_names()
+            .enumerate()
+            .skip(1)
+            .map(|(i, name)| name.map_or_else(|| i.to_string(), str::to_string))
+            .collect()
+    }
+
+    /// The `n` most frequent combinations of values, most frequent first, with their counts.
+    pub fn top(&self, n: usize) -> Vec<(&[String], u64)> {
+        let mut top: Vec<(&[String], u64)> = self.counts.iter().map(|(v, c)| (v.as_slice(), *c)).collect();
+        top.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        top.truncate(n);
+        top
+    }
+
+    pub fn select_next(&mut self) {
+        let shown = self.counts.len().min(MAX_SHOWN);
+        self.selected = (self.selected + 1).min(shown.saturating_sub(1));
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// The values currently selected, if there are any.
+    pub fn selected_values(&self) -> Option<Vec<String>> {
+        self.top(MAX_SHOWN).get(self.selected).map(|(values, _)| values.to_vec())
+    }
+}
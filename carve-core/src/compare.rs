@@ -0,0 +1,86 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::line::Line;
+
+/// How often a new point is added to the trend chart.
+const BUCKET_DURATION: Duration = Duration::from_secs(1);
+/// How many points of history the trend chart keeps.
+const MAX_HISTORY: usize = 120;
+
+/// Live counts of lines matching two patterns, e.g. "requests" vs "errors", along with a short
+/// history of the ratio between them so a change in error rate stands out during a rollout.
+pub struct Comparison {
+    pub a: String,
+    pub b: String,
+    pub count_a: u64,
+    pub count_b: u64,
+    scanned: usize,
+    bucket: (u64, u64),
+    bucket_start: Instant,
+    history: VecDeque<(u64, u64)>,
+}
+
+impl Comparison {
+    pub fn new(a: String, b: String) -> Self {
+        Self {
+            a,
+            b,
+            count_a: 0,
+            count_b: 0,
+            scanned: 0,
+            bucket: (0, 0),
+            bucket_start: Instant::now(),
+            history: VecDeque::new(),
+        }
+    }
+
+    /// Count any lines that have arrived since the last update and roll the trend forward.
+    pub fn update(&mut self, lines: &[Line]) {
+        for line in &lines[self.scanned.min(lines.len())..] {
+            if line.text.contains(&self.a) {
+                self.count_a += 1;
+                self.bucket.0 += 1;
+            }
+            if line.text.contains(&self.b) {
+                self.count_b += 1;
+                self.bucket.1 += 1;
+            }
+        }
+        self.scanned = lines.len();
+
+        while self.bucket_start.elapsed() >= BUCKET_DURATION {
+            self.history.push_back(std::mem::take(&mut self.bucket));
+            if self.history.len() > MAX_HISTORY {
+                self.history.pop_front();
+            }
+            self.bucket_start += BUCKET_DURATION;
+        }
+    }
+
+    /// Account for lines dropped from the buffer so new lines aren't skipped.
+    pub fn evicted(&mut self, removed: usize) {
+        self.scanned = self.scanned.saturating_sub(removed);
+    }
+
+    /// Account for deleted lines being put back into the buffer, which were already counted.
+    pub fn restored(&mut self, count: usize) {
+        self.scanned += count;
+    }
+
+    /// B as a fraction of A over the whole session.
+    pub fn ratio(&self) -> Option<f64> {
+        (self.count_a > 0).then(|| self.count_b as f64 / self.count_a as f64)
+    }
+
+    /// The ratio for each recent time bucket as a whole percentage, most recent last, for
+    /// rendering as a sparkline. Buckets where A didn't occur at all are reported as zero.
+    pub fn trend(&self, width: usize) -> Vec<u64> {
+        let skip = self.history.len().saturating_sub(width);
+        self.history
+            .iter()
+            .skip(skip)
+            .map(|(a, b)| if *a == 0 { 0 } else { b * 100 / a })
+            .collect()
+    }
+}
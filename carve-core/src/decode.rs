@@ -0,0 +1,249 @@
+/// Text encodings recognised in input.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Encoding {
+    /// The bytes of a `\n` in a UTF-16 encoding.
+    fn newline(self) -> [u8; 2] {
+        match self {
+            Encoding::Utf16Be => [0, b'\n'],
+            _ => [b'\n', 0],
+        }
+    }
+}
+
+/// A line of input, along with the bytes it was read from if they weren't valid UTF-8, so they
+/// can still be inspected.
+pub struct Decoded {
+    pub text: String,
+    pub raw: Option<Vec<u8>>,
+}
+
+/// Splits raw input into lines, working out its encoding from the first bytes.
+///
+/// Most input is UTF-8, but PowerShell pipelines and files written by `Out-File` are often
+/// UTF-16 and start with a byte order mark, so input starting with a UTF-16 byte order mark, or
+/// looking like UTF-16 ASCII text from the zero bytes in it, is decoded as UTF-16. A UTF-8 byte
+/// order mark is dropped, as is the `\r` of a `\r\n` line ending. Invalid sequences become `�`
+/// rather than ending the input.
+#[derive(Default)]
+pub struct Decoder {
+    encoding: Option<Encoding>,
+    pending: Vec<u8>,
+}
+
+impl Decoder {
+    /// Add the next chunk of input, returning the lines it completes.
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.feed_raw(bytes).into_iter().map(|line| line.text).collect()
+    }
+
+    /// The last line if the input ended without a line ending.
+    pub fn finish(&mut self) -> Option<String> {
+        self.finish_raw().map(|line| line.text)
+    }
+
+    /// Add the next chunk of input, returning the lines it completes with the bytes of any that
+    /// weren't valid UTF-8.
+    pub fn feed_raw(&mut self, bytes: &[u8]) -> Vec<Decoded> {
+        self.pending.extend_from_slice(bytes);
+        let encoding = match self.encoding {
+            Some(encoding) => encoding,
+            None => match self.detect() {
+                Some(encoding) => encoding,
+                None => return Vec::new(),
+            },
+        };
+        let mut lines = Vec::new();
+        match encoding {
+            Encoding::Utf8 => {
+                let mut start = 0;
+                while let Some(end) = self.pending[start..].iter().position(|&b| b == b'\n') {
+                    lines.push(utf8(&self.pending[start..start + end]));
+                    start += end + 1;
+                }
+                self.pending.drain(..start);
+            }
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                let mut start = 0;
+                for idx in (0..self.pending.len().saturating_sub(1)).step_by(2) {
+                    if self.pending[idx..idx + 2] == encoding.newline() {
+                        lines.push(Decoded {
+                            text: utf16(encoding, &self.pending[start..idx]),
+                            raw: None,
+                        });
+                        start = idx + 2;
+                    }
+                }
+                self.pending.drain(..start);
+            }
+        }
+        lines
+    }
+
+    /// The last line if the input ended without a line ending, with its bytes if they weren't
+    /// valid UTF-8.
+    pub fn finish_raw(&mut self) -> Option<Decoded> {
+        if self.encoding.is_none() {
+            self.detect();
+        }
+        let rest = std::mem::take(&mut self.pending);
+        if rest.is_empty() {
+            return None;
+        }
+        Some(match self.encoding {
+            Some(encoding @ (Encoding::Utf16Le | Encoding::Utf16Be)) => Decoded {
+                text: utf16(encoding, &rest),
+                raw: None,
+            },
+            _ => utf8(&rest),
+        })
+    }
+
+    /// Work out the encoding once there are enough bytes to tell, or the first line has ended,
+    /// dropping any byte order mark.
+    fn detect(&mut self) -> Option<Encoding> {
+        let bytes = &self.pending;
+        let (encoding, bom) = if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+            (Encoding::Utf8, 3)
+        } else if bytes.starts_with(&[0xFF, 0xFE]) {
+            (Encoding::Utf16Le, 2)
+        } else if bytes.starts_with(&[0xFE, 0xFF]) {
+            (Encoding::Utf16Be, 2)
+        } else if bytes.len() < 4 && !bytes.contains(&b'\n') {
+            return None;
+        } else if bytes.len() >= 4 && bytes[0] != 0 && bytes[1] == 0 && bytes[2] != 0 && bytes[3] == 0 {
+            (Encoding::Utf16Le, 0)
+        } else if bytes.len() >= 4 && bytes[0] == 0 && bytes[1] != 0 && bytes[2] == 0 && bytes[3] != 0 {
+            (Encoding::Utf16Be, 0)
+        } else {
+            (Encoding::Utf8, 0)
+        };
+        self.pending.drain(..bom);
+        if encoding != Encoding::Utf8 {
+            tracing::debug!(?encoding, "decoding input as UTF-16");
+        }
+        self.encoding = Some(encoding);
+        Some(encoding)
+    }
+}
+
+fn utf8(bytes: &[u8]) -> Decoded {
+    let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+    match String::from_utf8_lossy(bytes) {
+        std::borrow::Cow::Borrowed(text) => Decoded {
+            text: text.to_string(),
+            raw: None,
+        },
+        std::borrow::Cow::Owned(text) => Decoded {
+            text,
+            raw: Some(bytes.to_vec()),
+        },
+    }
+}
+
+/// Decode a line of UTF-16. A trailing odd byte, from input cut off mid-character, is dropped.
+fn utf16(encoding: Encoding, bytes: &[u8]) -> String {
+    let units = bytes.chunks_exact(2).map(|pair| match encoding {
+        Encoding::Utf16Be => u16::from_be_bytes([pair[0], pair[1]]),
+        _ => u16::from_le_bytes([pair[0], pair[1]]),
+    });
+    let mut text: String = char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect();
+    if text.ends_with('\r') {
+        text.pop();
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    fn utf16be(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_be_bytes).collect()
+    }
+
+    #[test]
+    fn utf8_byte_order_mark_is_dropped() {
+        let mut decoder = Decoder::default();
+        assert_eq!(decoder.feed(b"\xEF\xBB\xBFfirst\nsecond\n"), ["first", "second"]);
+    }
+
+    #[test]
+    fn byte_order_mark_split_across_chunks_is_dropped() {
+        let mut decoder = Decoder::default();
+        assert!(decoder.feed(b"\xEF").is_empty());
+        assert!(decoder.feed(b"\xBB").is_empty());
+        assert_eq!(decoder.feed(b"\xBFline\n"), ["line"]);
+    }
+
+    #[test]
+    fn utf16_byte_order_marks_pick_the_byte_order() {
+        let mut decoder = Decoder::default();
+        let mut input = vec![0xFF, 0xFE];
+        input.extend(utf16le("PS> Get-Date\r\nTuesday\r\n"));
+        assert_eq!(decoder.feed(&input), ["PS> Get-Date", "Tuesday"]);
+
+        let mut decoder = Decoder::default();
+        let mut input = vec![0xFE, 0xFF];
+        input.extend(utf16be("caf\u{e9}\n"));
+        assert_eq!(decoder.feed(&input), ["caf\u{e9}"]);
+    }
+
+    #[test]
+    fn utf16_is_detected_without_a_byte_order_mark() {
+        let mut decoder = Decoder::default();
+        assert_eq!(decoder.feed(&utf16le("ab\ncd\n")), ["ab", "cd"]);
+
+        let mut decoder = Decoder::default();
+        assert_eq!(decoder.feed(&utf16be("ab\ncd\n")), ["ab", "cd"]);
+    }
+
+    #[test]
+    fn a_line_shorter_than_the_detection_window_is_utf8() {
+        let mut decoder = Decoder::default();
+        assert_eq!(decoder.feed(b"a\nb"), ["a"]);
+        assert_eq!(decoder.finish().as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn crlf_split_across_chunks_ends_one_line() {
+        let mut decoder = Decoder::default();
+        assert_eq!(decoder.feed(b"first line\r"), Vec::<String>::new());
+        assert_eq!(decoder.feed(b"\nsecond\r\nthi"), ["first line", "second"]);
+        assert_eq!(decoder.feed(b"rd\r"), Vec::<String>::new());
+        assert_eq!(decoder.finish().as_deref(), Some("third"));
+    }
+
+    #[test]
+    fn utf16_split_mid_character_and_mid_crlf() {
+        let input = utf16le("one\r\ntwo\r\n");
+        let mut decoder = Decoder::default();
+        let mut lines = Vec::new();
+        for chunk in input.chunks(3) {
+            lines.extend(decoder.feed(chunk));
+        }
+        assert_eq!(lines, ["one", "two"]);
+        assert_eq!(decoder.finish(), None);
+    }
+
+    #[test]
+    fn invalid_utf8_keeps_its_bytes() {
+        let mut decoder = Decoder::default();
+        let lines = decoder.feed_raw(b"ok\nbad \xFF\xFE byte\n");
+        assert_eq!(lines[0].text, "ok");
+        assert_eq!(lines[0].raw, None);
+        assert_eq!(lines[1].text, "bad \u{fffd}\u{fffd} byte");
+        assert_eq!(lines[1].raw.as_deref(), Some(&b"bad \xFF\xFE byte"[..]));
+    }
+}
@@ -0,0 +1,205 @@
+use crate::line::Line;
+
+/// Give up on finding a minimal diff after this many insertions and deletions and treat the
+/// remainder as replaced wholesale, so two unrelated inputs can't stall the UI.
+const MAX_EDITS: usize = 2000;
+
+/// How a row of the diff differs between the two sides.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Change {
+    Equal,
+    /// Only on the left.
+    Removed,
+    /// Only on the right.
+    Added,
+    /// Replaced by the line opposite.
+    Changed,
+}
+
+/// A row of the side-by-side view, holding the buffer index of the line on each side.
+#[derive(Copy, Clone)]
+pub struct Row {
+    pub left: Option<usize>,
+    pub right: Option<usize>,
+    pub change: Change,
+}
+
+/// A side-by-side diff between the lines of two sources, e.g. the logs of a good run and a bad
+/// one. It's recomputed whenever new lines arrive.
+pub struct Diff {
+    pub left: usize, // Source shown on the left
+    pub right: usize, // Source shown on the right
+    pub rows: Vec<Row>,
+    pub scroll: usize,
+    pub height: usize, // Rows shown as of the last frame
+    scanned: usize, // Length of the buffer when the diff was last computed
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+impl Diff {
+    pub fn new(left: usize, right: usize) -> Self {
+        Self {
+            left,
+            right,
+            rows: Vec::new(),
+            scroll: 0,
+            height: 0,
+            scanned: 0,
+        }
+    }
+
+    /// Recompute the diff if any lines have arrived since it was last computed.
+    pub fn update(&mut self, lines: &[Line]) {
+        if lines.len() == self.scanned {
+            return;
+        }
+        self.scanned = lines.len();
+
+        let side = |source: usize| -> Vec<usize> {
+            (0..lines.len()).filter(|&idx| lines[idx].source == source).collect()
+        };
+        let (left, right) = (side(self.left), side(self.right));
+        let a: Vec<&str> = left.iter().map(|&idx| lines[idx].text.as_str()).collect();
+        let b: Vec<&str> = right.iter().map(|&idx| lines[idx].text.as_str()).collect();
+
+        self.rows.clear();
+        let (mut x, mut y) = (0, 0);
+        let mut removed = Vec::new();
+        let mut added = Vec::new();
+        for op in diff(&a, &b).into_iter().chain([Op::Equal]) {
+            match op {
+                Op::Delete => {
+                    removed.push(left[x]);
+                    x += 1;
+                }
+                Op::Insert => {
+                    added.push(right[y]);
+                    y += 1;
+                }
+                Op::Equal => {
+                    // Pair up the lines removed and added since the last common line so a changed
+                    // line sits opposite its replacement.
+                    for i in 0..removed.len().max(added.len()) {
+                        let (left, right) = (removed.get(i).copied(), added.get(i).copied());
+                        let change = match (left, right) {
+                            (Some(_), Some(_)) => Change::Changed,
+                            (Some(_), None) => Change::Removed,
+                            _ => Change::Added,
+                        };
+                        self.rows.push(Row { left, right, change });
+                    }
+                    removed.clear();
+                    added.clear();
+                    if x < left.len() && y < right.len() {
+                        self.rows.push(Row {
+                            left: Some(left[x]),
+                            right: Some(right[y]),
+                            change: Change::Equal,
+                        });
+                        x += 1;
+                        y += 1;
+                    }
+                }
+            }
+        }
+        self.scroll = self.scroll.min(self.rows.len().saturating_sub(self.height));
+    }
+
+    /// Force the diff to be recomputed after lines have been evicted, as the buffer indices it
+    /// holds are no longer valid.
+    pub fn evicted(&mut self) {
+        self.scanned = usize::MAX;
+    }
+
+    pub fn scroll_up(&mut self, amount: usize) {
+        self.scroll = self.scroll.saturating_sub(amount);
+    }
+
+    pub fn scroll_down(&mut self, amount: usize) {
+        self.scroll = self.scroll.saturating_add(amount).min(self.rows.len().saturating_sub(self.height));
+    }
+}
+
+/// The edits turning `a` into `b`, using Myers' algorithm after trimming the common prefix and
+/// suffix, which is usually most of two runs of the same program.
+fn diff(a: &[&str], b: &[&str]) -> Vec<Op> {
+    let prefix = a.iter().zip(b).take_while(|(a, b)| a == b).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut ops = vec![Op::Equal; prefix];
+    let (a_mid, b_mid) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    match myers(a_mid, b_mid) {
+        Some(mid) => ops.extend(mid),
+        None => {
+            ops.extend(std::iter::repeat_n(Op::Delete, a_mid.len()));
+            ops.extend(std::iter::repeat_n(Op::Insert, b_mid.len()));
+        }
+    }
+    ops.extend(std::iter::repeat_n(Op::Equal, suffix));
+    ops
+}
+
+/// A shortest edit script from `a` to `b`, or `None` if it needs more than `MAX_EDITS` edits.
+fn myers(a: &[&str], b: &[&str]) -> Option<Vec<Op>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let offset = n + m + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    // The furthest reaching x on each diagonal after each step, for -d..=d at step d
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    let at = |k: isize| (k + offset) as usize;
+    let mut found = None;
+    'search: for d in 0..=((n + m) as usize).min(MAX_EDITS) as isize {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) {
+                v[at(k + 1)]
+            } else {
+                v[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[at(k)] = x;
+            if x >= n && y >= m {
+                found = Some(d);
+                trace.push(v[at(-d)..=at(d)].to_vec());
+                break 'search;
+            }
+        }
+        trace.push(v[at(-d)..=at(d)].to_vec());
+    }
+
+    // Walk back from the end, one edit per step, following the snakes of equal lines between.
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (1..=found?).rev() {
+        let previous = &trace[d as usize - 1];
+        let prev = |k: isize| previous[(k + d - 1) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && prev(k - 1) < prev(k + 1)) { k + 1 } else { k - 1 };
+        let prev_x = prev(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            ops.push(Op::Equal);
+            x -= 1;
+            y -= 1;
+        }
+        ops.push(if prev_k == k + 1 { Op::Insert } else { Op::Delete });
+        (x, y) = (prev_x, prev_y);
+    }
+    ops.extend(std::iter::repeat_n(Op::Equal, x as usize));
+    ops.reverse();
+    Some(ops)
+}
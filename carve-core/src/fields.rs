@@ -0,0 +1,69 @@
+use std::ops::RangeInclusive;
+
+use crate::table::Delimiter;
+
+/// Which fields of each line to show, like `cut -f` or awk's `$1, $3`, for narrowing wide
+/// lines down to the interesting columns without changing them.
+pub struct Fields {
+    ranges: Vec<RangeInclusive<usize>>, // Field numbers, counting from 1
+    delimiter: Delimiter,
+}
+
+impl Fields {
+    /// Parse a list of field numbers and ranges separated by commas, such as `1,3,7-`, where
+    /// `N-` runs to the last field and `-N` from the first.
+    pub fn parse(list: &str, delimiter: Delimiter) -> Result<Self, String> {
+        let number = |n: &str| match n.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(format!("fields are numbered from 1, not {}", n)),
+        };
+        let ranges = list
+            .split(',')
+            .map(|item| match item.split_once('-') {
+                Some(("", "")) => Err("a range needs a start or an end".to_string()),
+                Some((start, "")) => Ok(number(start)?..=usize::MAX),
+                Some(("", end)) => Ok(1..=number(end)?),
+                Some((start, end)) => Ok(number(start)?..=number(end)?),
+                None => number(item).map(|n| n..=n),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { ranges, delimiter })
+    }
+
+    /// The byte ranges of the selected fields in `text`, in the order they appear in it.
+    pub fn select(&self, text: &str) -> Vec<(usize, usize)> {
+        let fields: Vec<(usize, usize)> = match self.delimiter {
+            Delimiter::Whitespace => text
+                .split_whitespace()
+                .map(|field| {
+                    let start = field.as_ptr() as usize - text.as_ptr() as usize;
+                    (start, start + field.len())
+                })
+                .collect(),
+            Delimiter::Char(delimiter) => {
+                let mut start = 0;
+                text.split(delimiter)
+                    .map(|field| {
+                        let range = (start, start + field.len());
+                        start += field.len() + delimiter.len_utf8();
+                        range
+                    })
+                    .collect()
+            }
+        };
+        fields
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| self.ranges.iter().any(|range| range.contains(&(i + 1))))
+            .map(|(_, field)| field)
+            .collect()
+    }
+
+    /// What goes between the selected fields when they're shown.
+    pub fn separator(&self) -> String {
+        match self.delimiter {
+            Delimiter::Whitespace => " ".to_string(),
+            Delimiter::Char(delimiter) => delimiter.to_string(),
+        }
+    }
+}
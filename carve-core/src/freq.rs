@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use crate::line::Line;
+
+/// The most messages listed in the panel.
+pub const MAX_SHOWN: usize = 100;
+
+/// Live counts of how often each kind of message occurs, like `sort | uniq -c | sort -rn`.
+///
+/// Lines are clustered by replacing anything that looks like a variable (a number, ID, timestamp
+/// and so on) with `*`, so `took 12ms` and `took 40ms` count as the same message. Counts cover
+/// the buffer as of when the panel was opened and every line read since, even once evicted.
+#[derive(Default)]
+pub struct Frequencies {
+    counts: HashMap<String, u64>,
+    scanned: usize,
+    pub selected: usize,
+}
+
+impl Frequencies {
+    /// Count any lines that have arrived since the last update.
+    pub fn update(&mut self, lines: &[Line]) {
+        for line in &lines[self.scanned.min(lines.len())..] {
+            *self.counts.entry(template(&line.text)).or_default() += 1;
+        }
+        self.scanned = lines.len();
+    }
+
+    /// Account for lines dropped from the buffer so new lines aren't skipped.
+    pub fn evicted(&mut self, removed: usize) {
+        self.scanned = self.scanned.saturating_sub(removed);
+    }
+
+    /// Account for deleted lines being put back into the buffer, which were already counted.
+    pub fn restored(&mut self, count: usize) {
+        self.scanned += count;
+    }
+
+    /// The `n` most frequent messages, most frequent first, with their counts.
+    pub fn top(&self, n: usize) -> Vec<(&str, u64)> {
+        let mut top: Vec<(&str, u64)> = self.counts.iter().map(|(t, c)| (t.as_str(), *c)).collect();
+        top.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        top.truncate(n);
+        top
+    }
+
+    pub fn select_next(&mut self) {
+        let shown = self.counts.len().min(MAX_SHOWN);
+        self.selected = (self.selected + 1).min(shown.saturating_sub(1));
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
+
+/// A line with each run of letters and digits containing a digit replaced by `*`, e.g.
+/// `GET /users/42 took 12ms` becomes `GET /users/* took *`.
+pub fn template(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut word = String::new();
+    let flush = |word: &mut String, out: &mut String| {
+        if word.chars().any(|c| c.is_ascii_digit()) {
+            out.push('*');
+        } else {
+            out.push_str(word);
+        }
+        word.clear();
+    };
+    for c in line.chars() {
+        if c.is_alphanumeric() {
+            word.push(c);
+        } else {
+            flush(&mut word, &mut out);
+            out.push(c);
+        }
+    }
+    flush(&mut word, &mut out);
+    out
+}
+
+/// The text to filter on to find the lines of a message: the longest part of its template that
+/// isn't a variable, since filters match plain text.
+pub fn filter_for(template: &str) -> String {
+    template
+        .split('*')
+        .map(str::trim)
+        .max_by_key(|part| part.len())
+        .unwrap_or_default()
+        .to_string()
+}
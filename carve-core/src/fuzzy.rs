@@ -0,0 +1,80 @@
+/// Points for each character of the pattern matched.
+const MATCH: i64 = 16;
+/// Extra points for a match at the start of a word, so `cto` ranks `connection timed out` above
+/// `doctor`.
+const BOUNDARY: i64 = 8;
+/// Extra points for a match directly after the previous one, or the bonus the run of matches
+/// started with if that's more, so `cto` prefers `cto` to `c t o`.
+const CONSECUTIVE: i64 = 4;
+/// Points lost for each character skipped between matches, after the first.
+const GAP: i64 = 1;
+/// Points lost for starting a gap.
+const GAP_START: i64 = 3;
+
+/// Score how well `text` matches `pattern`, fzf style: the characters of the pattern must all
+/// appear in the text in order, but not necessarily together. Matches that are closer together
+/// or start words score higher. `None` if the text doesn't match at all.
+///
+/// Matching ignores case unless the pattern has a capital letter in it.
+pub fn score(pattern: &str, text: &str) -> Option<i64> {
+    let ignore_case = !pattern.chars().any(char::is_uppercase);
+    let fold = |c: char| if ignore_case { c.to_ascii_lowercase() } else { c };
+    let pattern: Vec<char> = pattern.chars().map(fold).collect();
+    let text: Vec<char> = text.chars().collect();
+    if pattern.is_empty() {
+        return Some(0);
+    }
+
+    // Find where the first match ends, then work back from there to the latest place it could
+    // start, which gives the tightest match ending at the same place
+    let mut next = 0;
+    let mut end = None;
+    for (idx, &c) in text.iter().enumerate() {
+        if fold(c) == pattern[next] {
+            next += 1;
+            if next == pattern.len() {
+                end = Some(idx);
+                break;
+            }
+        }
+    }
+    let end = end?;
+    let mut remaining = pattern.len();
+    let mut start = end;
+    for idx in (0..=end).rev() {
+        if fold(text[idx]) == pattern[remaining - 1] {
+            remaining -= 1;
+            if remaining == 0 {
+                start = idx;
+                break;
+            }
+        }
+    }
+
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+    let mut run_bonus = 0; // The bonus the current run of consecutive matches started with
+    for idx in start..=end {
+        if next == pattern.len() || fold(text[idx]) != pattern[next] {
+            continue;
+        }
+        let boundary = if idx == 0 || !text[idx - 1].is_alphanumeric() { BOUNDARY } else { 0 };
+        let bonus = match previous {
+            Some(previous) if previous + 1 == idx => boundary.max(run_bonus).max(CONSECUTIVE),
+            Some(previous) => {
+                score -= GAP_START + GAP * (idx - previous - 2) as i64;
+                run_bonus = boundary;
+                boundary
+            }
+            None => {
+                run_bonus = boundary;
+                boundary
+            }
+        };
+        score += MATCH + bonus;
+        previous = Some(idx);
+        next += 1;
+    }
+    Some(score)
+}
@@ -0,0 +1,36 @@
+use crate::line::Line;
+
+/// How many bytes are shown on each row of a dump.
+pub const BYTES_PER_ROW: usize = 16;
+
+/// The bytes a line was read from: as read if they weren't valid UTF-8, otherwise its text.
+pub fn bytes(line: &Line) -> &[u8] {
+    line.raw.as_deref().unwrap_or(line.text.as_bytes())
+}
+
+/// A hex dump of `bytes`, in the style of `hexdump -C`: each row has the offset of its first
+/// byte, the bytes in hex in two groups of eight, then the bytes as ASCII, with `.` for any
+/// that aren't printable.
+pub fn dump(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .chunks(BYTES_PER_ROW)
+        .enumerate()
+        .map(|(row, chunk)| {
+            let mut hex = String::new();
+            for i in 0..BYTES_PER_ROW {
+                if i == BYTES_PER_ROW / 2 {
+                    hex.push(' ');
+                }
+                match chunk.get(i) {
+                    Some(byte) => hex.push_str(&format!("{:02x} ", byte)),
+                    None => hex.push_str("   "),
+                }
+            }
+            let ascii: String = chunk
+                .iter()
+                .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+                .collect();
+            format!("{:08x}  {} |{}|", row * BYTES_PER_ROW, hex, ascii)
+        })
+        .collect()
+}
@@ -0,0 +1,86 @@
+use crate::line::Line;
+
+/// The severity of a log line.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    pub const ALL: [Level; 5] = [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+
+    fn parse(word: &str) -> Option<Level> {
+        match word.to_ascii_uppercase().as_str() {
+            "ERROR" | "ERR" | "FATAL" | "CRITICAL" | "CRIT" | "PANIC" => Some(Level::Error),
+            "WARN" | "WARNING" => Some(Level::Warn),
+            "INFO" => Some(Level::Info),
+            "DEBUG" => Some(Level::Debug),
+            "TRACE" => Some(Level::Trace),
+            _ => None,
+        }
+    }
+}
+
+/// Detect the level of a line from the first word naming one that's either in capitals, as in
+/// `2024-01-01 ERROR ...`, or the value of a `level` field, as in `level=error` or
+/// `"level":"error"`. Lower case words elsewhere are ignored since "error" is as likely to be part
+/// of the message.
+pub fn detect(text: &str) -> Option<Level> {
+    let mut previous = "";
+    for word in text.split(|c: char| !c.is_ascii_alphanumeric()).filter(|w| !w.is_empty()) {
+        let is_field = previous.eq_ignore_ascii_case("level") || previous.eq_ignore_ascii_case("lvl");
+        let is_upper = word.bytes().all(|b| b.is_ascii_uppercase());
+        if is_field || is_upper {
+            if let Some(level) = Level::parse(word) {
+                return Some(level);
+            }
+        }
+        previous = word;
+    }
+    None
+}
+
+/// Live counts of lines at each level, for a quick picture of the health of a stream.
+#[derive(Default)]
+pub struct Histogram {
+    pub counts: [u64; Level::ALL.len()],
+    pub unknown: u64, // Lines with no recognisable level
+    scanned: usize,
+}
+
+impl Histogram {
+    /// Count any lines that have arrived since the last update.
+    pub fn update(&mut self, lines: &[Line]) {
+        for line in &lines[self.scanned.min(lines.len())..] {
+            match detect(&line.text) {
+                Some(level) => self.counts[level as usize] += 1,
+                None => self.unknown += 1,
+            }
+        }
+        self.scanned = lines.len();
+    }
+
+    /// Account for lines dropped from the buffer so new lines aren't skipped.
+    pub fn evicted(&mut self, removed: usize) {
+        self.scanned = self.scanned.saturating_sub(removed);
+    }
+
+    /// Account for deleted lines being put back into the buffer, which were already counted.
+    pub fn restored(&mut self, count: usize) {
+        self.scanned += count;
+    }
+}
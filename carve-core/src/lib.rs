@@ -0,0 +1,29 @@
+//! The engine behind carve, with no terminal attached: the line store and the incremental
+//! search, filtering and folding of panes onto it, along with the analyses carve offers over a
+//! session's lines. The `carve` binary wraps it in a TUI; other tools can embed it the same way.
+
+pub mod anomaly;
+#[cfg(feature = "app")]
+pub mod app;
+pub mod blocks;
+pub mod captures;
+pub mod compare;
+pub mod decode;
+pub mod diff;
+pub mod fields;
+pub mod freq;
+pub mod fuzzy;
+pub mod hex;
+pub mod level;
+pub mod line;
+pub mod objects;
+pub mod pane;
+pub mod query;
+pub mod region;
+pub mod sample;
+pub mod search;
+pub mod sort;
+pub mod table;
+pub mod timestamp;
+pub mod view;
+pub mod words;
@@ -0,0 +1,40 @@
+use std::time::SystemTime;
+
+use chrono::NaiveDateTime;
+
+/// A single line of input along with where it came from.
+pub struct Line {
+    pub text: String,
+    pub source: usize, // Index into App::sources
+    pub id: u64, // Position in arrival order across all sources, stable across eviction
+    pub record: u64, // ID of the first line of the multi-line record this line is part of
+    pub number: u64, // Position within its source, counting from 1
+    pub arrived: SystemTime,
+    pub time: Option<NaiveDateTime>, // When it was written, from its timestamp or the last line from its source with one
+    pub raw: Option<Box<[u8]>>, // The bytes read, when they weren't valid UTF-8
+}
+
+/// The time now, or the epoch in a browser, where `SystemTime::now` panics rather than reading
+/// a clock.
+fn now() -> SystemTime {
+    if cfg!(all(target_arch = "wasm32", target_os = "unknown")) {
+        SystemTime::UNIX_EPOCH
+    } else {
+        SystemTime::now()
+    }
+}
+
+impl Line {
+    pub fn new(text: String, source: usize) -> Self {
+        Self {
+            text,
+            source,
+            id: 0,
+            record: 0,
+            number: 0,
+            arrived: now(),
+            time: None,
+            raw: None,
+        }
+    }
+}
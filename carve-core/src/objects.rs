@@ -0,0 +1,145 @@
+use crate::blocks::Block;
+use crate::line::Line;
+
+/// How far back from the cursor to look for the start of an object, so a stray line doesn't
+/// mean scanning the whole buffer.
+const MAX_LINES_BACK: usize = 1000;
+
+/// Structured items that can be yanked whole with `Y` and a key naming them.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Object {
+    /// The JSON object on the cursor line or, for pretty-printed JSON, the top-level object
+    /// spanning the lines around it.
+    Json,
+    /// The stack trace or other multi-line block the cursor line is part of.
+    Block,
+    /// The `--join-on` record the cursor line is part of.
+    Record,
+}
+
+impl Object {
+    pub fn from_key(key: char) -> Option<Self> {
+        match key {
+            'j' => Some(Object::Json),
+            'b' => Some(Object::Block),
+            'r' => Some(Object::Record),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Object::Json => "JSON object",
+            Object::Block => "block",
+            Object::Record => "record",
+        }
+    }
+
+    /// The text of the object around the line at buffer index `idx`, if there is one.
+    pub fn find(self, lines: &[Line], idx: usize) -> Option<String> {
+        match self {
+            Object::Json => json(lines, idx),
+            Object::Block => {
+                let (start, end) = block(lines, idx);
+                Some(join(&lines[start..=end]))
+            }
+            Object::Record => {
+                let record = lines[idx].record;
+                let start = lines[..idx].partition_point(|line| line.id < record);
+                let rest = lines[start..].iter().filter(|line| line.record == record);
+                Some(rest.map(|line| format!("{}\n", line.text)).collect())
+            }
+        }
+    }
+}
+
+/// The JSON object on a line, or failing that the pretty-printed one spanning it: from the
+/// nearest line above starting with an unindented `{` to the `}` closing it.
+fn json(lines: &[Line], idx: usize) -> Option<String> {
+    let first = idx.saturating_sub(MAX_LINES_BACK);
+    if let Some(start) = (first..=idx).rev().find(|&i| lines[i].text.starts_with('{')) {
+        let mut depth = 0;
+        for (end, line) in lines.iter().enumerate().skip(start) {
+            depth += balance(&line.text);
+            if depth <= 0 {
+                if end >= idx {
+                    return Some(join(&lines[start..=end]));
+                }
+                break;
+            }
+        }
+    }
+    let text = &lines[idx].text;
+    let start = text.find('{')?;
+    let mut depth = 0;
+    for (offset, c) in unquoted(&text[start..]) {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ => continue,
+        }
+        if depth == 0 {
+            return Some(format!("{}\n", &text[start..=start + offset]));
+        }
+    }
+    None
+}
+
+/// The number of `{` less the number of `}` in a line, outside strings.
+fn balance(text: &str) -> i64 {
+    unquoted(text)
+        .map(|(_, c)| match c {
+            '{' => 1,
+            '}' => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// The characters of some JSON outside its strings, with their byte offsets.
+fn unquoted(text: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut in_string = false;
+    let mut escaped = false;
+    text.char_indices().filter(move |&(_, c)| {
+        if escaped {
+            escaped = false;
+        } else if in_string {
+            match c {
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else {
+            return true;
+        }
+        false
+    })
+}
+
+/// The first and last buffer indices of the block `idx` is part of, following the same rules as
+/// folding. A line that isn't part of a longer block is a block of its own.
+fn block(lines: &[Line], idx: usize) -> (usize, usize) {
+    let mut found = (idx, idx);
+    for head in (idx.saturating_sub(MAX_LINES_BACK)..=idx).rev() {
+        let mut block = Block::new(head, &lines[head].text);
+        let end = head + lines[head + 1..].iter().take_while(|line| block.continues(&line.text)).count();
+        if end >= idx {
+            found = (head, end);
+        } else if !is_continuation(&lines[head].text) {
+            // An earlier block ending before the cursor means there are no more to find
+            break;
+        }
+    }
+    found
+}
+
+/// Whether a line could only be the continuation of a block, never the start of one.
+fn is_continuation(text: &str) -> bool {
+    text.starts_with([' ', '\t']) || text.starts_with("Caused by: ")
+}
+
+fn join(lines: &[Line]) -> String {
+    lines.iter().map(|line| format!("{}\n", line.text)).collect()
+}
@@ -0,0 +1,444 @@
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::{DateTime, Local};
+use regex::Regex;
+
+use crate::fuzzy;
+use crate::line::Line;
+use crate::search::Search;
+use crate::sort::Sort;
+use crate::table::Table;
+use crate::view::{self, FilteredView};
+
+/// How a pane orders its rows, when they aren't in a table sorted by a column.
+#[derive(Copy, Clone, Default)]
+pub struct Order {
+    /// Order lines passing fuzzy filters by how well they match.
+    pub rank: bool,
+    /// Order lines by when they were written rather than when they arrived.
+    pub merge: bool,
+}
+
+/// One view onto the buffer with its own filter, search and position, so the screen can be split
+/// to show the same stream in different ways, e.g. errors above and everything below.
+#[derive(Default)]
+pub struct Pane {
+    pub scroll: usize,
+    pub tailing: bool,
+    pub filter: String,
+    pub filters: Vec<String>, // Filters pushed with `+`, each narrowing the lines passing the last
+    pub search_query: String,
+    pub current_match: usize,
+    pub search: Search,
+    pub search_all: bool, // Search lines the filters hide as well as those they pass
+    search_scope: (String, u64), // The filters and view rebuild the search was limited to
+    pub view: FilteredView,
+    pub expanded: BTreeSet<usize>, // Buffer indices of the first line of each unfolded run or block
+    pub hidden_sources: BTreeSet<usize>, // Sources whose lines aren't shown, whatever the filter
+    pub context: view::Context, // Lines to show around those passing the filter
+    pub sort: Option<Sort>, // What the rows are sorted by, rather than arrival
+    pub unique: bool, // Show each distinct line once, with how often it occurs
+    pub cursor: usize, // Position of the current line within the filtered view
+    pub view_height: usize, // Height of the pane as of the last frame
+}
+
+impl Pane {
+    pub fn new() -> Self {
+        Self {
+            tailing: true,
+            ..Default::default()
+        }
+    }
+
+    /// A new pane showing the same lines as this one, from the same position.
+    pub fn split(&self) -> Self {
+        Self {
+            scroll: self.scroll,
+            tailing: self.tailing,
+            filter: self.filter.clone(),
+            filters: self.filters.clone(),
+            hidden_sources: self.hidden_sources.clone(),
+            context: self.context,
+            sort: self.sort.clone(),
+            unique: self.unique,
+            search_all: self.search_all,
+            cursor: self.cursor,
+            view_height: self.view_height,
+            ..Default::default()
+        }
+    }
+
+    /// Catch the view and search up with new lines and any change to the filter, keeping it in
+    /// the table's sort order if there is one, or else the pane's, or otherwise in `order`.
+    pub fn refresh(&mut self, lines: &[Line], duplicates: bool, blocks: bool, records: bool, table: Option<&Table>, order: Order) {
+        self.view.set_context(self.context);
+        let (filters, filter, hidden) = (&self.filters, &self.filter, &self.hidden_sources);
+        let fold = view::FoldOptions {
+            duplicates,
+            blocks,
+            unique: self.unique,
+            expanded: &self.expanded,
+        };
+        // Pushing a filter extends the key, so the view only re-checks the lines that passed before
+        let key: String = filters.iter().flat_map(|filter| [filter.as_str(), "\n"]).chain([filter.as_str()]).collect();
+        // Column filters match whole values, a longer regular expression can match more and a
+        // longer exclusion excludes less, so extending any of them can show lines it hid before
+        let substring = table.is_none() && !filter.starts_with(['!', '/']);
+        let changed = self.view.update(&key, lines, &fold, records, substring, |line| {
+            !hidden.contains(&line.source) && passes(filters, filter, line, table)
+        });
+        let sorted = table.is_some_and(|table| table.sort.is_some());
+        if let Some(table) = table.filter(|_| changed) {
+            table.sort_rows(&mut self.view.rows, |row| &lines[row.idx]);
+            if sorted {
+                self.view.reordered();
+            }
+        }
+        let fuzzy = filters.iter().chain([filter]).any(|filter| filter.starts_with('~'));
+        if let Some(sort) = self.sort.as_ref().filter(|_| changed && !sorted && !records) {
+            sort.apply(&mut self.view.rows, |row| &lines[row.idx]);
+            self.view.reordered();
+        } else if changed && order.rank && fuzzy && !sorted && !records {
+            // Best matches last, nearest the prompt and where tailing keeps the cursor
+            self.view.rows.sort_by_cached_key(|row| score(filters, filter, &lines[row.idx].text));
+            self.view.reordered();
+        } else if changed && order.merge && !sorted && !records {
+            // Lines from sources without timestamps go by when they arrived, and lines written at
+            // the same time stay in arrival order
+            self.view.rows.sort_by_key(|row| {
+                let line = &lines[row.idx];
+                (line.time.unwrap_or_else(|| DateTime::<Local>::from(line.arrived).naive_local()), row.idx)
+            });
+            self.view.reordered();
+        }
+
+        // A change to the filters changes which lines are searched, so search them all again
+        let scope = (key, self.view.resets());
+        if !self.search_all && scope != self.search_scope {
+            self.search.rescan();
+            self.current_match = 0;
+            self.search_scope = scope;
+        }
+        let include = searchable(self.search_all, &self.hidden_sources, &self.filters, &self.filter, table);
+        self.search.update(lines, include);
+    }
+
+    /// Search every line, rather than only those passing the filters, or go back to only those.
+    pub fn set_search_all(&mut self, all: bool) {
+        if all != self.search_all {
+            self.search_all = all;
+            self.search.rescan();
+            self.current_match = 0;
+            self.search_scope = Default::default();
+        }
+    }
+
+    /// Whether a line passes every filter in the stack, as well as the one being edited, and
+    /// isn't from a hidden source.
+    pub fn passes(&self, line: &Line, table: Option<&Table>) -> bool {
+        !self.hidden_sources.contains(&line.source) && passes(&self.filters, &self.filter, line, table)
+    }
+
+    /// Show or hide the lines from a source.
+    pub fn set_source_hidden(&mut self, source: usize, hidden: bool) {
+        let changed = if hidden { self.hidden_sources.insert(source) } else { self.hidden_sources.remove(&source) };
+        if changed {
+            self.view.reset();
+        }
+    }
+
+    /// The parts of `text` matched by the filters in the stack, in order and without overlaps,
+    /// for showing only what matched. Fuzzy, column and excluding filters don't match any part
+    /// in particular.
+    pub fn matched_ranges(&self, text: &str) -> Vec<(usize, usize)> {
+        let mut ranges: Vec<(usize, usize)> = self
+            .filters
+            .iter()
+            .chain([&self.filter])
+            .filter(|filter| !filter.is_empty() && !filter.starts_with(['!', '~']))
+            .flat_map(|filter| match filter.strip_prefix('/') {
+                Some(pattern) => regex(pattern)
+                    .map(|regex| regex.find_iter(text).map(|m| (m.start(), m.end())).collect())
+                    .unwrap_or_default(),
+                None => text.match_indices(filter.as_str()).map(|(start, m)| (start, start + m.len())).collect::<Vec<_>>(),
+            })
+            .filter(|(start, end)| start < end)
+            .collect();
+        ranges.sort_unstable();
+        let mut merged: Vec<(usize, usize)> = Vec::new();
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        merged
+    }
+
+    pub fn is_filtered(&self) -> bool {
+        !self.filter.is_empty() || !self.filters.is_empty()
+    }
+
+    /// The filter stack, oldest first, for showing in the status bar and titles.
+    pub fn describe_filters(&self) -> String {
+        let current = Some(self.filter.as_str()).filter(|filter| !filter.is_empty());
+        self.filters.iter().map(String::as_str).chain(current).collect::<Vec<_>>().join(" › ")
+    }
+
+    /// Keep the current filter and start another on top of it, narrowing the lines shown further.
+    pub fn push_filter(&mut self) {
+        if !self.filter.is_empty() {
+            self.filters.push(std::mem::take(&mut self.filter));
+        }
+    }
+
+    /// Drop the most recent filter, going back to the one before it.
+    pub fn pop_filter(&mut self) {
+        self.filter = self.filters.pop().unwrap_or_default();
+    }
+
+    /// Sort the rows by something in their lines, or put them back in the order they arrived.
+    pub fn set_sort(&mut self, sort: Option<Sort>) {
+        if sort != self.sort {
+            self.sort = sort;
+            self.view.reset();
+        }
+    }
+
+    /// Show each distinct line once, or go back to showing every line.
+    pub fn set_unique(&mut self, unique: bool) {
+        if unique != self.unique {
+            self.unique = unique;
+            self.view.reset();
+        }
+    }
+
+    /// Forget any expanded folds and rebuild the view, after folding has been turned on or off.
+    pub fn refold(&mut self) {
+        self.expanded.clear();
+        self.view.reset();
+    }
+
+    pub fn scroll_to(&mut self, position: usize) {
+        self.scroll = position
+    }
+
+    pub fn scroll_up(&mut self, amount: usize) {
+        self.scroll = self.scroll.saturating_sub(amount);
+    }
+
+    pub fn scroll_down(&mut self, amount: usize, max_scroll: usize) {
+        self.scroll = (self.scroll + amount).min(max_scroll);
+    }
+
+    /// Number of lines that pass the current filter.
+    pub fn visible_len(&self) -> usize {
+        self.view.len()
+    }
+
+    /// Buffer index of the line under the cursor.
+    pub fn cursor_line(&self) -> Option<usize> {
+        self.view.line_index(self.cursor)
+    }
+
+    pub fn cursor_up(&mut self, amount: usize) {
+        self.cursor = self.cursor.saturating_sub(amount);
+    }
+
+    pub fn cursor_down(&mut self, amount: usize) {
+        self.cursor = (self.cursor + amount).min(self.visible_len().saturating_sub(1));
+    }
+
+    /// Fit the cursor and scroll offset to a viewport of the given height. This runs every frame
+    /// so that new lines, filter changes and terminal resizes are all reflected immediately.
+    pub fn fit_viewport(&mut self, view_height: usize) {
+        self.view_height = view_height;
+        let last = self.visible_len().saturating_sub(1);
+        if self.tailing {
+            self.cursor = last;
+        }
+        self.cursor = self.cursor.min(last);
+        // Don't leave empty rows below the last line, e.g. after the terminal grows.
+        self.scroll = self.scroll.min((last + 1).saturating_sub(view_height));
+
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if view_height > 0 && self.cursor >= self.scroll + view_height {
+            self.scroll = self.cursor + 1 - view_height;
+        }
+    }
+
+    /// Expand the folded run or block under the cursor, or fold the expanded one the cursor is in
+    /// back up, moving the cursor to its first line.
+    pub fn toggle_expanded(&mut self) {
+        let Some(row) = self.view.rows.get(self.cursor).copied() else {
+            return;
+        };
+        if row.fold != view::Fold::None {
+            self.expanded.insert(row.idx);
+        } else if self.expanded.remove(&row.group) {
+            while self.view.line_index(self.cursor) != Some(row.group) && self.cursor > 0 {
+                self.cursor -= 1;
+            }
+        } else {
+            return;
+        }
+        self.view.reset();
+    }
+
+    /// Rebuild the view and search after lines were put back into the buffer, which moves the
+    /// lines after them.
+    pub fn restored(&mut self) {
+        self.view.reset();
+        self.search.rescan();
+        self.current_match = 0;
+    }
+
+    /// Remap everything referring to buffer indices after `removed` lines were evicted from the
+    /// buffer. `remap` gives each surviving line's new index. Returns whether the view was reset
+    /// and must be rebuilt.
+    pub fn evicted(&mut self, removed: usize, remap: impl Fn(usize) -> Option<usize>) -> bool {
+        let current_match = self.current_match;
+        let mut matches_before_current = 0;
+        let matches = std::mem::take(self.search.matches_mut())
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, (idx, start, end))| {
+                let new_idx = remap(idx);
+                if new_idx.is_none() && i < current_match {
+                    matches_before_current += 1;
+                }
+                new_idx.map(|idx| (idx, start, end))
+            })
+            .collect();
+        *self.search.matches_mut() = matches;
+        self.search.evicted(removed);
+        self.current_match = current_match
+            .saturating_sub(matches_before_current)
+            .min(self.search.matches().len().saturating_sub(1));
+        let resets = self.view.resets();
+        let visible_removed = self.view.evicted(removed, &remap);
+        self.expanded = self.expanded.iter().filter_map(|idx| remap(*idx)).collect();
+
+        self.scroll = self.scroll.saturating_sub(visible_removed);
+        self.cursor = self.cursor.saturating_sub(visible_removed);
+        self.view.resets() != resets
+    }
+
+    pub fn update_search(&mut self, lines: &[Line], table: Option<&Table>) {
+        let include = searchable(self.search_all, &self.hidden_sources, &self.filters, &self.filter, table);
+        self.search.set_query(&self.search_query, lines, include);
+
+        // TODO: accept a current position and return the first search result after it so we can
+        // scroll directly to it.
+    }
+
+    /// Move to the current match, if there is one. Returns whether there was.
+    ///
+    /// Matches are found by buffer index, so this moves to the row showing the match's line or,
+    /// if it's folded away or the filters hide it, the nearest row before it.
+    pub fn jump_to_match(&mut self) -> bool {
+        let Some(&(line_idx, _, _)) = self.search.matches().get(self.current_match) else {
+            return false;
+        };
+        let rows = &self.view.rows;
+        let row = rows
+            .iter()
+            .position(|row| row.idx == line_idx)
+            .or_else(|| rows.iter().rposition(|row| row.idx < line_idx))
+            .unwrap_or(0);
+        self.scroll = row;
+        self.cursor = row;
+        self.tailing = false;
+        true
+    }
+
+    pub fn next_match(&mut self) -> bool {
+        if self.search.matches().is_empty() {
+            return false;
+        }
+        self.current_match = (self.current_match + 1) % self.search.matches().len();
+        self.jump_to_match()
+    }
+
+    pub fn prev_match(&mut self) -> bool {
+        if self.search.matches().is_empty() {
+            return false;
+        }
+        self.current_match = self.current_match.checked_sub(1).unwrap_or(self.search.matches().len() - 1);
+        self.jump_to_match()
+    }
+}
+
+fn passes(filters: &[String], filter: &str, line: &Line, table: Option<&Table>) -> bool {
+    filters.iter().all(|filter| passes_filter(filter, line, table)) && passes_filter(filter, line, table)
+}
+
+/// Which lines a search looks in: those passing the filters from sources that aren't hidden, or
+/// every line if `all`.
+fn searchable<'a>(
+    all: bool,
+    hidden: &'a BTreeSet<usize>,
+    filters: &'a [String],
+    filter: &'a str,
+    table: Option<&'a Table>,
+) -> impl Fn(&Line) -> bool + 'a {
+    move |line| all || (!hidden.contains(&line.source) && passes(filters, filter, line, table))
+}
+
+/// How well a line matches the fuzzy filters in a stack, as the sum of their scores.
+fn score(filters: &[String], filter: &str, text: &str) -> i64 {
+    filters
+        .iter()
+        .map(String::as_str)
+        .chain([filter])
+        .filter_map(|filter| filter.strip_prefix('~'))
+        .filter_map(|pattern| fuzzy::score(pattern, text))
+        .sum()
+}
+
+/// Whether a line passes a filter: by containing it, by fuzzily matching a filter starting with
+/// `~`, by matching the regular expression after a `/` or, in table mode, by having the value a
+/// `column=value` filter gives in that column. A filter starting with `!` passes the lines the
+/// rest of it doesn't.
+pub fn passes_filter(filter: &str, line: &Line, table: Option<&Table>) -> bool {
+    if let Some(excluded) = filter.strip_prefix('!').filter(|excluded| !excluded.is_empty()) {
+        return !passes_filter(excluded, line, table);
+    }
+    match table.and_then(|table| table.matches_column(filter, &line.text)) {
+        Some(matches) => matches,
+        None => match filter.strip_prefix('~') {
+            Some(pattern) => fuzzy::score(pattern, &line.text).is_some(),
+            None => match filter.strip_prefix('/') {
+                Some(pattern) => regex(pattern).is_some_and(|regex| regex.is_match(&line.text)),
+                None => filter.is_empty() || line.text.contains(filter),
+            },
+        },
+    }
+}
+
+/// Check a filter written out ahead of time, such as in a config file, reporting a regular
+/// expression that isn't valid rather than letting it quietly match nothing.
+pub fn check_filter(filter: &str) -> Result<(), String> {
+    match filter.trim_start_matches('!').strip_prefix('/') {
+        Some(pattern) => Regex::new(pattern).map(|_| ()).map_err(|e| e.to_string()),
+        None => Ok(()),
+    }
+}
+
+thread_local! {
+    static REGEXES: RefCell<HashMap<String, Option<Regex>>> = RefCell::new(HashMap::new());
+}
+
+/// A regular expression filter compiled once rather than for every line it's checked against,
+/// or nothing if it isn't valid, such as part way through typing it.
+fn regex(pattern: &str) -> Option<Regex> {
+    REGEXES.with_borrow_mut(|regexes| {
+        // Every prefix typed is compiled on the way to the filter wanted, so don't keep them all
+        if regexes.len() > 64 {
+            regexes.clear();
+        }
+        regexes.entry(pattern.to_string()).or_insert_with(|| Regex::new(pattern).ok()).clone()
+    })
+}
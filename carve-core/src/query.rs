@@ -0,0 +1,835 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// A query over the fields of structured log lines, in a small subset of SQL:
+///
+/// ```text
+/// select ITEM, ... [where CONDITION] [group by FIELD, ...] [order by COLUMN [asc|desc], ...] [limit N]
+/// ```
+///
+/// An item is a field, `*` for every field, or `count(*)`, `count(FIELD)`, `sum(FIELD)`,
+/// `avg(FIELD)`, `min(FIELD)` or `max(FIELD)`, optionally named with `as NAME`. Conditions
+/// compare fields with `=`, `!=`, `<`, `<=`, `>`, `>=` or `like`, combined with `and`, `or`,
+/// `not` and parentheses. Values are compared as numbers when both sides are numbers.
+///
+/// There's no `from`: a query runs over whatever lines it's given, taking the fields of each
+/// from a JSON object or from logfmt `key=value` pairs, and leaving out lines with neither. Nested JSON objects give fields named
+/// with dots, like `http.status`.
+pub struct Query {
+    items: Vec<Item>,
+    condition: Option<Condition>,
+    group_by: Vec<String>,
+    order_by: Vec<(usize, bool)>, // Output column and whether it's descending
+    limit: Option<usize>,
+}
+
+/// The table a query produces.
+#[derive(Default)]
+pub struct Results {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    pub selected: usize,
+}
+
+impl Results {
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1).min(self.rows.len().saturating_sub(1));
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
+
+/// A query kept up to date with the lines it runs over, for showing while they arrive.
+pub struct LiveQuery {
+    pub sql: String,
+    query: Query,
+    pub results: Results,
+    view: String, // Identifies the lines the results are for
+    lines: usize, // How many lines the results are for
+}
+
+impl LiveQuery {
+    pub fn new(sql: &str) -> Result<Self, String> {
+        Ok(Self {
+            sql: sql.to_string(),
+            query: Query::parse(sql)?,
+            results: Results::default(),
+            view: String::new(),
+            lines: usize::MAX,
+        })
+    }
+
+    /// Run the query again if the lines have changed since it last ran, keeping the selection.
+    /// A different `view` means different lines, even if there are as many.
+    pub fn update<'a>(&mut self, view: &str, lines: impl ExactSizeIterator<Item = &'a str>) {
+        if view == self.view && lines.len() == self.lines {
+            return;
+        }
+        self.view = view.to_string();
+        self.lines = lines.len();
+        let selected = self.results.selected;
+        self.results = self.query.run(lines);
+        self.results.selected = selected.min(self.results.rows.len().saturating_sub(1));
+    }
+}
+
+struct Item {
+    expr: Expr,
+    name: String,
+}
+
+enum Expr {
+    All,
+    Field(String),
+    Aggregate(Aggregate, Option<String>), // No field for `count(*)`
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Aggregate {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+enum Condition {
+    Compare(String, Op, String),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Like,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Word(String), // A keyword or field name
+    Quoted(String), // A string literal
+    Symbol(&'static str),
+}
+
+impl Query {
+    pub fn parse(sql: &str) -> Result<Self, String> {
+        let mut parser = Parser { tokens: tokenize(sql)?, pos: 0 };
+        parser.keyword("select")?;
+        let mut items = vec![parser.item()?];
+        while parser.symbol(",") {
+            items.push(parser.item()?);
+        }
+        let condition = if parser.try_keyword("where") { Some(parser.condition()?) } else { None };
+        let mut group_by = Vec::new();
+        if parser.try_keyword("group") {
+            parser.keyword("by")?;
+            group_by.push(parser.word()?);
+            while parser.symbol(",") {
+                group_by.push(parser.word()?);
+            }
+        }
+        let mut order_by = Vec::new();
+        if parser.try_keyword("order") {
+            parser.keyword("by")?;
+            loop {
+                let column = parser.column(&items)?;
+                let descending = parser.try_keyword("desc");
+                if !descending {
+                    parser.try_keyword("asc");
+                }
+                order_by.push((column, descending));
+                if !parser.symbol(",") {
+                    break;
+                }
+            }
+        }
+        let limit = if parser.try_keyword("limit") {
+            let limit = parser.word()?;
+            Some(limit.parse().map_err(|_| format!("limit must be a number, not {}", limit))?)
+        } else {
+            None
+        };
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(format!("unexpected {}", describe(token)));
+        }
+        let aggregated = items.iter().any(|item| matches!(item.expr, Expr::Aggregate(..)));
+        if (aggregated || !group_by.is_empty()) && items.iter().any(|item| matches!(item.expr, Expr::All)) {
+            return Err("* can't be selected with group by or an aggregate".to_string());
+        }
+        Ok(Self {
+            items,
+            condition,
+            group_by,
+            order_by,
+            limit,
+        })
+    }
+
+    /// Run the query over the text of some lines.
+    pub fn run<'a>(&self, lines: impl IntoIterator<Item = &'a str>) -> Results {
+        // Lines with no fields, such as plain messages mixed in with structured ones, are left out
+        let records = lines.into_iter().map(fields).filter(|record| {
+            !record.is_empty() && self.condition.as_ref().is_none_or(|condition| condition.holds(record))
+        });
+        let aggregated = !self.group_by.is_empty() || self.items.iter().any(|item| matches!(item.expr, Expr::Aggregate(..)));
+        let (columns, mut rows) = if aggregated { self.aggregate(records) } else { self.project(records) };
+        if self.order_by.is_empty() && !self.group_by.is_empty() {
+            // Groups come out in the order of their values unless asked otherwise
+            rows.sort_by(|a, b| a.iter().zip(b).map(|(a, b)| compare(a, b)).find(|o| o.is_ne()).unwrap_or(Ordering::Equal));
+        }
+        rows.sort_by(|a, b| {
+            self.order_by
+                .iter()
+                .map(|&(column, descending)| {
+                    let ordering = compare(&a[column], &b[column]);
+                    if descending { ordering.reverse() } else { ordering }
+                })
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+        if let Some(limit) = self.limit {
+            rows.truncate(limit);
+        }
+        Results { columns, rows, selected: 0 }
+    }
+
+    /// A row for each record, with a column for each field selected, and for every field seen
+    /// where `*` is selected.
+    fn project(&self, records: impl Iterator<Item = Vec<(String, String)>>) -> (Vec<String>, Vec<Vec<String>>) {
+        let records: Vec<Vec<(String, String)>> = records.collect();
+        // The heading of each column and the field it shows
+        let mut columns: Vec<(String, String)> = Vec::new();
+        for item in &self.items {
+            match &item.expr {
+                Expr::All => {
+                    for record in &records {
+                        for (name, _) in record {
+                            if !columns.iter().any(|(_, field)| field == name) {
+                                columns.push((name.clone(), name.clone()));
+                            }
+                        }
+                    }
+                }
+                Expr::Field(field) => columns.push((item.name.clone(), field.clone())),
+                Expr::Aggregate(..) => {}
+            }
+        }
+        let rows = records
+            .iter()
+            .map(|record| columns.iter().map(|(_, field)| lookup(record, field).unwrap_or_default().to_string()).collect())
+            .collect();
+        (columns.into_iter().map(|(heading, _)| heading).collect(), rows)
+    }
+
+    /// A row for each group of records, or a single row without `group by`.
+    fn aggregate(&self, records: impl Iterator<Item = Vec<(String, String)>>) -> (Vec<String>, Vec<Vec<String>>) {
+        let mut groups: Vec<Vec<Accumulator>> = Vec::new();
+        let mut index: HashMap<Vec<String>, usize> = HashMap::new();
+        for record in records {
+            let key: Vec<String> = self.group_by.iter().map(|field| lookup(&record, field).unwrap_or_default().to_string()).collect();
+            let group = *index.entry(key).or_insert_with(|| {
+                groups.push(self.items.iter().map(|_| Accumulator::default()).collect());
+                groups.len() - 1
+            });
+            for (item, accumulator) in self.items.iter().zip(&mut groups[group]) {
+                accumulator.add(&item.expr, &record);
+            }
+        }
+        if groups.is_empty() && self.group_by.is_empty() {
+            // Counting nothing still gives a count
+            groups.push(self.items.iter().map(|_| Accumulator::default()).collect());
+        }
+        let columns = self.items.iter().map(|item| item.name.clone()).collect();
+        let rows = groups
+            .into_iter()
+            .map(|group| self.items.iter().zip(group).map(|(item, accumulator)| accumulator.value(&item.expr)).collect())
+            .collect();
+        (columns, rows)
+    }
+}
+
+/// The running value of an item over a group of records.
+#[derive(Default)]
+struct Accumulator {
+    count: u64,
+    sum: f64,
+    extreme: Option<String>, // The least or greatest value so far, for `min` and `max`
+    first: Option<String>, // The value of a plain field in the first record
+}
+
+impl Accumulator {
+    fn add(&mut self, expr: &Expr, record: &[(String, String)]) {
+        match expr {
+            Expr::All => {}
+            Expr::Field(field) => {
+                if self.first.is_none() {
+                    self.first = Some(lookup(record, field).unwrap_or_default().to_string());
+                }
+            }
+            Expr::Aggregate(Aggregate::Count, None) => self.count += 1,
+            Expr::Aggregate(aggregate, Some(field)) => {
+                let Some(value) = lookup(record, field) else {
+                    return;
+                };
+                match aggregate {
+                    Aggregate::Count => self.count += 1,
+                    Aggregate::Sum | Aggregate::Avg => {
+                        if let Ok(number) = value.parse::<f64>() {
+                            self.count += 1;
+                            self.sum += number;
+                        }
+                    }
+                    Aggregate::Min | Aggregate::Max => {
+                        let wanted = if *aggregate == Aggregate::Min { Ordering::Less } else { Ordering::Greater };
+                        if self.extreme.as_deref().is_none_or(|extreme| compare(value, extreme) == wanted) {
+                            self.extreme = Some(value.to_string());
+                        }
+                    }
+                }
+            }
+            Expr::Aggregate(_, None) => {}
+        }
+    }
+
+    fn value(self, expr: &Expr) -> String {
+        match expr {
+            Expr::All => String::new(),
+            Expr::Field(_) => self.first.unwrap_or_default(),
+            Expr::Aggregate(Aggregate::Count, _) => self.count.to_string(),
+            Expr::Aggregate(Aggregate::Sum, _) => number(self.sum),
+            Expr::Aggregate(Aggregate::Avg, _) if self.count == 0 => String::new(),
+            Expr::Aggregate(Aggregate::Avg, _) => number(self.sum / self.count as f64),
+            Expr::Aggregate(Aggregate::Min | Aggregate::Max, _) => self.extreme.unwrap_or_default(),
+        }
+    }
+}
+
+impl Condition {
+    fn holds(&self, record: &[(String, String)]) -> bool {
+        match self {
+            Condition::Compare(field, op, expected) => {
+                let Some(value) = lookup(record, field) else {
+                    return false;
+                };
+                match op {
+                    Op::Eq => compare(value, expected).is_eq(),
+                    Op::Ne => compare(value, expected).is_ne(),
+                    Op::Lt => compare(value, expected).is_lt(),
+                    Op::Le => compare(value, expected).is_le(),
+                    Op::Gt => compare(value, expected).is_gt(),
+                    Op::Ge => compare(value, expected).is_ge(),
+                    Op::Like => like(value, expected),
+                }
+            }
+            Condition::And(a, b) => a.holds(record) && b.holds(record),
+            Condition::Or(a, b) => a.holds(record) || b.holds(record),
+            Condition::Not(a) => !a.holds(record),
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.tokens.get(self.pos), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn try_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek_keyword(keyword);
+        self.pos += found as usize;
+        found
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<(), String> {
+        if self.try_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(format!("expected {}", keyword))
+        }
+    }
+
+    fn symbol(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.tokens.get(self.pos), Some(Token::Symbol(s)) if *s == symbol);
+        self.pos += found as usize;
+        found
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        if self.symbol(symbol) {
+            Ok(())
+        } else {
+            Err(format!("expected {}", symbol))
+        }
+    }
+
+    fn word(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Word(word)) => Ok(word),
+            Some(token) => Err(format!("unexpected {}", describe(&token))),
+            None => Err("unexpected end of query".to_string()),
+        }
+    }
+
+    fn item(&mut self) -> Result<Item, String> {
+        if self.symbol("*") {
+            return Ok(Item {
+                expr: Expr::All,
+                name: "*".to_string(),
+            });
+        }
+        let word = self.word()?;
+        let aggregate = match word.to_ascii_lowercase().as_str() {
+            "count" => Some(Aggregate::Count),
+            "sum" => Some(Aggregate::Sum),
+            "avg" => Some(Aggregate::Avg),
+            "min" => Some(Aggregate::Min),
+            "max" => Some(Aggregate::Max),
+            _ => None,
+        };
+        let (expr, name) = match aggregate.filter(|_| self.symbol("(")) {
+            Some(aggregate) => {
+                let field = if aggregate == Aggregate::Count && self.symbol("*") { None } else { Some(self.word()?) };
+                self.expect(")")?;
+                let name = format!("{}({})", word.to_ascii_lowercase(), field.as_deref().unwrap_or("*"));
+                (Expr::Aggregate(aggregate, field), name)
+            }
+            None => (Expr::Field(word.clone()), word),
+        };
+        let name = if self.try_keyword("as") { self.word()? } else { name };
+        Ok(Item { expr, name })
+    }
+
+    /// An output column, by its number counting from 1, or its name.
+    fn column(&mut self, items: &[Item]) -> Result<usize, String> {
+        let mut name = self.word()?;
+        if self.symbol("(") {
+            // An aggregate written out again, like `count(*)`
+            let field = if self.symbol("*") { "*".to_string() } else { self.word()? };
+            self.expect(")")?;
+            name = format!("{}({})", name.to_ascii_lowercase(), field);
+        }
+        if let Ok(number) = name.parse::<usize>() {
+            return (1..=items.len())
+                .contains(&number)
+                .then(|| number - 1)
+                .ok_or_else(|| format!("there's no column {}", number));
+        }
+        items
+            .iter()
+            .position(|item| item.name == name)
+            .ok_or_else(|| format!("order by {} isn't a selected column", name))
+    }
+
+    fn condition(&mut self) -> Result<Condition, String> {
+        let mut condition = self.conjunction()?;
+        while self.try_keyword("or") {
+            condition = Condition::Or(Box::new(condition), Box::new(self.conjunction()?));
+        }
+        Ok(condition)
+    }
+
+    fn conjunction(&mut self) -> Result<Condition, String> {
+        let mut condition = self.negation()?;
+        while self.try_keyword("and") {
+            condition = Condition::And(Box::new(condition), Box::new(self.negation()?));
+        }
+        Ok(condition)
+    }
+
+    fn negation(&mut self) -> Result<Condition, String> {
+        if self.try_keyword("not") {
+            return Ok(Condition::Not(Box::new(self.negation()?)));
+        }
+        if self.symbol("(") {
+            let condition = self.condition()?;
+            self.expect(")")?;
+            return Ok(condition);
+        }
+        let field = self.word()?;
+        let op = match self.next() {
+            Some(Token::Symbol("=")) => Op::Eq,
+            Some(Token::Symbol("!=" | "<>")) => Op::Ne,
+            Some(Token::Symbol("<")) => Op::Lt,
+            Some(Token::Symbol("<=")) => Op::Le,
+            Some(Token::Symbol(">")) => Op::Gt,
+            Some(Token::Symbol(">=")) => Op::Ge,
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case("like") => Op::Like,
+            _ => return Err(format!("expected a comparison after {}", field)),
+        };
+        // A bare word is taken as a value, so quoting is only needed for spaces and symbols
+        let value = match self.next() {
+            Some(Token::Word(value) | Token::Quoted(value)) => value,
+            _ => return Err(format!("expected a value to compare {} with", field)),
+        };
+        Ok(Condition::Compare(field, op, value))
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Word(word) => format!("'{}'", word),
+        Token::Quoted(value) => format!("'{}'", value),
+        Token::Symbol(symbol) => format!("'{}'", symbol),
+    }
+}
+
+/// Split a query into words, quoted strings and symbols. Strings are quoted with `'`, doubling
+/// it for a literal quote, and field names with `"` or backticks.
+fn tokenize(sql: &str) -> Result<Vec<Token>, String> {
+    const SYMBOLS: [&str; 11] = ["!=", "<>", "<=", ">=", "=", "<", ">", "(", ")", ",", "*"];
+    let mut tokens = Vec::new();
+    let mut rest = sql.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        } else if matches!(c, '\'' | '"' | '`') {
+            let mut value = String::new();
+            let mut chars = rest[1..].char_indices();
+            let end = loop {
+                match chars.next() {
+                    Some((i, q)) if q == c && rest[1 + i + 1..].starts_with(c) => {
+                        value.push(c);
+                        chars.next();
+                    }
+                    Some((i, q)) if q == c => break 1 + i + 1,
+                    Some((_, other)) => value.push(other),
+                    None => return Err(format!("unclosed {}", c)),
+                }
+            };
+            tokens.push(if c == '\'' { Token::Quoted(value) } else { Token::Word(value) });
+            rest = &rest[end..];
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || "!=<>(),*'\"`".contains(c))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                return Err(format!("unexpected '{}'", c));
+            }
+            tokens.push(Token::Word(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+fn lookup<'a>(record: &'a [(String, String)], field: &str) -> Option<&'a str> {
+    record.iter().find(|(name, _)| name == field).map(|(_, value)| value.as_str())
+}
+
+/// Compare values as numbers if they both are, or else as text.
+fn compare(a: &str, b: &str) -> Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        _ => a.cmp(b),
+    }
+}
+
+/// Whether `value` matches a `like` pattern, where `%` stands for any run of characters and
+/// `_` for any one.
+fn like(value: &str, pattern: &str) -> bool {
+    fn matches(value: &[char], pattern: &[char]) -> bool {
+        match pattern.split_first() {
+            None => value.is_empty(),
+            Some(('%', rest)) => (0..=value.len()).any(|i| matches(&value[i..], rest)),
+            Some((p, rest)) => value.split_first().is_some_and(|(c, value)| (*p == '_' || p == c) && matches(value, rest)),
+        }
+    }
+    let value: Vec<char> = value.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    matches(&value, &pattern)
+}
+
+/// A number as short as it can be written, as a whole number if it is one.
+fn number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        let text = format!("{:.3}", value);
+        text.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+}
+
+/// The fields of a line: the members of a JSON object, with nested objects flattened into
+/// dotted names, or else its logfmt `key=value` pairs. JSON strings are unescaped, `null`
+/// members are left out and arrays are kept as written.
+pub fn fields(text: &str) -> Vec<(String, String)> {
+    let trimmed = text.trim_start();
+    if trimmed.starts_with('{') {
+        let mut json = Json { text: trimmed, pos: 0 };
+        let mut fields = Vec::new();
+        if json.object("", &mut fields).is_some() {
+            return fields;
+        }
+    }
+    logfmt(text)
+}
+
+/// `key=value` pairs, where a value may be in double quotes to hold spaces.
+fn logfmt(text: &str) -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+    let mut rest = text;
+    while let Some(eq) = rest.find('=') {
+        let key = rest[..eq].rsplit(char::is_whitespace).next().unwrap_or_default();
+        let after = &rest[eq + 1..];
+        let (value, next) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let mut value = String::new();
+                let mut chars = quoted.char_indices();
+                let mut end = quoted.len();
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' => value.extend(chars.next().map(|(_, c)| c)),
+                        '"' => {
+                            end = i + 1;
+                            break;
+                        }
+                        c => value.push(c),
+                    }
+                }
+                (value, &quoted[end..])
+            }
+            None => {
+                let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                (after[..end].to_string(), &after[end..])
+            }
+        };
+        if !key.is_empty() {
+            fields.push((key.to_string(), value));
+        }
+        rest = next;
+    }
+    fields
+}
+
+/// Just enough of a JSON parser to pull the members out of an object.
+struct Json<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Json<'_> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, c: char) -> Option<()> {
+        self.skip_whitespace();
+        self.text[self.pos..].starts_with(c).then(|| self.pos += 1)
+    }
+
+    /// Add the members of the object starting here to `fields`, their names after `prefix`.
+    fn object(&mut self, prefix: &str, fields: &mut Vec<(String, String)>) -> Option<()> {
+        self.eat('{')?;
+        if self.eat('}').is_some() {
+            return Some(());
+        }
+        loop {
+            self.skip_whitespace();
+            let name = format!("{}{}", prefix, self.string()?);
+            self.eat(':')?;
+            self.skip_whitespace();
+            match self.text[self.pos..].chars().next()? {
+                '{' => self.object(&format!("{}.", name), fields)?,
+                '"' => {
+                    let value = self.string()?;
+                    fields.push((name, value));
+                }
+                _ => {
+                    let start = self.pos;
+                    self.skip_value()?;
+                    let value = &self.text[start..self.pos];
+                    if value != "null" {
+                        fields.push((name, value.to_string()));
+                    }
+                }
+            }
+            if self.eat(',').is_none() {
+                return self.eat('}');
+            }
+        }
+    }
+
+    /// The string starting here, unescaped.
+    fn string(&mut self) -> Option<String> {
+        let rest = self.text[self.pos..].strip_prefix('"')?;
+        let mut value = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += 1 + i + 1;
+                    return Some(value);
+                }
+                '\\' => match chars.next()?.1 {
+                    'n' => value.push('\n'),
+                    't' => value.push('\t'),
+                    'r' => value.push('\r'),
+                    'b' => value.push('\u{8}'),
+                    'f' => value.push('\u{c}'),
+                    'u' => {
+                        let hex: String = (0..4).filter_map(|_| chars.next().map(|(_, c)| c)).collect();
+                        value.push(u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32).unwrap_or('\u{fffd}'));
+                    }
+                    c => value.push(c),
+                },
+                c => value.push(c),
+            }
+        }
+        None
+    }
+
+    /// Move past a number, literal or array, keeping track of nesting and strings.
+    fn skip_value(&mut self) -> Option<()> {
+        let mut depth = 0usize;
+        while let Some(c) = self.text[self.pos..].chars().next() {
+            match c {
+                '"' => {
+                    self.string()?;
+                    continue;
+                }
+                '[' | '{' => depth += 1,
+                ']' | '}' if depth > 0 => depth -= 1,
+                ',' | '}' | ']' if depth == 0 => return Some(()),
+                c if c.is_whitespace() && depth == 0 => return Some(()),
+                _ => {}
+            }
+            self.pos += c.len_utf8();
+        }
+        (depth == 0).then_some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(sql: &str, lines: &[&str]) -> Vec<Vec<String>> {
+        Query::parse(sql).unwrap().run(lines.iter().copied()).rows
+    }
+
+    fn error(sql: &str) -> String {
+        Query::parse(sql).err().unwrap()
+    }
+
+    #[test]
+    fn parse_errors_say_what_went_wrong() {
+        assert_eq!(error("level"), "expected select");
+        assert_eq!(error("select"), "unexpected end of query");
+        assert_eq!(error("select level from logs"), "unexpected 'from'");
+        assert_eq!(error("select level where status"), "expected a comparison after status");
+        assert_eq!(error("select level where status ="), "expected a value to compare status with");
+        assert_eq!(error("select level where (status = 5"), "expected )");
+        assert_eq!(error("select count(status"), "expected )");
+        assert_eq!(error("select level group level"), "expected by");
+        assert_eq!(error("select level order by status"), "order by status isn't a selected column");
+        assert_eq!(error("select level order by 2"), "there's no column 2");
+        assert_eq!(error("select level limit ten"), "limit must be a number, not ten");
+        assert_eq!(error("select *, count(*)"), "* can't be selected with group by or an aggregate");
+        assert_eq!(error("select level where msg = 'unclosed"), "unclosed '");
+    }
+
+    #[test]
+    fn like_matches_any_run_with_percent_and_any_one_with_underscore() {
+        assert!(like("GET /users/42", "GET %"));
+        assert!(like("GET /users/42", "%/users/%"));
+        assert!(like("anything", "%"));
+        assert!(like("", "%"));
+        assert!(like("a1c", "a_c"));
+        assert!(!like("ac", "a_c"));
+        assert!(!like("a12c", "a_c"));
+        assert!(like("a12c", "a%c"));
+        assert!(!like("POST /users", "GET%"));
+        // Without wildcards it's an exact match
+        assert!(like("200", "200"));
+        assert!(!like("2000", "200"));
+    }
+
+    #[test]
+    fn like_in_a_condition() {
+        let lines = ["path=/api/users", "path=/api/orders", "path=/health", "path=/apix"];
+        assert_eq!(run("select path where path like '/api/%'", &lines), [["/api/users"], ["/api/orders"]]);
+        assert_eq!(run("select path where path like '/api_'", &lines), [["/apix"]]);
+        assert_eq!(run("select path where not path like '%/api%'", &lines), [["/health"]]);
+    }
+
+    #[test]
+    fn groups_come_out_in_the_order_of_their_values() {
+        let lines = ["level=warn", "level=error", "level=info", "level=error", "level=warn", "level=warn"];
+        assert_eq!(run("select level, count(*) group by level", &lines), [["error", "2"], ["info", "1"], ["warn", "3"]]);
+        assert_eq!(run("select level, count(*) group by level order by 2 desc", &lines)[0], ["warn", "3"]);
+    }
+
+    #[test]
+    fn numeric_groups_are_ordered_as_numbers() {
+        let lines = ["status=500", "status=1000", "status=200", "status=404", "status=200"];
+        let statuses: Vec<String> = run("select status group by status", &lines).into_iter().flatten().collect();
+        assert_eq!(statuses, ["200", "404", "500", "1000"]);
+    }
+
+    #[test]
+    fn avg_of_no_numbers_is_empty() {
+        let lines = ["ms=fast", "ms=slow", "other=1"];
+        assert_eq!(run("select avg(ms), count(ms)", &lines), [["", "2"]]);
+        assert_eq!(run("select avg(ms), count(*)", &[]), [["", "0"]]);
+        // Values that aren't numbers are left out of the average, not counted as zero
+        assert_eq!(run("select avg(ms)", &["ms=10", "ms=n/a", "ms=15"]), [["12.5"]]);
+    }
+
+    #[test]
+    fn nested_json_objects_give_dotted_field_names() {
+        let line = r#"{"msg":"hi","http":{"status":500,"request":{"path":"/x","tags":[1,2]}},"user":null}"#;
+        assert_eq!(
+            fields(line),
+            [
+                ("msg".to_string(), "hi".to_string()),
+                ("http.status".to_string(), "500".to_string()),
+                ("http.request.path".to_string(), "/x".to_string()),
+                ("http.request.tags".to_string(), "[1,2]".to_string()),
+            ]
+        );
+        assert_eq!(run("select http.request.path where http.status >= 500", &[line]), [["/x"]]);
+    }
+
+    #[test]
+    fn quoted_logfmt_values_hold_spaces_and_escapes() {
+        let line = r#"level=error msg="disk full on /var" detail="said \"no\"" code=28"#;
+        assert_eq!(
+            fields(line),
+            [
+                ("level".to_string(), "error".to_string()),
+                ("msg".to_string(), "disk full on /var".to_string()),
+                ("detail".to_string(), r#"said "no""#.to_string()),
+                ("code".to_string(), "28".to_string()),
+            ]
+        );
+        assert_eq!(run("select code where msg = 'disk full on /var'", &[line]), [["28"]]);
+    }
+
+    #[test]
+    fn lines_without_fields_are_left_out() {
+        let lines = ["plain message", "level=info", r#"{"level":"warn"}"#];
+        assert_eq!(run("select level", &lines), [["info"], ["warn"]]);
+    }
+}
@@ -0,0 +1,143 @@
+use std::ops::Range;
+use std::time::SystemTime;
+
+use crate::line::Line;
+
+/// A region of the buffer to operate on, shared by everything that works on "some of the lines"
+/// (copying context, exporting, stats...) so they all agree on what a region contains.
+///
+/// Regions refer to lines by ID rather than buffer index so they stay valid as lines are evicted.
+/// IDs only ever increase along the buffer, as do arrival times, so resolving a region is a
+/// binary search rather than a scan.
+#[derive(Copy, Clone, Debug)]
+pub enum Region {
+    /// Every line in the buffer.
+    All,
+    /// A line and up to this many lines either side of it.
+    Around(u64, usize),
+    /// The lines from one marked line to another, given in either order.
+    Between(u64, u64),
+    /// The lines that arrived from the first time up to, but not including, the second.
+    Time(SystemTime, SystemTime),
+    /// The lines spanned by a selection in a pane, from the first line selected to the last.
+    /// Of these, a filtered pane only shows some, and only those are selected.
+    Selection(u64, u64),
+}
+
+impl Region {
+    /// The buffer indices of the lines in the region. Lines that have since been evicted are
+    /// simply missing from the result.
+    pub fn indices(&self, lines: &[Line]) -> Range<usize> {
+        match *self {
+            Region::All => 0..lines.len(),
+            Region::Around(id, context) => {
+                let idx = lines.partition_point(|line| line.id < id);
+                if lines.get(idx).is_none_or(|line| line.id != id) {
+                    return idx..idx;
+                }
+                idx.saturating_sub(context)..(idx + context + 1).min(lines.len())
+            }
+            Region::Between(from, to) | Region::Selection(from, to) => {
+                let (from, to) = (from.min(to), from.max(to));
+                lines.partition_point(|line| line.id < from)..lines.partition_point(|line| line.id <= to)
+            }
+            Region::Time(from, to) => {
+                let start = lines.partition_point(|line| line.arrived < from);
+                start..lines.partition_point(|line| line.arrived < to).max(start)
+            }
+        }
+    }
+
+    /// The lines in the region.
+    pub fn lines<'a>(&self, lines: &'a [Line]) -> &'a [Line] {
+        &lines[self.indices(lines)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// Lines with the given IDs, arriving a second apart from the epoch.
+    fn buffer(ids: &[u64]) -> Vec<Line> {
+        ids.iter()
+            .enumerate()
+            .map(|(idx, &id)| {
+                let mut line = Line::new(format!("line {id}"), 0);
+                line.id = id;
+                line.arrived = SystemTime::UNIX_EPOCH + Duration::from_secs(idx as u64);
+                line
+            })
+            .collect()
+    }
+
+    fn at(seconds: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn all_is_the_whole_buffer() {
+        assert_eq!(Region::All.indices(&buffer(&[3, 4, 5])), 0..3);
+        assert_eq!(Region::All.indices(&[]), 0..0);
+    }
+
+    #[test]
+    fn around_stops_at_either_end() {
+        let lines = buffer(&[1, 2, 3, 4, 5]);
+        assert_eq!(Region::Around(3, 1).indices(&lines), 1..4);
+        assert_eq!(Region::Around(1, 2).indices(&lines), 0..3);
+        assert_eq!(Region::Around(5, 10).indices(&lines), 0..5);
+    }
+
+    #[test]
+    fn around_an_evicted_line_is_empty() {
+        let lines = buffer(&[4, 5, 6]);
+        assert!(Region::Around(2, 3).indices(&lines).is_empty());
+    }
+
+    #[test]
+    fn between_takes_its_ends_in_either_order() {
+        let lines = buffer(&[10, 11, 12, 13, 14]);
+        assert_eq!(Region::Between(11, 13).indices(&lines), 1..4);
+        assert_eq!(Region::Between(13, 11).indices(&lines), 1..4);
+        assert_eq!(Region::Between(12, 12).indices(&lines), 2..3);
+    }
+
+    #[test]
+    fn between_keeps_what_is_left_once_an_end_is_evicted() {
+        let lines = buffer(&[10, 11, 12]);
+        assert_eq!(Region::Between(5, 11).indices(&lines), 0..2);
+        assert_eq!(Region::Between(11, 20).indices(&lines), 1..3);
+        assert!(Region::Between(1, 5).indices(&lines).is_empty());
+    }
+
+    #[test]
+    fn between_skips_deleted_lines_inside_it() {
+        let lines = buffer(&[1, 2, 5, 6, 9]);
+        assert_eq!(Region::Between(3, 7).indices(&lines), 2..4);
+    }
+
+    #[test]
+    fn time_includes_its_start_but_not_its_end() {
+        let lines = buffer(&[1, 2, 3, 4, 5]);
+        assert_eq!(Region::Time(at(1), at(3)).indices(&lines), 1..3);
+        assert_eq!(Region::Time(at(0), at(100)).indices(&lines), 0..5);
+        assert!(Region::Time(at(10), at(20)).indices(&lines).is_empty());
+    }
+
+    #[test]
+    fn time_backwards_is_empty() {
+        let lines = buffer(&[1, 2, 3, 4, 5]);
+        assert!(Region::Time(at(3), at(1)).indices(&lines).is_empty());
+    }
+
+    #[test]
+    fn selection_spans_its_first_and_last_lines() {
+        let lines = buffer(&[1, 2, 3, 4, 5]);
+        assert_eq!(Region::Selection(4, 2).indices(&lines), 1..4);
+        let texts: Vec<&str> = Region::Selection(2, 4).lines(&lines).iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(texts, ["line 2", "line 3", "line 4"]);
+    }
+}
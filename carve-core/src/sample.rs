@@ -0,0 +1,79 @@
+use std::time::UNIX_EPOCH;
+
+use crate::line::Line;
+
+/// How much of a chatty source to keep, so it can't drown out the others in the same session.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Policy {
+    /// Keep one line in every N: `1/N`.
+    Every(u64),
+    /// Keep at most N lines for each second they arrived in: `N/s`.
+    Rate(u64),
+}
+
+impl Policy {
+    /// Parse `1/N` or `N/s`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid sampling '{}', expected 1/N or N/s", spec);
+        let (count, per) = spec.split_once('/').ok_or_else(invalid)?;
+        match (count.trim(), per.trim()) {
+            ("1", n) => match n.parse() {
+                Ok(0) | Err(_) => Err(invalid()),
+                Ok(n) => Ok(Policy::Every(n)),
+            },
+            (n, "s") => n.parse().map(Policy::Rate).map_err(|_| invalid()),
+            _ => Err(invalid()),
+        }
+    }
+
+    pub fn describe(self) -> String {
+        match self {
+            Policy::Every(n) => format!("1/{}", n),
+            Policy::Rate(n) => format!("{}/s", n),
+        }
+    }
+}
+
+/// Decides which lines of one source are kept under its sampling policy, counting those dropped.
+pub struct Sampler {
+    pub policy: Policy,
+    pub dropped: u64,
+    seen: u64, // Lines seen since sampling started
+    second: u64, // The second, since the epoch, lines are being counted for
+    kept_this_second: u64,
+}
+
+impl Sampler {
+    pub fn new(policy: Policy) -> Self {
+        Self {
+            policy,
+            dropped: 0,
+            seen: 0,
+            second: 0,
+            kept_this_second: 0,
+        }
+    }
+
+    /// Whether to keep a line. Rate caps go by when lines arrived rather than when they're
+    /// looked at, so a backlog read in one go isn't cut down to a second's worth.
+    pub fn keep(&mut self, line: &Line) -> bool {
+        self.seen += 1;
+        let keep = match self.policy {
+            Policy::Every(n) => (self.seen - 1).is_multiple_of(n),
+            Policy::Rate(n) => {
+                let second = line.arrived.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+                if second != self.second {
+                    self.second = second;
+                    self.kept_this_second = 0;
+                }
+                self.kept_this_second += 1;
+                self.kept_this_second <= n
+            }
+        };
+        if !keep {
+            self.dropped += 1;
+        }
+        keep
+    }
+}
+
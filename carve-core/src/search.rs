@@ -0,0 +1,137 @@
+use regex::Regex;
+
+use crate::line::Line;
+
+/// The results of searching the buffer for a single query.
+#[derive(Default)]
+struct Results {
+    query: String,
+    regex: Option<Regex>, // The query compiled, when it's a regular expression
+    matches: Vec<(usize, usize, usize)>, // (line_index, start, end)
+    scanned: usize, // Number of lines from the start of the buffer already searched
+}
+
+/// Incrementally maintained search results.
+///
+/// A query starting with `/` is a regular expression, which matches nothing until it's valid.
+///
+/// Rather than rescanning the whole buffer on every keystroke, only lines that arrived since the
+/// last update are searched. Extending the query only re-checks lines that matched the shorter
+/// query, and the results for each shorter query are kept so backspacing is free.
+///
+/// Only lines for which the `include` given to each update returns true are searched, so a
+/// search can be kept to the lines passing a filter. It must give the same answer for a line
+/// every time until the search is rescanned.
+#[derive(Default)]
+pub struct Search {
+    current: Results,
+    previous: Vec<Results>,
+}
+
+impl Search {
+    pub fn query(&self) -> &str {
+        &self.current.query
+    }
+
+    /// The query compiled, when it's a valid regular expression.
+    pub fn regex(&self) -> Option<&Regex> {
+        self.current.regex.as_ref()
+    }
+
+    pub fn matches(&self) -> &[(usize, usize, usize)] {
+        &self.current.matches
+    }
+
+    pub fn matches_mut(&mut self) -> &mut Vec<(usize, usize, usize)> {
+        &mut self.current.matches
+    }
+
+    /// Change the query, searching only as much of the buffer as the change requires.
+    #[tracing::instrument(name = "search", level = "debug", skip(self, lines, include), fields(lines = lines.len()))]
+    pub fn set_query(&mut self, query: &str, lines: &[Line], include: impl Fn(&Line) -> bool) {
+        if query == self.current.query {
+            return self.update(lines, include);
+        }
+
+        if query.is_empty() {
+            *self = Self::default();
+            return;
+        }
+
+        // A longer regular expression can match lines the shorter one didn't
+        let narrowing = !self.current.query.is_empty() && query.starts_with(&self.current.query) && !query.starts_with('/');
+        if narrowing {
+            // Every line containing the new query contains the old one too, so only those lines
+            // need searching again.
+            let mut candidates: Vec<usize> = self.current.matches.iter().map(|(idx, _, _)| *idx).collect();
+            candidates.dedup();
+            let mut results = Results {
+                query: query.to_string(),
+                regex: None,
+                matches: Vec::new(),
+                scanned: self.current.scanned,
+            };
+            for idx in candidates {
+                results.find_in_line(idx, &lines[idx].text);
+            }
+            self.previous.push(std::mem::replace(&mut self.current, results));
+        } else if let Some(pos) = self.previous.iter().position(|r| r.query == query) {
+            self.previous.truncate(pos + 1);
+            self.current = self.previous.pop().unwrap();
+        } else {
+            self.previous.clear();
+            self.current = Results {
+                query: query.to_string(),
+                regex: query.strip_prefix('/').and_then(|pattern| Regex::new(pattern).ok()),
+                ..Default::default()
+            };
+        }
+
+        self.update(lines, include);
+    }
+
+    /// Search any lines appended since the last update.
+    pub fn update(&mut self, lines: &[Line], include: impl Fn(&Line) -> bool) {
+        let results = &mut self.current;
+        if results.query.is_empty() {
+            return;
+        }
+        for (idx, line) in lines.iter().enumerate().skip(results.scanned).filter(|(_, line)| include(line)) {
+            results.find_in_line(idx, &line.text);
+        }
+        results.scanned = lines.len();
+    }
+
+    /// Forget the matches found so far so the whole buffer is searched again, after lines were
+    /// put back into it or a change to which lines are included.
+    pub fn rescan(&mut self) {
+        self.previous.clear();
+        self.current.matches.clear();
+        self.current.scanned = 0;
+    }
+
+    /// Account for `removed` lines being dropped from the front of the buffer. The caller is
+    /// responsible for remapping the current matches; cached results for other queries are
+    /// discarded rather than remapped.
+    pub fn evicted(&mut self, removed: usize) {
+        self.previous.clear();
+        self.current.scanned = self.current.scanned.saturating_sub(removed);
+    }
+}
+
+impl Results {
+    fn find_in_line(&mut self, idx: usize, line: &str) {
+        match &self.regex {
+            Some(regex) => {
+                let found = regex.find_iter(line).filter(|m| !m.is_empty());
+                self.matches.extend(found.map(|m| (idx, m.start(), m.end())));
+            }
+            None if self.query.starts_with('/') => {}
+            None => {
+                for (start, _) in line.match_indices(&self.query) {
+                    self.matches.push((idx, start, start + self.query.len()));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::cmp::Ordering;
+
+use chrono::NaiveDateTime;
+
+use crate::line::Line;
+use crate::query;
+
+/// What to sort a pane's rows by, in place of the order the lines arrived in.
+#[derive(Clone, PartialEq)]
+pub struct Sort {
+    pub key: Key,
+    pub field: Option<Field>, // The part of each line to sort on, or the whole line
+    pub descending: bool,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Key {
+    /// Alphabetically.
+    Text,
+    /// By the first number, so `12ms` sorts before `100ms`.
+    Number,
+    /// By the timestamp each line was written at.
+    Time,
+}
+
+/// A part of a line to sort on.
+#[derive(Clone, PartialEq)]
+pub enum Field {
+    /// A field of the line split on whitespace, counting from 1.
+    Column(usize),
+    /// A JSON or logfmt field, by name.
+    Named(String),
+}
+
+/// The value a line is sorted by. Lines without one sort after those with.
+#[derive(PartialEq, PartialOrd)]
+enum Value {
+    Text(String),
+    Number(f64),
+    Time(NaiveDateTime),
+}
+
+impl Sort {
+    /// Parse `[text|number|time] [FIELD]`, where the field is a number for a column or the name of
+    /// a JSON or logfmt field, e.g. `number 3` or `text level`.
+    pub fn parse(args: &[String], descending: bool) -> Result<Self, String> {
+        let (key, rest) = match args.split_first() {
+            Some((key, rest)) if key == "text" => (Key::Text, rest),
+            Some((key, rest)) if key == "number" || key == "n" => (Key::Number, rest),
+            Some((key, rest)) if key == "time" => (Key::Time, rest),
+            _ => (Key::Text, args),
+        };
+        let field = match rest {
+            [] => None,
+            [_] if key == Key::Time => return Err("time sorts by each line's timestamp, so takes no field".to_string()),
+            [field] => Some(match field.parse::<usize>() {
+                Ok(0) => return Err("columns are numbered from 1".to_string()),
+                Ok(column) => Field::Column(column),
+                Err(_) => Field::Named(field.clone()),
+            }),
+            _ => return Err("usage: sort [text|number|time] [FIELD]".to_string()),
+        };
+        Ok(Self { key, field, descending })
+    }
+
+    /// Put `rows` in order by the lines they show, keeping lines with equal values in the order
+    /// they arrived.
+    pub fn apply<'a, T>(&self, rows: &mut [T], line: impl Fn(&T) -> &'a Line) {
+        rows.sort_by_cached_key(|row| {
+            let line = line(row);
+            SortValue {
+                value: self.value(line),
+                descending: self.descending,
+                id: line.id,
+            }
+        });
+    }
+
+    fn value(&self, line: &Line) -> Option<Value> {
+        if self.key == Key::Time {
+            return line.time.map(Value::Time);
+        }
+        let text = match &self.field {
+            None => line.text.clone(),
+            Some(Field::Column(column)) => line.text.split_whitespace().nth(column - 1)?.to_string(),
+            Some(Field::Named(name)) => query::fields(&line.text).into_iter().find(|(field, _)| field == name)?.1,
+        };
+        match self.key {
+            Key::Number => first_number(&text).map(Value::Number),
+            _ => Some(Value::Text(text)),
+        }
+    }
+
+    /// What the sort is by, for showing in messages.
+    pub fn describe(&self) -> String {
+        let key = match self.key {
+            Key::Text => "text",
+            Key::Number => "number",
+            Key::Time => "time",
+        };
+        let field = match &self.field {
+            None => String::new(),
+            Some(Field::Column(column)) => format!(" of column {}", column),
+            Some(Field::Named(name)) => format!(" of {}", name),
+        };
+        let direction = if self.descending { ", descending" } else { "" };
+        format!("{}{}{}", key, field, direction)
+    }
+}
+
+/// A line's place in the sort: by value, then by arrival.
+struct SortValue {
+    value: Option<Value>,
+    descending: bool,
+    id: u64,
+}
+
+impl Ord for SortValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let by_value = match (&self.value, &other.value) {
+            (Some(a), Some(b)) => {
+                let ordering = a.partial_cmp(b).unwrap_or(Ordering::Equal);
+                if self.descending { ordering.reverse() } else { ordering }
+            }
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+        by_value.then(self.id.cmp(&other.id))
+    }
+}
+
+impl PartialOrd for SortValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for SortValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for SortValue {}
+
+/// The first number written in `text`, with any sign and decimal places, e.g. `-1.5` in
+/// `took -1.5s`.
+fn first_number(text: &str) -> Option<f64> {
+    let digits = text.find(|c: char| c.is_ascii_digit())?;
+    let start = if text[..digits].ends_with('-') { digits - 1 } else { digits };
+    let mut end = digits;
+    let mut point = false;
+    for (i, c) in text[digits..].char_indices() {
+        match c {
+            '0'..='9' => end = digits + i + 1,
+            '.' if !point => point = true,
+            _ => break,
+        }
+    }
+    text[start..end].parse().ok()
+}
@@ -0,0 +1,301 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+use ratatui::text::Span;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::line::Line;
+
+/// Columns wider than this are truncated so one long value doesn't push the rest off screen.
+const MAX_COLUMN_WIDTH: usize = 40;
+/// Space left between columns.
+const COLUMN_GAP: &str = "  ";
+
+/// How a line is split into columns.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Delimiter {
+    /// Runs of spaces and tabs, as in `ps` or `kubectl get` output.
+    Whitespace,
+    Char(char),
+}
+
+impl Delimiter {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        let mut chars = name.chars();
+        match (name, chars.next(), chars.next()) {
+            ("tab" | "\\t", _, _) => Ok(Delimiter::Char('\t')),
+            ("comma", _, _) => Ok(Delimiter::Char(',')),
+            ("space" | "whitespace", _, _) => Ok(Delimiter::Whitespace),
+            (_, Some(c), None) => Ok(Delimiter::Char(c)),
+            _ => Err(format!("unknown delimiter: {}", name)),
+        }
+    }
+
+    /// Guess the delimiter from the header line: tabs, then commas, then whitespace.
+    fn detect(header: &str) -> Self {
+        if header.contains('\t') {
+            Delimiter::Char('\t')
+        } else if header.contains(',') {
+            Delimiter::Char(',')
+        } else {
+            Delimiter::Whitespace
+        }
+    }
+}
+
+/// Lays delimited lines out in aligned columns under the first line read, which is taken as the
+/// header. Column widths grow to fit the widest value seen so far.
+pub struct Table {
+    delimiter: Option<Delimiter>, // Detected from the header if not given
+    pub header: Option<(u64, String)>, // ID and text of the header line
+    widths: Vec<usize>,
+    pub first_column: usize, // Columns to the left of this are scrolled out of view
+    pub sort: Option<Sort>,
+    scanned: usize,
+}
+
+/// A column to order rows by.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Sort {
+    pub column: usize,
+    pub descending: bool,
+}
+
+impl Table {
+    pub fn new(delimiter: Option<Delimiter>) -> Self {
+        Self {
+            delimiter,
+            header: None,
+            widths: Vec::new(),
+            first_column: 0,
+            sort: None,
+            scanned: 0,
+        }
+    }
+
+    /// Measure any lines that have arrived since the last update.
+    pub fn update(&mut self, lines: &[Line]) {
+        if self.header.is_none() {
+            let Some(first) = lines.first() else {
+                return;
+            };
+            self.delimiter.get_or_insert_with(|| Delimiter::detect(&first.text));
+            self.header = Some((first.id, first.text.clone()));
+        }
+        for line in &lines[self.scanned.min(lines.len())..] {
+            let cells = self.cells(&line.text);
+            if self.widths.len() < cells.len() {
+                self.widths.resize(cells.len(), 0);
+            }
+            for (width, (start, end)) in self.widths.iter_mut().zip(cells) {
+                *width = (*width).max(line.text[start..end].width().min(MAX_COLUMN_WIDTH));
+            }
+        }
+        self.scanned = lines.len();
+    }
+
+    /// Account for lines dropped from the buffer so new lines aren't skipped.
+    pub fn evicted(&mut self, removed: usize) {
+        self.scanned = self.scanned.saturating_sub(removed);
+    }
+
+    /// Account for deleted lines being put back into the buffer, which were already measured.
+    pub fn restored(&mut self, count: usize) {
+        self.scanned += count;
+    }
+
+    pub fn scroll_left(&mut self) {
+        self.first_column = self.first_column.saturating_sub(1);
+    }
+
+    pub fn scroll_right(&mut self) {
+        self.first_column = (self.first_column + 1).min(self.widths.len().saturating_sub(1));
+    }
+
+    /// Sort by the leftmost column in view, ascending, then descending, then not at all.
+    pub fn cycle_sort(&mut self) {
+        let column = self.first_column;
+        self.sort = match self.sort {
+            Some(Sort { column: c, descending: false }) if c == column => Some(Sort { column, descending: true }),
+            Some(Sort { column: c, descending: true }) if c == column => None,
+            _ => Some(Sort { column, descending: false }),
+        };
+    }
+
+    /// Order rows, given by their line, by the sort column, keeping the header first and lines
+    /// with equal values in arrival order. Values which are all numbers are compared as numbers.
+    pub fn sort_rows<'a, T>(&self, rows: &mut Vec<T>, line: impl Fn(&T) -> &'a Line) {
+        let Some(sort) = self.sort else {
+            return;
+        };
+        let header = self.header.as_ref().map(|(id, _)| *id);
+        let mut keyed: Vec<(bool, Option<f64>, String, T)> = rows
+            .drain(..)
+            .map(|row| {
+                let line = line(&row);
+                let value = self.cell(&line.text, sort.column).unwrap_or_default().into_owned();
+                (Some(line.id) != header, value.parse().ok(), value, row)
+            })
+            .collect();
+        keyed.sort_by(|a, b| {
+            let by_value = match (a.1, b.1) {
+                (Some(x), Some(y)) => x.total_cmp(&y),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => a.2.cmp(&b.2),
+            };
+            a.0.cmp(&b.0).then(if sort.descending && a.0 && b.0 { by_value.reverse() } else { by_value })
+        });
+        rows.extend(keyed.into_iter().map(|(_, _, _, row)| row));
+    }
+
+    /// Whether a line passes a filter naming a column, like `status=500`, by having exactly that
+    /// value in the column. `None` if the filter doesn't name one of the header's columns.
+    pub fn matches_column(&self, filter: &str, text: &str) -> Option<bool> {
+        let (name, value) = filter.split_once('=')?;
+        let (_, header) = self.header.as_ref()?;
+        let column = self
+            .cells(header)
+            .into_iter()
+            .position(|(start, end)| unquote(&header[start..end]).eq_ignore_ascii_case(name.trim()))?;
+        Some(self.cell(text, column).is_some_and(|cell| cell == value))
+    }
+
+    /// The value of a column in a line, with any CSV quoting removed.
+    fn cell<'a>(&self, text: &'a str, column: usize) -> Option<Cow<'a, str>> {
+        let (start, end) = *self.cells(text).get(column)?;
+        Some(unquote(&text[start..end]))
+    }
+
+    /// The byte ranges of each cell in a line, with surrounding spaces trimmed. When splitting on
+    /// whitespace, the last header column takes the rest of the line so values with spaces in,
+    /// like the command in `ps` output, stay in one piece. Otherwise cells may be quoted, CSV
+    /// style, to hold the delimiter, in which case the range is of the text inside the quotes.
+    fn cells(&self, text: &str) -> Vec<(usize, usize)> {
+        let mut cells = Vec::new();
+        match self.delimiter.unwrap_or(Delimiter::Whitespace) {
+            Delimiter::Char(delimiter) => {
+                let mut start = 0;
+                loop {
+                    let (cell, next) = quoted_cell(text, start, delimiter);
+                    cells.push(cell);
+                    match next {
+                        Some(next) => start = next,
+                        None => break,
+                    }
+                }
+            }
+            Delimiter::Whitespace => {
+                let columns = match &self.header {
+                    Some((_, header)) => header.split_whitespace().count().max(1),
+                    None => usize::MAX,
+                };
+                let mut rest = text.trim_start();
+                while !rest.is_empty() {
+                    let start = text.len() - rest.len();
+                    let end = if cells.len() + 1 == columns {
+                        rest.len()
+                    } else {
+                        rest.find(char::is_whitespace).unwrap_or(rest.len())
+                    };
+                    cells.push(trimmed(text, start, start + end));
+                    rest = rest[end..].trim_start();
+                }
+            }
+        }
+        cells
+    }
+
+    /// Rearrange the styled spans of a line into aligned columns, starting from the first column
+    /// scrolled into view. `spans` must cover `text` exactly, so search highlights carry over.
+    pub fn layout(&self, text: &str, spans: &[Span<'static>]) -> Vec<Span<'static>> {
+        let mut out = Vec::new();
+        for (column, (start, end)) in self.cells(text).into_iter().enumerate().skip(self.first_column) {
+            if column > self.first_column {
+                out.push(Span::raw(COLUMN_GAP));
+            }
+            let width = self.widths.get(column).copied().unwrap_or(MAX_COLUMN_WIDTH);
+            let len = text[start..end].width();
+            if len > width {
+                // Cut the cell short, leaving room for an ellipsis, and padding where a wide
+                // character didn't fit
+                let mut cut = start;
+                let mut cut_width = 0;
+                for c in text[start..end].chars() {
+                    let c_width = c.width().unwrap_or(0);
+                    if cut_width + c_width > width.saturating_sub(1) {
+                        break;
+                    }
+                    cut += c.len_utf8();
+                    cut_width += c_width;
+                }
+                out.extend(slice(spans, start, cut));
+                out.push(Span::raw(format!("…{}", " ".repeat(width.saturating_sub(1) - cut_width))));
+            } else {
+                out.extend(slice(spans, start, end));
+                out.push(Span::raw(" ".repeat(width - len)));
+            }
+        }
+        out
+    }
+}
+
+/// The cell starting at byte `start`, and where the next one starts if there is one. A cell
+/// starting with `"` runs to the closing quote, with `""` standing for a quote inside it.
+fn quoted_cell(text: &str, start: usize, delimiter: char) -> ((usize, usize), Option<usize>) {
+    let rest = &text[start..];
+    let content_start = start + (rest.len() - rest.trim_start().len());
+    let close = text[content_start..].strip_prefix('"').and_then(closing_quote);
+    if let Some(close) = close.map(|idx| content_start + 1 + idx) {
+        let next = text[close + 1..].find(delimiter).map(|idx| close + 1 + idx + delimiter.len_utf8());
+        return ((content_start + 1, close), next);
+    }
+    // Unquoted, or with an unterminated quote which is taken literally
+    let end = text[start..].find(delimiter).map(|idx| start + idx);
+    (trimmed(text, start, end.unwrap_or(text.len())), end.map(|idx| idx + delimiter.len_utf8()))
+}
+
+/// The byte offset of the quote closing a quoted cell, given the text after its opening quote.
+fn closing_quote(text: &str) -> Option<usize> {
+    let mut chars = text.char_indices().peekable();
+    while let Some((idx, c)) = chars.next() {
+        if c == '"' {
+            if chars.peek().is_some_and(|&(_, next)| next == '"') {
+                chars.next();
+            } else {
+                return Some(idx);
+            }
+        }
+    }
+    None
+}
+
+/// A cell's value with CSV's doubled quotes collapsed.
+fn unquote(cell: &str) -> Cow<'_, str> {
+    if cell.contains("\"\"") {
+        Cow::Owned(cell.replace("\"\"", "\""))
+    } else {
+        Cow::Borrowed(cell)
+    }
+}
+
+fn trimmed(text: &str, start: usize, end: usize) -> (usize, usize) {
+    let cell = &text[start..end];
+    let start = start + (cell.len() - cell.trim_start().len());
+    (start, start + cell.trim().len())
+}
+
+/// The parts of `spans` covering the byte range `start..end` of their combined text.
+fn slice(spans: &[Span<'static>], start: usize, end: usize) -> Vec<Span<'static>> {
+    let mut out = Vec::new();
+    let mut offset = 0;
+    for span in spans {
+        let (span_start, span_end) = (offset, offset + span.content.len());
+        offset = span_end;
+        let (from, to) = (start.max(span_start), end.min(span_end));
+        if from < to {
+            out.push(Span::styled(span.content[from - span_start..to - span_start].to_string(), span.style));
+        }
+    }
+    out
+}
@@ -0,0 +1,152 @@
+use chrono::{Datelike, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+
+/// How far into a line a timestamp is looked for, so long lines aren't scanned to the end.
+const MAX_OFFSET: usize = 64;
+
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+/// Find the time a log line was written from the first timestamp near its start, as local time.
+///
+/// ISO 8601 timestamps such as `2024-01-01T12:00:00.123Z` or `2024-01-01 12:00:00,123` are
+/// recognised, with or without an offset, as are the `01/Jan/2024:12:00:00 +0000` of web
+/// server access logs and the `Jan  1 12:00:00` of syslog, which is taken to be this year. A
+/// line starting with a bare time of day, like `12:00:00.123`, is taken to be from today.
+pub fn parse(text: &str) -> Option<NaiveDateTime> {
+    let bytes = text.as_bytes();
+    let end = bytes.len().min(MAX_OFFSET);
+    for i in 0..end {
+        let starts_word = i == 0 || !bytes[i - 1].is_ascii_alphanumeric();
+        if !starts_word {
+            continue;
+        }
+        let rest = &bytes[i..];
+        let found = if rest[0].is_ascii_digit() {
+            iso(rest)
+                .or_else(|| access_log(rest))
+                .or_else(|| (i == 0 || bytes[i - 1] == b'[').then(|| time_of_day(rest)).flatten())
+        } else if rest[0].is_ascii_uppercase() {
+            syslog(rest)
+        } else {
+            None
+        };
+        if found.is_some() {
+            return found;
+        }
+    }
+    None
+}
+
+/// `2024-01-01T12:00:00`, with a space in place of the `T`, an optional fraction of a second
+/// and an optional `Z` or offset.
+fn iso(s: &[u8]) -> Option<NaiveDateTime> {
+    let year = number(s, 0, 4)?;
+    let month = number(s.get(5..)?, 0, 2)?;
+    let day = number(s.get(8..)?, 0, 2)?;
+    if s[4] != b'-' || s[7] != b'-' || !matches!(s.get(10)?, b'T' | b' ') {
+        return None;
+    }
+    let date = NaiveDate::from_ymd_opt(year as i32, month, day)?;
+    let (time, rest) = clock(s.get(11..)?)?;
+    let time = date.and_time(time);
+    Some(match offset(rest) {
+        Some(offset) => local(offset.from_local_datetime(&time).single()?.naive_utc()),
+        None => time,
+    })
+}
+
+/// `01/Jan/2024:12:00:00 +0000`, as in the common log format.
+fn access_log(s: &[u8]) -> Option<NaiveDateTime> {
+    let day = number(s, 0, 2)?;
+    let month = month(s.get(3..6)?)?;
+    let year = number(s.get(7..)?, 0, 4)?;
+    if s[2] != b'/' || s[6] != b'/' || *s.get(11)? != b':' {
+        return None;
+    }
+    let date = NaiveDate::from_ymd_opt(year as i32, month, day)?;
+    let (time, rest) = clock(s.get(12..)?)?;
+    let time = date.and_time(time);
+    Some(match rest.strip_prefix(b" ").and_then(offset) {
+        Some(offset) => local(offset.from_local_datetime(&time).single()?.naive_utc()),
+        None => time,
+    })
+}
+
+/// `Jan  1 12:00:00`, as in syslog, which has no year.
+fn syslog(s: &[u8]) -> Option<NaiveDateTime> {
+    let month = month(s.get(..3)?)?;
+    if *s.get(3)? != b' ' {
+        return None;
+    }
+    let s = &s[4..];
+    let (day, s) = match s {
+        [b' ', d, rest @ ..] if d.is_ascii_digit() => ((d - b'0') as u32, rest),
+        [a, b, rest @ ..] if a.is_ascii_digit() && b.is_ascii_digit() => (((a - b'0') * 10 + (b - b'0')) as u32, rest),
+        [d, rest @ ..] if d.is_ascii_digit() => ((d - b'0') as u32, rest),
+        _ => return None,
+    };
+    let (time, _) = clock(s.strip_prefix(b" ")?)?;
+    let date = NaiveDate::from_ymd_opt(Local::now().year(), month, day)?;
+    Some(date.and_time(time))
+}
+
+/// A bare time of day, taken to be today.
+fn time_of_day(s: &[u8]) -> Option<NaiveDateTime> {
+    let (time, _) = clock(s)?;
+    Some(Local::now().date_naive().and_time(time))
+}
+
+/// `12:00:00` with an optional fraction of a second after a `.` or `,`, and what follows it.
+fn clock(s: &[u8]) -> Option<(NaiveTime, &[u8])> {
+    let hour = number(s, 0, 2)?;
+    let minute = number(s.get(3..)?, 0, 2)?;
+    let second = number(s.get(6..)?, 0, 2)?;
+    if s[2] != b':' || s[5] != b':' {
+        return None;
+    }
+    let mut rest = &s[8..];
+    let mut nanos = 0;
+    if let [b'.' | b',', fraction @ ..] = rest {
+        let digits = fraction.iter().take_while(|b| b.is_ascii_digit()).count();
+        if digits > 0 {
+            for (i, digit) in fraction[..digits.min(9)].iter().enumerate() {
+                nanos += (digit - b'0') as u32 * 10u32.pow(8 - i as u32);
+            }
+            rest = &fraction[digits..];
+        }
+    }
+    Some((NaiveTime::from_hms_nano_opt(hour, minute, second.min(59), nanos)?, rest))
+}
+
+/// `Z`, or an offset like `+01:00` or `-0500`, at the start of `s`.
+fn offset(s: &[u8]) -> Option<FixedOffset> {
+    let (sign, s) = match s.first()? {
+        b'Z' => return FixedOffset::east_opt(0),
+        b'+' => (1, &s[1..]),
+        b'-' => (-1, &s[1..]),
+        _ => return None,
+    };
+    let hours = number(s, 0, 2)?;
+    let minutes = match s.get(2)? {
+        b':' => number(s.get(3..)?, 0, 2)?,
+        _ => number(s.get(2..)?, 0, 2)?,
+    };
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60) as i32)
+}
+
+/// A time in UTC as local time.
+fn local(utc: NaiveDateTime) -> NaiveDateTime {
+    Local.from_utc_datetime(&utc).naive_local()
+}
+
+fn month(name: &[u8]) -> Option<u32> {
+    MONTHS.iter().position(|month| month.as_bytes() == name).map(|i| i as u32 + 1)
+}
+
+/// The number written with exactly `len` digits at `start`.
+fn number(s: &[u8], start: usize, len: usize) -> Option<u32> {
+    let digits = s.get(start..start + len)?;
+    if !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    Some(digits.iter().fold(0, |n, digit| n * 10 + (digit - b'0') as u32))
+}
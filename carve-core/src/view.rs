@@ -0,0 +1,371 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::blocks::Block;
+use crate::line::Line;
+
+/// How many lines a row of the view stands in for, when it is folded.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Fold {
+    None,
+    /// A run of this many identical consecutive lines.
+    Duplicates(usize),
+    /// The first line of a block with this many continuation lines hidden beneath it.
+    Block(usize),
+}
+
+/// A row of the main view.
+#[derive(Copy, Clone)]
+pub struct Row {
+    pub idx: usize, // Buffer index of the line shown
+    pub fold: Fold,
+    pub group: usize, // Buffer index of the first line of the run or block this row is part of
+    pub context: bool, // Shown only for being near a line passing the filter
+    pub gap: bool, // Lines from the same source were skipped between the last row shown from it and this one
+}
+
+impl Row {
+    fn new(idx: usize) -> Self {
+        Self {
+            idx,
+            fold: Fold::None,
+            group: idx,
+            context: false,
+            gap: false,
+        }
+    }
+}
+
+/// Where the lines with the same text as a row of the unique view are in the buffer.
+#[derive(Copy, Clone)]
+pub struct Occurrences {
+    pub count: usize,
+    pub first: usize, // Buffer index of the first line with the text, which the row shows
+    pub last: usize,
+}
+
+/// How many lines either side of each line passing the filter to show as well, like grep's
+/// `-B` and `-A`.
+#[derive(Copy, Clone, Default, PartialEq, Eq)]
+pub struct Context {
+    pub before: usize,
+    pub after: usize,
+}
+
+/// Which kinds of folding are enabled, along with the runs and blocks that have been expanded
+/// (identified by the buffer index of their first line).
+pub struct FoldOptions<'a> {
+    pub duplicates: bool,
+    pub blocks: bool,
+    pub unique: bool, // Show each distinct line once, however far apart its repeats are
+    pub expanded: &'a BTreeSet<usize>,
+}
+
+/// The rows of the main view: the lines passing the current filter, maintained incrementally so
+/// that rendering and navigation only ever touch the lines they need rather than the whole
+/// buffer.
+///
+/// Runs of identical consecutive lines, and continuation lines of multi-line blocks such as stack
+/// traces, can be folded into a single row for their first line.
+///
+/// When lines are joined into multi-line records, a record is shown in full as soon as any one of
+/// its lines passes the filter.
+///
+/// When unique, each distinct line is shown once, at its first occurrence, counting how often it
+/// recurs anywhere in the buffer rather than only in consecutive runs. Nothing else is folded and
+/// no context is shown.
+///
+/// With context, the lines from the same source just before and after each line passing the
+/// filter are shown too, and a row after lines that were skipped is marked as a gap.
+#[derive(Default)]
+pub struct FilteredView {
+    pub rows: Vec<Row>,
+    scanned: usize, // Number of lines from the start of the buffer already considered
+    filter: String, // The filter `rows` was computed for
+    run_start: Option<usize>, // Buffer index of the first line of the current run of duplicates
+    block: Option<Block>, // The block the next line may continue
+    records: HashSet<u64>, // Records with a line passing the filter, when lines are joined
+    context: Context,
+    after: HashMap<usize, usize>, // Lines of trailing context still to show from each source
+    shown: HashMap<usize, u64>, // Number of the last line shown from each source, when showing context
+    seen: HashMap<String, usize>, // Buffer index of the first line with each text, when unique
+    occurrences: HashMap<usize, Occurrences>, // By the buffer index of the row's line, when unique
+    resets: u64, // How many times the view has been rebuilt, so others can tell when it is
+    reordered: bool, // The rows have been sorted out of buffer order since they were last added to
+}
+
+impl FilteredView {
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Buffer index of the line shown on the given row.
+    pub fn line_index(&self, row: usize) -> Option<usize> {
+        self.rows.get(row).map(|row| row.idx)
+    }
+
+    /// Where the line shown on a row of the unique view occurs in the buffer, given the buffer
+    /// index of the row's line.
+    pub fn occurrences(&self, idx: usize) -> Option<Occurrences> {
+        self.occurrences.get(&idx).copied()
+    }
+
+    /// Forget everything so the next update rebuilds the view from scratch.
+    pub fn reset(&mut self) {
+        *self = Self {
+            resets: self.resets + 1,
+            context: self.context,
+            ..Self::default()
+        };
+    }
+
+    /// Change how much context is shown around lines passing the filter, rebuilding the view if
+    /// it's different.
+    pub fn set_context(&mut self, context: Context) {
+        if context != self.context {
+            self.context = context;
+            self.reset();
+        }
+    }
+
+    /// Note that the rows have been put in an order other than the buffer's, such as by a sort.
+    /// They're put back in buffer order before rows are next added, as context and the earlier
+    /// lines of records are slotted in among them by buffer index.
+    pub fn reordered(&mut self) {
+        self.reordered = true;
+    }
+
+    /// How many times the view has been rebuilt from scratch.
+    pub fn resets(&self) -> u64 {
+        self.resets
+    }
+
+    /// Bring the view up to date with the buffer and filter, only considering as many lines as
+    /// necessary: new lines are checked as they arrive and, for `substring` filters, extending
+    /// the filter only re-checks lines which passed the shorter one. Returns whether any rows
+    /// were added or the view was rebuilt.
+    pub fn update(
+        &mut self,
+        filter: &str,
+        lines: &[Line],
+        fold: &FoldOptions,
+        records: bool,
+        substring: bool,
+        is_visible: impl Fn(&Line) -> bool,
+    ) -> bool {
+        // Only trace updates with work to do rather than every frame
+        let new_lines = lines.len().saturating_sub(self.scanned);
+        let _span = (filter != self.filter || new_lines > 0)
+            .then(|| tracing::debug_span!("filter", filter, new_lines).entered());
+
+        let folding = fold.duplicates || fold.blocks || fold.unique;
+        let mut changed = self.scanned == 0;
+        if filter != self.filter {
+            changed = true;
+            let narrowing = substring && !self.filter.is_empty() && filter.starts_with(&self.filter);
+            let context = self.context != Context::default();
+            if narrowing && !folding && !records && !context {
+                self.rows.retain(|row| is_visible(&lines[row.idx]));
+            } else {
+                self.reset();
+            }
+            self.filter = filter.to_string();
+        }
+
+        if self.reordered && self.scanned < lines.len() {
+            self.rows.sort_by_key(|row| row.idx);
+            self.reordered = false;
+        }
+
+        // Whole records are shown already, so there's no context to add
+        let context = (!records).then_some(self.context).filter(|context| *context != Context::default());
+        for (idx, line) in lines.iter().enumerate().skip(self.scanned) {
+            if fold.unique {
+                if is_visible(line) {
+                    self.push_unique(idx, line);
+                }
+                continue;
+            }
+            if records && self.records.contains(&line.record) {
+                // Part of a record that's already shown, whether or not it passes itself
+            } else if !is_visible(line) {
+                if context.is_some() {
+                    self.push_after(idx, line);
+                }
+                continue;
+            } else if records {
+                self.records.insert(line.record);
+                self.show_record(idx, lines);
+            }
+
+            let mut gap = false;
+            if let Some(context) = context {
+                let before = self.push_before(idx, lines, context.before);
+                gap = self.skipped(line);
+                self.after.insert(line.source, context.after);
+                if before || gap {
+                    // Lines aren't folded together across context or a gap
+                    self.run_start = None;
+                    self.block = None;
+                }
+            }
+
+            if let Some(block) = self.block.as_mut().filter(|_| fold.blocks) {
+                if block.continues(&line.text) {
+                    let head = block.head;
+                    self.push_folded(idx, head, fold.expanded, false);
+                    continue;
+                }
+            }
+
+            if let Some(run_start) = self.run_start.filter(|_| fold.duplicates) {
+                if lines[run_start].text == line.text {
+                    self.push_folded(idx, run_start, fold.expanded, true);
+                    continue;
+                }
+            }
+
+            self.run_start = Some(idx);
+            self.block = Some(Block::new(idx, &line.text));
+            self.rows.push(Row { gap, ..Row::new(idx) });
+        }
+        changed |= self.scanned != lines.len();
+        self.scanned = lines.len();
+        changed
+    }
+
+    /// Count a line passing the filter in the unique view, showing it if it's the first with its
+    /// text.
+    fn push_unique(&mut self, idx: usize, line: &Line) {
+        if let Some(first) = self.seen.get(&line.text) {
+            if let Some(occurrences) = self.occurrences.get_mut(first) {
+                occurrences.count += 1;
+                occurrences.last = idx;
+            }
+            return;
+        }
+        self.seen.insert(line.text.clone(), idx);
+        self.occurrences.insert(idx, Occurrences { count: 1, first: idx, last: idx });
+        self.rows.push(Row::new(idx));
+    }
+
+    /// Show the lines before `idx` which belong to the same record, now that a line of it has
+    /// passed the filter. They may be interleaved with lines from other sources.
+    fn show_record(&mut self, idx: usize, lines: &[Line]) {
+        let record = lines[idx].record;
+        let start = lines[..idx].partition_point(|line| line.id < record);
+        for earlier in (start..idx).filter(|&i| lines[i].record == record) {
+            if let Err(pos) = self.rows.binary_search_by_key(&earlier, |row| row.idx) {
+                self.rows.insert(
+                    pos,
+                    Row::new(earlier),
+                );
+            }
+        }
+    }
+
+    /// Show up to `count` lines from the same source before the line at `idx`, now that it has
+    /// passed the filter, stopping at any already shown. Returns whether there were any.
+    fn push_before(&mut self, idx: usize, lines: &[Line], count: usize) -> bool {
+        let line = &lines[idx];
+        let shown = self.shown.get(&line.source).copied().unwrap_or(0);
+        let earlier: Vec<usize> = (0..idx)
+            .rev()
+            .filter(|&i| lines[i].source == line.source)
+            .take_while(|&i| lines[i].number > shown)
+            .take(count)
+            .collect();
+        for &i in earlier.iter().rev() {
+            let gap = self.skipped(&lines[i]);
+            self.insert_context(Row { context: true, gap, ..Row::new(i) });
+        }
+        !earlier.is_empty()
+    }
+
+    /// Show a line that didn't pass the filter if it's soon enough after one from the same source
+    /// that did.
+    fn push_after(&mut self, idx: usize, line: &Line) {
+        let Some(remaining) = self.after.get_mut(&line.source).filter(|remaining| **remaining > 0) else {
+            return;
+        };
+        *remaining -= 1;
+        let gap = self.skipped(line);
+        self.insert_context(Row { context: true, gap, ..Row::new(idx) });
+        self.run_start = None;
+        self.block = None;
+    }
+
+    /// Add a row of context in buffer order, which lines shown since from other sources may be
+    /// after.
+    fn insert_context(&mut self, row: Row) {
+        if let Err(pos) = self.rows.binary_search_by_key(&row.idx, |row| row.idx) {
+            self.rows.insert(pos, row);
+        }
+    }
+
+    /// Note that a line is being shown, returning whether lines from its source were skipped
+    /// since the last one shown.
+    fn skipped(&mut self, line: &Line) -> bool {
+        let last = self.shown.insert(line.source, line.number);
+        last.is_some_and(|last| line.number > last + 1)
+    }
+
+    /// Add a line belonging to the run of duplicates or block starting at `group`, either as a row
+    /// of its own if the group is expanded or by growing the fold on the group's row.
+    fn push_folded(&mut self, idx: usize, group: usize, expanded: &BTreeSet<usize>, duplicate: bool) {
+        if expanded.contains(&group) {
+            self.rows.push(Row { group, ..Row::new(idx) });
+            return;
+        }
+        let Some(row) = self.rows.iter_mut().rev().find(|row| row.idx == group) else {
+            return;
+        };
+        row.fold = match row.fold {
+            Fold::None if duplicate => Fold::Duplicates(2),
+            Fold::None => Fold::Block(1),
+            Fold::Duplicates(n) => Fold::Duplicates(n + 1),
+            Fold::Block(n) => Fold::Block(n + 1),
+        };
+    }
+
+    /// Remap rows after lines have been evicted from the front of the buffer, dropping those for
+    /// which `remap` returns `None`. Returns how many rows were dropped.
+    ///
+    /// Only the oldest lines are evicted, so a fold whose first line is still there is there in
+    /// full and can be remapped. One that lost its first line can't be, nor can the counts of
+    /// unique lines, so then the view is reset instead and must be rebuilt with `update`.
+    pub fn evicted(&mut self, removed: usize, remap: impl Fn(usize) -> Option<usize>) -> usize {
+        let before = self.rows.len();
+        let unique = !self.occurrences.is_empty();
+        // Lines which moved down along with everything after them, rather than being kept back
+        // at the front of the buffer as pinned lines are
+        let shifted = |idx: usize| idx.checked_sub(removed).is_some_and(|new| remap(idx) == Some(new));
+        let folded = |row: &Row| row.fold != Fold::None || row.group != row.idx;
+        if unique || self.rows.iter().any(|row| folded(row) && !(shifted(row.idx) && shifted(row.group))) {
+            let dropped = self.rows.iter().filter(|row| remap(row.idx).is_none()).count();
+            self.reset();
+            return dropped;
+        }
+        self.rows = self
+            .rows
+            .iter()
+            .filter_map(|row| {
+                remap(row.idx).map(|idx| Row {
+                    idx,
+                    group: remap(row.group).unwrap_or(idx),
+                    ..*row
+                })
+            })
+            .collect();
+        self.run_start = self.run_start.and_then(&remap);
+        self.block = self.block.take().and_then(|mut block| {
+            block.head = remap(block.head)?;
+            Some(block)
+        });
+        self.scanned = self.scanned.saturating_sub(removed);
+        before - self.rows.len()
+    }
+}
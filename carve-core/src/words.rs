@@ -0,0 +1,91 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::line::Line;
+use crate::view::Row;
+
+/// The most words shown in the panel.
+pub const MAX_SHOWN: usize = 60;
+
+/// Words shorter than this are left out, as they're rarely telling.
+const MIN_LENGTH: usize = 3;
+
+/// Common English words left out of the counts, on top of any listed under `stopwords` in the
+/// config file.
+const STOPWORDS: &[&str] = &[
+    "about", "after", "all", "also", "and", "any", "are", "because", "been", "before", "being", "but", "can", "could",
+    "did", "does", "for", "from", "had", "has", "have", "her", "his", "how", "into", "its", "may", "more", "most",
+    "not", "now", "off", "one", "only", "other", "our", "out", "over", "she", "should", "some", "such", "than",
+    "that", "the", "their", "them", "then", "there", "these", "they", "this", "those", "too", "two", "very", "was",
+    "were", "what", "when", "where", "which", "while", "who", "why", "will", "with", "would", "you", "your",
+];
+
+/// Live counts of the words in the lines of the focused pane's view, for a quick sense of the
+/// vocabulary of an unfamiliar log.
+///
+/// Words containing digits are skipped since they're mostly IDs and numbers, and words are
+/// counted with their case as written so filtering on one finds every line it was counted in.
+pub struct Words {
+    counts: HashMap<String, u64>,
+    stopwords: HashSet<String>,
+    view: String, // Identifies the view the counts are for
+    counted: usize, // Rows of the view already counted
+    pub selected: usize,
+}
+
+impl Words {
+    pub fn new(stopwords: &[String]) -> Self {
+        let configured = stopwords.iter().map(|word| word.to_lowercase());
+        Self {
+            counts: HashMap::new(),
+            stopwords: STOPWORDS.iter().map(|word| word.to_string()).chain(configured).collect(),
+            view: String::new(),
+            counted: 0,
+            selected: 0,
+        }
+    }
+
+    /// Count the words in any rows added to the view since the last update, or start again if
+    /// it's a different view, or the rows were dropped or reordered.
+    pub fn update(&mut self, view: &str, rows: &[Row], lines: &[Line], reordered: bool) {
+        let rebuilt = rows.len() < self.counted || (reordered && rows.len() != self.counted);
+        if view != self.view || rebuilt {
+            self.counts.clear();
+            self.counted = 0;
+            self.view = view.to_string();
+        }
+        for row in &rows[self.counted..] {
+            let text = &lines[row.idx].text;
+            for word in text.split(|c: char| !c.is_alphanumeric() && c != '_') {
+                let counted = word.chars().count() >= MIN_LENGTH
+                    && !word.chars().any(|c| c.is_numeric())
+                    && !self.stopwords.contains(&word.to_lowercase());
+                if counted {
+                    *self.counts.entry(word.to_string()).or_default() += 1;
+                }
+            }
+        }
+        self.counted = rows.len();
+    }
+
+    /// The `n` most frequent words, most frequent first, with their counts.
+    pub fn top(&self, n: usize) -> Vec<(&str, u64)> {
+        let mut top: Vec<(&str, u64)> = self.counts.iter().map(|(w, c)| (w.as_str(), *c)).collect();
+        top.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        top.truncate(n);
+        top
+    }
+
+    pub fn select_next(&mut self) {
+        let shown = self.counts.len().min(MAX_SHOWN);
+        self.selected = (self.selected + 1).min(shown.saturating_sub(1));
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// The word currently selected, if there are any.
+    pub fn selected_word(&self) -> Option<String> {
+        self.top(MAX_SHOWN).get(self.selected).map(|(word, _)| word.to_string())
+    }
+}
@@ -0,0 +1,348 @@
+//! carve in a browser: the engine from carve-core compiled to WebAssembly behind a terminal such
+//! as xterm.js. `www/carve.js` fetches a log, such as a CI artifact, pushes it into a `Viewer`,
+//! passes it what's typed into the terminal and writes back what it draws.
+
+use carve_core::line::Line;
+use carve_core::pane::{Order, Pane};
+use unicode_width::UnicodeWidthChar;
+use wasm_bindgen::prelude::*;
+
+const TAB_WIDTH: usize = 4;
+
+/// Styles drawn with SGR escape sequences, matching those of the TUI.
+const CURSOR: &str = "1;48;5;236"; // The row under the cursor
+const MATCH: &str = "97;100";
+const CURRENT_MATCH: &str = "30;43";
+
+#[derive(Copy, Clone, PartialEq)]
+enum Mode {
+    Normal,
+    Filter,
+    Search,
+}
+
+/// A key pressed, read from the input xterm.js passes on.
+#[derive(Copy, Clone, PartialEq)]
+enum Key {
+    Char(char),
+    Ctrl(char),
+    Enter,
+    Esc,
+    Backspace,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+}
+
+/// The escape sequences terminals send for the keys the viewer uses, in both cursor key modes.
+const SEQUENCES: &[(&str, Key)] = &[
+    ("\x1b[A", Key::Up),
+    ("\x1bOA", Key::Up),
+    ("\x1b[B", Key::Down),
+    ("\x1bOB", Key::Down),
+    ("\x1b[5~", Key::PageUp),
+    ("\x1b[6~", Key::PageDown),
+    ("\x1b[H", Key::Home),
+    ("\x1bOH", Key::Home),
+    ("\x1b[1~", Key::Home),
+    ("\x1b[F", Key::End),
+    ("\x1bOF", Key::End),
+    ("\x1b[4~", Key::End),
+];
+
+/// A log and a pane onto it, with carve's keys for scrolling, filtering and search, drawn as
+/// escape sequences for a terminal `rows` by `cols` characters.
+#[wasm_bindgen]
+pub struct Viewer {
+    lines: Vec<Line>,
+    pane: Pane,
+    mode: Mode,
+    rows: usize,
+    cols: usize,
+    partial: String, // The start of a line whose end hasn't been pushed yet
+}
+
+#[wasm_bindgen]
+impl Viewer {
+    #[wasm_bindgen(constructor)]
+    pub fn new(rows: usize, cols: usize) -> Self {
+        Self {
+            lines: Vec::new(),
+            pane: Pane::new(),
+            mode: Mode::Normal,
+            rows,
+            cols,
+            partial: String::new(),
+        }
+    }
+
+    /// Add text read from the log. It can stop partway through a line, which the next text
+    /// pushed, or `finish`, completes.
+    pub fn push(&mut self, text: &str) {
+        self.partial.push_str(text);
+        let Some(end) = self.partial.rfind('\n') else {
+            return;
+        };
+        let rest = self.partial.split_off(end + 1);
+        for text in std::mem::replace(&mut self.partial, rest).lines() {
+            self.add(text);
+        }
+    }
+
+    /// Add the last line of the log if it didn't end with a newline.
+    pub fn finish(&mut self) {
+        if !self.partial.is_empty() {
+            let text = std::mem::take(&mut self.partial);
+            self.add(text.trim_end_matches('\r'));
+        }
+    }
+
+    pub fn resize(&mut self, rows: usize, cols: usize) {
+        self.rows = rows;
+        self.cols = cols;
+    }
+
+    /// Filter the lines shown, as typed after `/`.
+    pub fn set_filter(&mut self, filter: &str) {
+        self.pane.filter = filter.to_string();
+    }
+
+    /// Search the lines shown, as typed after `f`, moving to the first match.
+    pub fn set_search(&mut self, query: &str) {
+        self.pane.search_query = query.to_string();
+        self.pane.refresh(&self.lines, false, false, false, None, Order::default());
+        self.pane.update_search(&self.lines, None);
+        self.pane.jump_to_match();
+    }
+
+    /// Handle what's typed into the terminal, as xterm.js's `onData` gives it: a key, the escape
+    /// sequence for one, or pasted text.
+    pub fn input(&mut self, data: &str) {
+        for key in keys(data) {
+            self.handle_key(key);
+        }
+    }
+
+    /// Draw the lines in view and a status bar, as escape sequences to write to the terminal.
+    pub fn render(&mut self) -> String {
+        let height = self.rows.saturating_sub(1);
+        self.pane.refresh(&self.lines, false, false, false, None, Order::default());
+        self.pane.fit_viewport(height);
+
+        let mut out = String::from("\x1b[?25l");
+        for row in 0..height {
+            out.push_str(&format!("\x1b[{};1H", row + 1));
+            let position = self.pane.scroll + row;
+            if let Some(idx) = self.pane.view.line_index(position) {
+                self.draw_line(&mut out, idx, position == self.pane.cursor);
+            }
+            out.push_str("\x1b[K\x1b[0m");
+        }
+        out.push_str(&format!("\x1b[{};1H\x1b[7m", self.rows));
+        out.push_str(&fit(&self.status(), self.cols));
+        out.push_str("\x1b[0m");
+        out
+    }
+}
+
+impl Viewer {
+    fn add(&mut self, text: &str) {
+        let mut line = Line::new(text.to_string(), 0);
+        line.id = self.lines.len() as u64 + 1;
+        line.record = line.id;
+        line.number = line.id;
+        self.lines.push(line);
+    }
+
+    fn handle_key(&mut self, key: Key) {
+        let pane = &mut self.pane;
+        match (self.mode, key) {
+            (Mode::Normal, Key::Char('j') | Key::Down) => {
+                pane.cursor_down(1);
+                pane.tailing = false;
+            }
+            (Mode::Normal, Key::Char('k') | Key::Up) => {
+                pane.cursor_up(1);
+                pane.tailing = false;
+            }
+            (Mode::Normal, Key::Ctrl('d') | Key::PageDown) => {
+                let (view_height, len) = (pane.view_height, pane.visible_len());
+                if len > view_height {
+                    pane.scroll_down(view_height / 2, len - view_height);
+                }
+                pane.cursor_down(view_height / 2);
+                pane.tailing = false;
+            }
+            (Mode::Normal, Key::Ctrl('u') | Key::PageUp) => {
+                let view_height = pane.view_height;
+                if pane.visible_len() > view_height {
+                    pane.scroll_up(view_height / 2);
+                }
+                pane.cursor_up(view_height / 2);
+                pane.tailing = false;
+            }
+            (Mode::Normal, Key::Char('g') | Key::Home) => {
+                pane.scroll_to(0);
+                pane.cursor = 0;
+                pane.tailing = false;
+            }
+            (Mode::Normal, Key::Char('G') | Key::End) => pane.tailing = true,
+            (Mode::Normal, Key::Char('/')) => {
+                pane.filter.clear();
+                self.mode = Mode::Filter;
+            }
+            (Mode::Normal, Key::Char('+')) => {
+                pane.push_filter();
+                self.mode = Mode::Filter;
+            }
+            (Mode::Normal, Key::Char('-')) => pane.pop_filter(),
+            (Mode::Normal, Key::Char('f')) => {
+                pane.search_query.clear();
+                pane.update_search(&self.lines, None);
+                self.mode = Mode::Search;
+            }
+            (Mode::Normal, Key::Char('n')) => {
+                pane.next_match();
+            }
+            (Mode::Normal, Key::Char('N')) => {
+                pane.prev_match();
+            }
+            (Mode::Filter, Key::Char(c)) => pane.filter.push(c),
+            (Mode::Filter, Key::Backspace) => {
+                pane.filter.pop();
+            }
+            (Mode::Search, Key::Char(c)) => {
+                pane.search_query.push(c);
+                pane.update_search(&self.lines, None);
+            }
+            (Mode::Search, Key::Backspace) => {
+                pane.search_query.pop();
+                pane.update_search(&self.lines, None);
+            }
+            (Mode::Search, Key::Enter) => {
+                pane.jump_to_match();
+                self.mode = Mode::Normal;
+            }
+            (Mode::Filter | Mode::Search, Key::Enter | Key::Esc) => self.mode = Mode::Normal,
+            _ => {}
+        }
+    }
+
+    /// Draw a line cut to the width of the terminal, with search matches highlighted and any
+    /// control characters in it made harmless.
+    fn draw_line(&self, out: &mut String, idx: usize, cursor: bool) {
+        let text = &self.lines[idx].text;
+        let matches = self.pane.search.matches();
+        let first = matches.partition_point(|&(line_idx, _, _)| line_idx < idx);
+        let ranges: Vec<(usize, usize, bool)> = matches[first..]
+            .iter()
+            .enumerate()
+            .take_while(|(_, &(line_idx, _, _))| line_idx == idx)
+            .map(|(i, &(_, start, end))| (start, end, first + i == self.pane.current_match))
+            .collect();
+        let base = if cursor { CURSOR } else { "" };
+
+        let mut style = None;
+        let mut width = 0;
+        for (at, c) in text.char_indices() {
+            let next = match ranges.iter().find(|&&(start, end, _)| start <= at && at < end) {
+                Some(&(_, _, true)) => CURRENT_MATCH,
+                Some(_) => MATCH,
+                None => base,
+            };
+            if style != Some(next) {
+                out.push_str(&format!("\x1b[0;{}m", next));
+                style = Some(next);
+            }
+            let (shown, columns) = match c {
+                '\t' => (None, TAB_WIDTH - width % TAB_WIDTH),
+                c if c.is_control() => (Some('\u{fffd}'), 1),
+                c => (Some(c), c.width().unwrap_or(0)),
+            };
+            if width + columns > self.cols {
+                break;
+            }
+            match shown {
+                Some(c) => out.push(c),
+                None => out.extend(std::iter::repeat_n(' ', columns)),
+            }
+            width += columns;
+        }
+        out.push_str(&format!("\x1b[0;{}m", base));
+    }
+
+    /// The mode and what's being typed on the left, and where the cursor is on the right.
+    fn status(&self) -> String {
+        let pane = &self.pane;
+        let left = match self.mode {
+            Mode::Filter => format!(" FILTER  /{}", pane.filter),
+            Mode::Search => format!(" SEARCH  f{}", pane.search_query),
+            Mode::Normal => {
+                let mut left = " NORMAL ".to_string();
+                if pane.is_filtered() {
+                    left.push_str(&format!(" [Filter: {}]", pane.describe_filters()));
+                }
+                let matches = pane.search.matches().len();
+                if !pane.search_query.is_empty() {
+                    left.push_str(&format!(" [Search: {} {}/{}]", pane.search_query, (pane.current_match + 1).min(matches), matches));
+                }
+                left
+            }
+        };
+        let len = pane.visible_len();
+        let right = match len {
+            0 => "0/0 ".to_string(),
+            len => format!("{}% {}/{} ", (pane.cursor + 1) * 100 / len, pane.cursor + 1, len),
+        };
+        let gap = self.cols.saturating_sub(left.chars().count() + right.chars().count()).max(1);
+        format!("{}{}{}", left, " ".repeat(gap), right)
+    }
+}
+
+/// Cut text to a width, padding it out to the width if it's shorter.
+fn fit(text: &str, cols: usize) -> String {
+    let mut out = String::new();
+    let mut width = 0;
+    for c in text.chars().filter(|c| !c.is_control()) {
+        let columns = c.width().unwrap_or(0);
+        if width + columns > cols {
+            break;
+        }
+        out.push(c);
+        width += columns;
+    }
+    out.extend(std::iter::repeat_n(' ', cols - width));
+    out
+}
+
+/// Split input from the terminal into keys. Escape sequences for keys the viewer doesn't use
+/// are skipped whole rather than typed.
+fn keys(data: &str) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut rest = data;
+    while let Some(c) = rest.chars().next() {
+        if let Some(&(sequence, key)) = SEQUENCES.iter().find(|(sequence, _)| rest.starts_with(sequence)) {
+            keys.push(key);
+            rest = &rest[sequence.len()..];
+            continue;
+        }
+        if let Some(after) = rest.strip_prefix("\x1b[").or_else(|| rest.strip_prefix("\x1bO")) {
+            // A sequence ends with its first character from `@` to `~`
+            let end = after.find(|c| ('@'..='~').contains(&c)).map_or(after.len(), |end| end + 1);
+            rest = &after[end..];
+            continue;
+        }
+        keys.push(match c {
+            '\r' | '\n' => Key::Enter,
+            '\x1b' => Key::Esc,
+            '\x7f' | '\x08' => Key::Backspace,
+            c if (c as u32) < 0x20 => Key::Ctrl((c as u8 + b'`') as char),
+            c => Key::Char(c),
+        });
+        rest = &rest[c.len_utf8()..];
+    }
+    keys
+}
@@ -0,0 +1,65 @@
+// Shows a log in an xterm.js terminal, with carve's engine filtering and searching it. The log
+// is fetched from `?log=URL`, such as a CI artifact, and `?filter=` and `?search=` start with a
+// filter or search already entered, as `--filter` and `--search` do.
+import init, { Viewer } from "./pkg/carve_web.js";
+
+const params = new URLSearchParams(location.search);
+
+await init();
+const term = new Terminal({ fontSize: 13 });
+const fit = new FitAddon.FitAddon();
+term.loadAddon(fit);
+term.open(document.getElementById("terminal"));
+fit.fit();
+
+const viewer = new Viewer(term.rows, term.cols);
+let drawing = false;
+
+// Draw at most once a frame, however fast the log arrives or keys are typed.
+function draw() {
+  if (drawing) return;
+  drawing = true;
+  requestAnimationFrame(() => {
+    drawing = false;
+    term.write(viewer.render());
+  });
+}
+
+term.onData((data) => {
+  viewer.input(data);
+  draw();
+});
+term.onResize(({ rows, cols }) => {
+  viewer.resize(rows, cols);
+  draw();
+});
+window.addEventListener("resize", () => fit.fit());
+term.focus();
+
+async function load(url) {
+  const response = await fetch(url);
+  if (!response.ok) {
+    throw new Error(`${response.status} ${response.statusText}`);
+  }
+  // Read the log as it downloads, so a long one starts showing straight away.
+  const reader = response.body.pipeThrough(new TextDecoderStream()).getReader();
+  for (;;) {
+    const { done, value } = await reader.read();
+    if (done) break;
+    viewer.push(value);
+    draw();
+  }
+  viewer.finish();
+}
+
+if (params.has("filter")) viewer.set_filter(params.get("filter"));
+const log = params.get("log");
+if (log) {
+  try {
+    await load(log);
+  } catch (e) {
+    viewer.push(`carve: failed to fetch ${log}: ${e.message}\n`);
+  }
+}
+if (params.has("search")) viewer.set_search(params.get("search"));
+draw();
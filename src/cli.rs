@@ -0,0 +1,343 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use carve_core::app::export::ExportOptions;
+use carve_core::app::options::{parse_duration, Annotation, ControlChars, Output};
+use clap::{Parser, ValueEnum};
+
+/// Interactively search and filter text piped into stdin.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Args {
+    /// Configure carve with the named profile from the config file
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Run `;` separated commands at startup as if entered on the `:` command line, e.g.
+    /// `--cmd 'filter error; search timeout; set wrap'`. May be given more than once
+    #[arg(long = "cmd", value_name = "COMMANDS")]
+    pub cmd: Vec<String>,
+
+    /// Start with TEXT as the filter, as if typed after `/`
+    #[arg(long, value_name = "TEXT")]
+    pub filter: Option<String>,
+
+    /// Count the lines matching PATTERN in the status bar as they're read, such as to watch how
+    /// many errors there are during a deploy. Written like a filter, and may be given more than
+    /// once
+    #[arg(long, value_name = "PATTERN")]
+    pub count: Vec<String>,
+
+    /// Ring the terminal bell and flash the status bar whenever a line matching PATTERN is read,
+    /// so a crash is noticed with carve in a background pane. Written like a filter, and may be
+    /// given more than once. `[[alert]]` in the config file can also send desktop notifications and
+    /// run commands
+    #[arg(long, value_name = "PATTERN")]
+    pub alert: Vec<String>,
+
+    /// Show only the parts of each line the filter matches, like `grep -o`, or the search's
+    /// matches when the filter has none, such as to pick IDs or URLs out of noisy output with
+    /// a `/` regular expression filter
+    #[arg(short, long)]
+    pub only_matching: bool,
+
+    /// Show NUM lines from the same source before each line passing the filter, like `grep -B`,
+    /// with `--` wherever lines were skipped
+    #[arg(short = 'B', long, value_name = "NUM")]
+    pub before_context: Option<usize>,
+
+    /// Show NUM lines from the same source after each line passing the filter, like `grep -A`
+    #[arg(short = 'A', long, value_name = "NUM")]
+    pub after_context: Option<usize>,
+
+    /// Show NUM lines from the same source either side of each line passing the filter, like
+    /// `grep -C`. `-A` and `-B` take precedence
+    #[arg(short = 'C', long, value_name = "NUM")]
+    pub context: Option<usize>,
+
+    /// Start with a search for TEXT, as if typed after `f`
+    #[arg(long, value_name = "TEXT")]
+    pub search: Option<String>,
+
+    /// Search every line, including those the filter hides, rather than only the lines shown.
+    /// `n` and `N` stop at the nearest line shown to a hidden match
+    #[arg(long)]
+    pub search_all: bool,
+
+    /// Show each distinct line once, wherever its repeats are, with how many times it occurs
+    #[arg(long)]
+    pub unique: bool,
+
+    /// Start at the top rather than following new lines as they arrive
+    #[arg(long)]
+    pub no_tail: bool,
+
+    /// Start at line N, once it has arrived, rather than following new lines
+    #[arg(long, value_name = "N")]
+    pub line: Option<u64>,
+
+    /// Keep at most this many lines in memory, dropping the oldest unpinned lines first
+    #[arg(long, value_name = "LINES")]
+    pub max_lines: Option<usize>,
+
+    /// Show the local time each line arrived in front of it, like `ts`. The time is also added
+    /// to the lines printed on exit, as with `--annotate time`, and those written to `--export`
+    /// and by `:w`. `:set notimestamps` hides it from view
+    #[arg(long)]
+    pub timestamps: bool,
+
+    /// Show the lines from every source in the order they were written, going by the timestamps
+    /// in them, rather than the order they arrived in, to follow events across the logs of
+    /// several services. Lines without a timestamp stay with the line before them from the same
+    /// source
+    #[arg(long)]
+    pub merge: bool,
+
+    /// Draw a rule with the time between them across the view wherever consecutive lines are
+    /// more than DURATION apart, such as `5s`, `500ms` or `2m`, going by the timestamps in the
+    /// lines, or when they arrived for lines without one
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub gap: Option<Duration>,
+
+    /// Prefix each line printed on exit with its provenance, tab separated and in the order
+    /// given
+    #[arg(long, value_name = "FIELDS", value_enum, value_delimiter = ',')]
+    pub annotate: Vec<Annotation>,
+
+    /// Treat each line matching REGEX as the start of a record, joining the lines that follow it
+    /// onto it until the next match. Records are kept or dropped by the filter as a whole, e.g.
+    /// `--join-on '^\S'` keeps an exception's entire stack trace
+    #[arg(long, value_name = "REGEX")]
+    pub join_on: Option<regex::Regex>,
+
+    /// Follow a streaming HTTP endpoint as a source, such as a log tail served with chunked
+    /// encoding or as Server-Sent Events, reconnecting if it drops. May be given more than once.
+    /// `https://` URLs are fetched with `curl`
+    #[arg(long, value_name = "URL")]
+    pub url: Vec<String>,
+
+    /// Follow a WebSocket as a source, adding each line of each message received, and
+    /// reconnecting if it drops. May be given more than once. `wss://` URLs are connected to with
+    /// `openssl`
+    #[arg(long, value_name = "URL")]
+    pub ws: Vec<String>,
+
+    /// Follow a Docker container's logs as two sources, one for its stdout and one for its
+    /// stderr, carrying on when it restarts. May be given more than once. The daemon is reached
+    /// through `DOCKER_HOST` if set
+    #[arg(long, value_name = "CONTAINER")]
+    pub docker: Vec<String>,
+
+    /// Follow the logs of every running pod of a workload, such as `deploy/api`, or matching a
+    /// label selector, such as `app=api`, with `kubectl`, each pod as a source of its own. Pods
+    /// that start later are followed as they appear. May be given more than once
+    #[arg(long, value_name = "TARGET")]
+    pub kube: Vec<String>,
+
+    /// Follow the systemd journal as a source, or only UNIT's entries, with `journalctl`. Each
+    /// entry's priority is shown as its level and the fields it was logged with as JSON
+    #[arg(long, value_name = "UNIT")]
+    pub journal: Option<Option<String>>,
+
+    /// Receive syslog messages as a source, listening on `udp:PORT` or `tcp:PORT`, or with a
+    /// host to bind to, e.g. `udp:127.0.0.1:5514`. Each message's severity is shown as its level
+    /// and its facility and structured data as JSON. May be given more than once
+    #[arg(long, value_name = "PROTO:PORT")]
+    pub syslog: Vec<String>,
+
+    /// Take lines from other local processes sent to a Unix socket created at PATH, or written to
+    /// an existing FIFO at PATH, as a source. May be given more than once
+    #[arg(long, value_name = "PATH")]
+    pub listen: Vec<PathBuf>,
+
+    /// Show a side-by-side diff of two files, or of a file and stdin
+    #[arg(long, value_name = "PATH", num_args = 1..=2)]
+    pub diff: Vec<PathBuf>,
+
+    /// Print runs of identical consecutive lines only once on exit
+    #[arg(long)]
+    pub collapse_duplicates: bool,
+
+    /// Handle the output printed on exit as `--on-max-output` says once it's more than this many
+    /// lines
+    #[arg(long, value_name = "LINES")]
+    pub max_output: Option<usize>,
+
+    /// Which lines to print on exit. `O` switches between them while running
+    #[arg(long, value_name = "LINES", value_enum, default_value_t = Output::Filtered)]
+    pub output: Output,
+
+    /// Print the output on exit to file descriptor N instead of stdout, e.g. `--output-fd 3
+    /// 3>picked.log`. stdout then carries the input through unchanged, so carve can sit in the
+    /// middle of a pipeline
+    #[arg(long, value_name = "N", conflicts_with = "output_file")]
+    pub output_fd: Option<i32>,
+
+    /// Write the output on exit to PATH instead of stdout, which then carries the input through
+    /// unchanged
+    #[arg(long, value_name = "PATH")]
+    pub output_file: Option<PathBuf>,
+
+    /// Print a summary line to stderr after the output on exit. FORMAT can use `{read}`,
+    /// `{buffered}`, `{filtered}`, `{marked}`, `{matches}`, `{printed}`, `{elapsed}` (as
+    /// HH:MM:SS) and `{seconds}`
+    #[arg(long, value_name = "FORMAT", num_args = 0..=1, default_missing_value = DEFAULT_SUMMARY)]
+    pub summary: Option<String>,
+
+    /// What to do with output over `--max-output`
+    #[arg(long, value_name = "ACTION", value_enum, default_value_t = Overflow::Truncate, requires = "max_output")]
+    pub on_max_output: Overflow,
+
+    /// Ask before quitting when more than this many lines would be printed to a terminal on exit,
+    /// unless `--max-output` is given
+    #[arg(long, value_name = "LINES", default_value_t = 10_000)]
+    pub confirm_quit_over: usize,
+
+    /// Continuously write every line read to PATH as it arrives
+    #[arg(long, value_name = "PATH")]
+    pub export: Option<PathBuf>,
+
+    /// Rotate the export file once it grows past SIZE (accepts K, M and G suffixes)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, requires = "export")]
+    pub export_max_size: Option<u64>,
+
+    /// Rotate the export file once it holds this many lines
+    #[arg(long, value_name = "LINES", requires = "export")]
+    pub export_max_lines: Option<u64>,
+
+    /// Number of rotated export files to keep around
+    #[arg(long, value_name = "N", default_value_t = 5, requires = "export")]
+    pub export_max_files: usize,
+
+    /// Gzip export files as they are rotated out
+    #[arg(long, requires = "export")]
+    pub export_gzip: bool,
+
+    /// Print nothing on exit, and exit with status 0 if any line passed the filter or 1 if none
+    /// did, like `grep -q`. Without a terminal to draw on, the input is read to the end and
+    /// checked against `--filter` without showing anything
+    #[arg(short, long, conflicts_with = "pick")]
+    pub quiet: bool,
+
+    /// Record a summary of the session, such as how many errors there were, under the profile
+    /// in a history file on exit, and compare against the profile's last session in the levels
+    /// panel
+    #[arg(long)]
+    pub history: bool,
+
+    /// Don't load the plugins in the `plugins` directory, or the scripts in the `scripts`
+    /// directory, next to the config file
+    #[arg(long)]
+    pub no_plugins: bool,
+
+    /// Use carve to pick lines in a pipeline: Enter prints the current line, or the lines marked
+    /// with Tab, and quits. Quitting with `q` prints nothing and exits with status 130
+    #[arg(long)]
+    pub pick: bool,
+
+    /// Emit OSC 8 hyperlinks for URLs and file:line references in the output printed on exit and,
+    /// with `always`, in exports. `auto` only does so when stdout is a terminal
+    #[arg(long, value_name = "WHEN", value_enum, default_value_t = Hyperlinks::Auto)]
+    pub hyperlinks: Hyperlinks,
+
+    /// Listen on a Unix socket at PATH for commands from other programs, one per line: any `:`
+    /// command, `get counts`, `get state` or `export selection`
+    #[arg(long, value_name = "PATH")]
+    pub control: Option<PathBuf>,
+
+    /// Press KEYS without a terminal, drawing to an off-screen buffer, once the input has been
+    /// read, then print the state of the session and the final screen. KEYS are written as in
+    /// the `--log-actions` log, separated by spaces, e.g. `'f timeout Enter n'`
+    #[arg(long, value_name = "KEYS")]
+    pub headless: Option<String>,
+
+    /// Write each key pressed, and the state it changed, to PATH for attaching to bug reports
+    #[arg(long, value_name = "PATH")]
+    pub log_actions: Option<PathBuf>,
+
+    /// Write a trace of ingestion, searching, filtering and drawing, with timings, to PATH
+    #[arg(long, value_name = "PATH")]
+    pub debug_log: Option<PathBuf>,
+
+    /// Highlight lines as LANGUAGE, a name or file extension such as `rust` or `yml`, rather than
+    /// guessing from the content. `none` turns highlighting off
+    #[arg(long, value_name = "LANGUAGE")]
+    pub language: Option<String>,
+
+    /// Lines of context either side of the current match copied by `y`
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    pub yank_context: usize,
+
+    /// Where `y` sends the current match and its context
+    #[arg(long, value_name = "TARGET", value_enum, default_value_t = YankTarget::Clipboard)]
+    pub yank_to: YankTarget,
+
+    /// Columns between tab stops when showing tabs
+    #[arg(long, value_name = "N", default_value_t = 8, value_parser = clap::value_parser!(u16).range(1..))]
+    pub tab_width: u16,
+
+    /// How to show control characters other than tabs, such as the escape sequences of coloured
+    /// output, which would otherwise garble the screen
+    #[arg(long, value_name = "HOW", value_enum, default_value_t = ControlChars::Visible)]
+    pub control_chars: ControlChars,
+}
+
+impl Args {
+    /// Where and how to export lines as they're read, if `--export` was given.
+    pub fn export_options(&self) -> Option<ExportOptions> {
+        self.export.as_ref().map(|path| ExportOptions {
+            path: path.clone(),
+            max_size: self.export_max_size,
+            max_lines: self.export_max_lines,
+            max_files: self.export_max_files,
+            gzip: self.export_gzip,
+            hyperlinks: self.hyperlinks == Hyperlinks::Always,
+            timestamps: self.timestamps,
+        })
+    }
+}
+
+/// The summary printed by `--summary` without a format.
+const DEFAULT_SUMMARY: &str = "carve: {read} lines read, {filtered} passed the filter, {printed} printed in {elapsed}";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Hyperlinks {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Overflow {
+    /// Print the first `--max-output` lines, followed by a notice on stderr
+    Truncate,
+    /// Write everything to a temporary file instead and print its path on stderr
+    File,
+    /// Show everything in `$PAGER`, or `less`
+    Page,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum YankTarget {
+    /// The system clipboard, via the terminal's OSC 52 support
+    Clipboard,
+    /// Straight to stdout, ahead of the filtered output printed on exit
+    Stdout,
+}
+
+/// Parse a byte count with an optional binary K/M/G suffix, e.g. `512K` or `10M`.
+fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (digits, multiplier) = match s.char_indices().last() {
+        Some((idx, 'k' | 'K')) => (&s[..idx], 1 << 10),
+        Some((idx, 'm' | 'M')) => (&s[..idx], 1 << 20),
+        Some((idx, 'g' | 'G')) => (&s[..idx], 1 << 30),
+        _ => (s, 1),
+    };
+    let value: u64 = digits
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", s))?;
+    value
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("size '{}' is too large", s))
+}
@@ -0,0 +1,13 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+
+use carve_core::app::base64;
+
+/// Copy text to the system clipboard using the OSC 52 escape sequence. This is understood by most
+/// modern terminal emulators (and tmux with `set-clipboard on`) and, unlike shelling out to
+/// xclip/pbcopy, keeps working over SSH.
+pub fn copy(text: &str) -> io::Result<()> {
+    let mut tty = OpenOptions::new().write(true).open(crate::TTY)?;
+    write!(tty, "\x1b]52;c;{}\x07", base64::encode(text.as_bytes()))?;
+    tty.flush()
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context};
+use serde::Deserialize;
+
+use carve_core::app::alerts::AlertRule;
+use carve_core::app::FilterPreset;
+
+use crate::cli::Args;
+
+/// The contents of carve's config file.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default, rename = "profile")]
+    pub profiles: HashMap<String, Profile>,
+    #[serde(default, rename = "highlight")]
+    pub highlights: Vec<HighlightRule>,
+    #[serde(default, rename = "filter")]
+    pub filters: Vec<FilterPreset>,
+    #[serde(default, rename = "bind")]
+    pub bindings: Vec<Binding>,
+    #[serde(default, rename = "alert")]
+    pub alerts: Vec<AlertRule>,
+    /// The key `<leader>` stands for in bindings, a backslash unless given.
+    pub leader: Option<String>,
+    /// How long to wait for the next key of a bound sequence, in milliseconds.
+    #[serde(rename = "chord-timeout")]
+    pub chord_timeout: Option<u64>,
+    /// Words left out of the word cloud, on top of common English ones.
+    #[serde(default)]
+    pub stopwords: Vec<String>,
+    #[serde(default)]
+    pub status: StatusBar,
+    #[serde(skip)]
+    path: Option<PathBuf>, // Where the config was loaded from, if anywhere
+}
+
+/// The key `<leader>` stands for in bindings unless the config file says otherwise.
+pub const DEFAULT_LEADER: &str = "\\";
+
+/// A named bundle of settings for a recurring task, selected with `--profile`, e.g.
+///
+/// ```toml
+/// [profile.nginx]
+/// open = ["/var/log/nginx/access.log", "/var/log/nginx/error.log"]
+/// cmd = ["compare ' 200 ' ' 500 '", "set fold"]
+/// max-lines = 100000
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Profile {
+    /// Files to open as additional sources, as with `:open`.
+    pub open: Vec<PathBuf>,
+    /// Commands to run at startup, before any given with `--cmd`.
+    pub cmd: Vec<String>,
+    pub max_lines: Option<usize>,
+    pub collapse_duplicates: bool,
+    /// Keep a history of sessions, as with `--history`.
+    pub history: bool,
+}
+
+/// A pattern to style wherever it appears, whatever else is going on, e.g.
+///
+/// ```toml
+/// [[highlight]]
+/// pattern = '[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}'
+/// fg = "cyan"
+///
+/// [[highlight]]
+/// pattern = "panic"
+/// bg = "red"
+/// line = true
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HighlightRule {
+    /// A regular expression.
+    pub pattern: String,
+    /// Colours by name, like `cyan` or `lightred`, number, or `#rrggbb`.
+    pub fg: Option<String>,
+    pub bg: Option<String>,
+    #[serde(default)]
+    pub bold: bool,
+    #[serde(default)]
+    pub underline: bool,
+    /// Style the whole line containing a match rather than just the match.
+    #[serde(default)]
+    pub line: bool,
+}
+
+/// A key which runs commands in normal mode, taking precedence over what it normally does, e.g.
+///
+/// ```toml
+/// [[bind]]
+/// key = "E"
+/// cmd = "filter ERROR; set levels"
+///
+/// [[bind]]
+/// key = "ctrl+t"
+/// cmd = "source ~/.config/carve/triage.carve"
+///
+/// [[bind]]
+/// key = "<leader>t"
+/// cmd = "call trace"
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Binding {
+    /// Written as in the action log, e.g. `E`, `g e`, `ctrl+t` or `<leader>e`.
+    pub key: String,
+    /// `;` separated commands, as entered on the `:` command line.
+    pub cmd: String,
+}
+
+/// What the status bar shows on either side, each a list of segments in order, e.g.
+///
+/// ```toml
+/// [[status.left]]
+/// format = " {mode} "
+/// fg = "black"
+/// bg = "yellow"
+///
+/// [[status.left]]
+/// format = "{match}/{matches} matches"
+///
+/// [[status.right]]
+/// format = "{filtered}/{read} lines"
+/// ```
+///
+/// A side that isn't given keeps its default segments.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct StatusBar {
+    pub left: Option<Vec<StatusSegment>>,
+    pub right: Option<Vec<StatusSegment>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StatusSegment {
+    /// Text with fields like `{mode}` filled in. A segment whose fields are all empty is left
+    /// out.
+    pub format: String,
+    /// Colours as for highlight rules.
+    pub fg: Option<String>,
+    pub bg: Option<String>,
+    #[serde(default)]
+    pub bold: bool,
+}
+
+impl Profile {
+    /// Fill in anything not given on the command line from the profile. Startup commands from
+    /// the profile run first so `--cmd` can override them.
+    pub fn apply(&self, args: &mut Args) {
+        args.cmd.splice(0..0, self.cmd.iter().cloned());
+        args.max_lines = args.max_lines.or(self.max_lines);
+        args.collapse_duplicates |= self.collapse_duplicates;
+        args.history |= self.history;
+    }
+}
+
+/// Where the config file lives: `$CARVE_CONFIG` if set, otherwise `carve/config.toml` under
+/// `$XDG_CONFIG_HOME` or `~/.config`.
+fn path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("CARVE_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(dir.join("carve").join("config.toml"))
+}
+
+/// Where plugins are loaded from: `plugins` next to the config file.
+pub fn plugins_dir() -> Option<PathBuf> {
+    Some(path()?.parent()?.join("plugins"))
+}
+
+/// Where scripts are loaded from: `scripts` next to the config file.
+pub fn scripts_dir() -> Option<PathBuf> {
+    Some(path()?.parent()?.join("scripts"))
+}
+
+/// Load the config file. Having no config file is the same as having an empty one.
+pub fn load() -> anyhow::Result<Config> {
+    let Some(path) = path().filter(|path| path.exists()) else {
+        return Ok(Config::default());
+    };
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("failed to read config file {}", path.display()))?;
+    let config: Config = toml::from_str(&contents)
+        .with_context(|| format!("invalid config file {}", path.display()))?;
+    Ok(Config {
+        path: Some(path),
+        ..config
+    })
+}
+
+impl Config {
+    /// Take the named profile out of the config.
+    pub fn take_profile(&mut self, name: &str) -> anyhow::Result<Profile> {
+        let Some(path) = &self.path else {
+            return Err(anyhow!("no config file to find profile '{}' in, set $CARVE_CONFIG", name));
+        };
+        self.profiles
+            .remove(name)
+            .ok_or_else(|| anyhow!("no profile named '{}' in {}", name, path.display()))
+    }
+}
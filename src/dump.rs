@@ -0,0 +1,111 @@
+use std::collections::hash_map::RandomState;
+use std::fs::{File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::cli::Overflow;
+
+/// Where the lines printed on exit go instead of stdout, if anywhere: file descriptor `fd`,
+/// which must already be open, or a file created at `path`.
+pub fn destination(fd: Option<i32>, path: Option<&Path>) -> io::Result<Option<File>> {
+    match (fd, path) {
+        (Some(fd), _) => from_fd(fd).map(Some),
+        (None, Some(path)) => File::create(path).map(Some),
+        (None, None) => Ok(None),
+    }
+}
+
+#[cfg(unix)]
+fn from_fd(fd: i32) -> io::Result<File> {
+    use std::os::fd::FromRawFd;
+
+    // SAFETY: F_GETFD only checks that the descriptor is open
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: the descriptor is open, and was handed to carve to write its output to
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+#[cfg(not(unix))]
+fn from_fd(_fd: i32) -> io::Result<File> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "file descriptors need a Unix-like system, use --output-file"))
+}
+
+/// Write the lines printed on exit to `out`, or stdout if not given, or, when there are more
+/// than `max` of them, deal with them as `overflow` says so a huge dump doesn't flood a shell or
+/// pipe by surprise. Notices go to stderr to keep them out of the output.
+///
+/// Paging the output or setting it aside in a temporary file only applies to stdout. Output
+/// sent to `out` was headed for a file all along, so it's written there in full.
+pub fn write(
+    out: Option<File>,
+    lines: impl Iterator<Item = String>,
+    count: usize,
+    max: Option<usize>,
+    overflow: Overflow,
+) -> io::Result<()> {
+    let Some(max) = max.filter(|&max| count > max) else {
+        return write_all(stdout_or(out), lines);
+    };
+    match (overflow, out) {
+        (Overflow::Truncate, out) => {
+            write_all(stdout_or(out), lines.take(max))?;
+            eprintln!("carve: output truncated to the first {} of {} lines", max, count);
+        }
+        (Overflow::File | Overflow::Page, Some(file)) => write_all(file, lines)?,
+        (Overflow::File, None) => {
+            let (path, file) = create_temp_file()?;
+            write_all(file, lines)?;
+            eprintln!("carve: {} lines written to {}", count, path.display());
+        }
+        (Overflow::Page, None) => {
+            // Like git, take the pager's arguments from $PAGER too, e.g. `less -R`
+            let pager = std::env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+            let mut words = pager.split_whitespace();
+            let program = words.next().unwrap_or("less");
+            let mut child = Command::new(program).args(words).stdin(Stdio::piped()).spawn()?;
+            let result = write_all(child.stdin.take().expect("stdin is piped"), lines);
+            child.wait()?;
+            // Quitting the pager before the end isn't an error
+            match result {
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+                result => result?,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Create a file for the output in the temporary directory. Its name ends with a random suffix
+/// so it can't be guessed ahead of time, and it must not already exist, so nothing, such as a
+/// symlink planted by another user, is written through in its place.
+fn create_temp_file() -> io::Result<(PathBuf, File)> {
+    let time = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    loop {
+        let suffix = RandomState::new().build_hasher().finish();
+        let path = std::env::temp_dir().join(format!("carve-{}-{:016x}.log", time, suffix));
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn stdout_or(out: Option<File>) -> Box<dyn Write> {
+    match out {
+        Some(file) => Box::new(file),
+        None => Box::new(io::stdout().lock()),
+    }
+}
+
+fn write_all(out: impl Write, lines: impl Iterator<Item = String>) -> io::Result<()> {
+    let mut out = BufWriter::new(out);
+    for line in lines {
+        writeln!(out, "{}", line)?;
+    }
+    out.flush()
+}
@@ -0,0 +1,21 @@
+use carve_core::app::groups::{Groups, MAX_GROUPS};
+use ratatui::style::{Color, Style};
+use ratatui::text::Span;
+
+use crate::render;
+
+/// The colours of each group, distinct from search matches.
+const STYLES: [Style; MAX_GROUPS] = [
+    Style::new().fg(Color::Black).bg(Color::Magenta),
+    Style::new().fg(Color::Black).bg(Color::Cyan),
+    Style::new().fg(Color::Black).bg(Color::Green),
+];
+
+/// Style each group's matches on top of any styling `spans` already has, including search
+/// matches. `spans` must cover `text` exactly. Later groups win where groups overlap.
+pub fn apply(groups: &Groups, text: &str, mut spans: Vec<Span<'static>>) -> Vec<Span<'static>> {
+    for (slot, ranges) in groups.matches(text) {
+        spans = render::patch_ranges(spans, &ranges, STYLES[slot]);
+    }
+    spans
+}
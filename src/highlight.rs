@@ -0,0 +1,115 @@
+use std::sync::LazyLock;
+
+use ratatui::style::{Color, Style};
+use ratatui::text::Span;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use carve_core::line::Line;
+
+use crate::render;
+
+/// Loaded on first use, since most logs aren't highlighted at all.
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_nonewlines);
+static THEME: LazyLock<Theme> = LazyLock::new(|| {
+    let mut themes = ThemeSet::load_defaults().themes;
+    themes.remove("base16-ocean.dark").unwrap_or_default()
+});
+
+/// How many lines from the start of the buffer are looked at to recognise the content.
+const DETECT_LINES: usize = 20;
+
+/// Colours lines by the syntax of the content, when it's recognisably source code, JSON, YAML or
+/// a diff rather than a log.
+///
+/// Each line is highlighted on its own as it's drawn, so constructs spanning several lines, like
+/// block comments, are only coloured on their first line.
+#[derive(Default)]
+pub struct Highlighter {
+    syntax: Option<&'static SyntaxReference>,
+    detected: bool, // Whether the syntax is settled, from `--language` or the content
+}
+
+impl Highlighter {
+    /// Highlight as `language`, a syntax name or file extension such as `rust` or `yml`, rather
+    /// than whatever the content turns out to be. `none` turns highlighting off.
+    pub fn new(language: &str) -> Result<Self, String> {
+        let syntax = match language {
+            "none" | "plain" => None,
+            _ => Some(find(language).ok_or_else(|| format!("unknown language: {}", language))?),
+        };
+        Ok(Self { syntax, detected: true })
+    }
+
+    /// Recognise the content from the first lines to arrive, once there are enough of them.
+    pub fn update(&mut self, lines: &[Line]) {
+        if self.detected || lines.is_empty() {
+            return;
+        }
+        let sample: Vec<&str> = lines.iter().take(DETECT_LINES).map(|line| line.text.as_str()).collect();
+        self.syntax = detect(&sample);
+        self.detected = lines.len() >= DETECT_LINES || self.syntax.is_some();
+    }
+
+    /// The name of the syntax lines are highlighted as, if any.
+    pub fn language(&self) -> Option<&str> {
+        self.syntax.map(|syntax| syntax.name.as_str())
+    }
+
+    /// Colour the text of `spans`, which must cover `text` exactly, leaving the colours of any
+    /// highlighted parts, like search matches, alone.
+    pub fn highlight(&self, text: &str, mut spans: Vec<Span<'static>>) -> Vec<Span<'static>> {
+        let Some(syntax) = self.syntax else {
+            return spans;
+        };
+        let Ok(regions) = HighlightLines::new(syntax, &THEME).highlight_line(text, &SYNTAXES) else {
+            return spans;
+        };
+        let mut offset = 0;
+        for (style, piece) in regions {
+            let color = Color::Rgb(style.foreground.r, style.foreground.g, style.foreground.b);
+            let range = [(offset, offset + piece.len())];
+            spans = render::map_ranges(spans, &range, |existing| Style::default().fg(color).patch(existing));
+            offset += piece.len();
+        }
+        spans
+    }
+}
+
+fn find(language: &str) -> Option<&'static SyntaxReference> {
+    SYNTAXES
+        .find_syntax_by_token(language)
+        .or_else(|| SYNTAXES.syntaxes().iter().find(|syntax| syntax.name.eq_ignore_ascii_case(language)))
+}
+
+/// Guess the syntax of a sample of lines, only committing to one when the content is clearly
+/// structured so ordinary logs are left uncoloured.
+fn detect(sample: &[&str]) -> Option<&'static SyntaxReference> {
+    let first = sample.first()?.trim_start();
+    if first.starts_with("diff --git") || (first.starts_with("--- ") && sample.get(1)?.starts_with("+++ ")) {
+        return find("diff");
+    }
+    if first.starts_with("#!") || first.starts_with("<?xml") {
+        return SYNTAXES.find_syntax_by_first_line(first);
+    }
+    if first == "{" || first == "[" || (first.starts_with('{') && first.ends_with('}')) {
+        return find("json");
+    }
+    let nested = sample.iter().any(|line| line.starts_with(' ') || line.starts_with("- "));
+    if first == "---" || (sample.len() >= 3 && nested && sample.iter().all(|line| is_yaml(line))) {
+        return find("yaml");
+    }
+    None
+}
+
+/// Whether a line looks like part of a YAML document: a `key: value` pair, a list item or a
+/// comment.
+fn is_yaml(line: &str) -> bool {
+    let line = line.trim_start();
+    let key = line.split_once(':').is_some_and(|(key, value)| {
+        !key.is_empty()
+            && key.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '"'))
+            && (value.is_empty() || value.starts_with(' '))
+    });
+    key || line.starts_with("- ") || line.starts_with('#') || line.is_empty()
+}
@@ -1,385 +1,1432 @@
-use std::fs::OpenOptions;
-use std::io::{self, IsTerminal};
+mod backend;
+mod cli;
+mod clipboard;
+mod config;
+mod dump;
+mod groups;
+mod highlight;
+mod pacing;
+mod render;
+mod rules;
+mod status;
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, IsTerminal, Write};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use carve_core::{captures, diff, freq, hex, level, line, pane, view, words};
+use carve_core::app::{self, actions, command, control, export, history, hyperlink, keymap, menu, options, plugin, quickfix, script, source, url, App, KeyOptions, Mode};
+use anyhow::Context;
 use crossterm::execute;
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
-use ratatui::Terminal;
-use tokio::io::{AsyncBufReadExt, BufReader};
-use crossterm::event::{self, Event, KeyCode};
+use clap::Parser;
+use ratatui::{Frame, Terminal};
+use crossterm::event::{self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEvent, KeyModifiers};
 use ratatui::{
-    backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
+    backend::{CrosstermBackend, TestBackend},
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Style, Stylize},
     text::Line,
-    widgets::{List, ListItem, Paragraph},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Sparkline},
 };
+use unicode_width::UnicodeWidthStr;
 
-#[derive(Copy, Clone)]
-enum Mode {
-    Normal, // Manual scrolling and searching
-    Search, // Command/search entry
-    Filter, // Filter expression entry
+/// What the terminal UI draws a session with, on top of its state: how lines are coloured and
+/// how the status bar is laid out.
+#[derive(Default)]
+struct Ui {
+    highlighter: highlight::Highlighter,
+    rules: Vec<rules::Rule>, // Highlight rules from the config file
+    status: status::Layout, // The segments of the status bar
 }
 
-impl Mode {
-    fn status_text(&self) -> &'static str {
-        match self {
-            Mode::Normal => "NORMAL",
-            Mode::Search => "SEARCH",
-            Mode::Filter => "FILTER",
-        }
+/// How long passed between two lines, going by their timestamps if both have one, or else when
+/// they arrived. Nothing if the second came first.
+fn gap_between(first: &line::Line, second: &line::Line) -> Option<Duration> {
+    match (first.time, second.time) {
+        (Some(first), Some(second)) => (second - first).to_std().ok(),
+        _ => second.arrived.duration_since(first.arrived).ok(),
     }
 }
 
-struct App {
-    lines: Arc<Mutex<Vec<String>>>,
-    scroll: usize,
-    mode: Mode,
-    tailing: bool,
-    filter: String,
-    search_query: String,
-    current_match: usize,
-    matches: Vec<(usize, usize, usize)>, // (line_index, start, end)
+/// A gap between lines to the nearest tenth of a second, or in larger units once it's longer
+/// than a minute, e.g. `4.2s`, `3m 12s` or `2h 5m`.
+fn format_gap(gap: Duration) -> String {
+    let seconds = gap.as_secs();
+    match seconds {
+        0..60 => format!("{:.1}s", gap.as_secs_f64()),
+        60..3600 => format!("{}m {}s", seconds / 60, seconds % 60),
+        3600..86400 => format!("{}h {}m", seconds / 3600, seconds / 60 % 60),
+        _ => format!("{}d {}h", seconds / 86400, seconds / 3600 % 24),
+    }
+}
+
+/// Copy the text the last key yanked to the clipboard or write it to stdout, saying in the status
+/// bar how it went.
+fn yank(app: &mut App, to: cli::YankTarget) {
+    let Some(text) = app.yank.take() else {
+        return;
+    };
+    let result = match to {
+        cli::YankTarget::Clipboard => clipboard::copy(&text),
+        cli::YankTarget::Stdout => {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(text.as_bytes()).and_then(|_| stdout.flush())
+        }
+    };
+    app.message = Some(match result {
+        Ok(()) => format!("Yanked {} lines", text.lines().count()),
+        Err(e) => format!("Yank failed: {}", e),
+    });
 }
 
-impl App {
-    fn new() -> Self {
-        Self {
-            lines: Arc::new(Mutex::new(Vec::new())),
-            scroll: 0,
-            mode: Mode::Normal,
-            search_query: String::new(),
-            current_match: 0,
-            matches: Vec::new(),
-            tailing: true,
-            filter: String::new(),
+/// Turn a key pressed in the terminal into one the session understands, if it has a use for it.
+fn key_event(key: KeyEvent) -> Option<app::key::KeyEvent> {
+    use app::key::KeyCode as Code;
+    let code = match key.code {
+        KeyCode::Char(c) => Code::Char(c),
+        KeyCode::Enter => Code::Enter,
+        KeyCode::Esc => Code::Esc,
+        KeyCode::Tab => Code::Tab,
+        KeyCode::BackTab => Code::BackTab,
+        KeyCode::Backspace => Code::Backspace,
+        KeyCode::Delete => Code::Delete,
+        KeyCode::Insert => Code::Insert,
+        KeyCode::Up => Code::Up,
+        KeyCode::Down => Code::Down,
+        KeyCode::Left => Code::Left,
+        KeyCode::Right => Code::Right,
+        KeyCode::Home => Code::Home,
+        KeyCode::End => Code::End,
+        KeyCode::PageUp => Code::PageUp,
+        KeyCode::PageDown => Code::PageDown,
+        KeyCode::F(n) => Code::F(n),
+        _ => return None,
+    };
+    let mut modifiers = app::key::KeyModifiers::NONE;
+    for (from, to) in [
+        (KeyModifiers::SHIFT, app::key::KeyModifiers::SHIFT),
+        (KeyModifiers::CONTROL, app::key::KeyModifiers::CONTROL),
+        (KeyModifiers::ALT, app::key::KeyModifiers::ALT),
+    ] {
+        if key.modifiers.contains(from) {
+            modifiers |= to;
         }
     }
+    Some(app::key::KeyEvent::new(code, modifiers))
+}
+
+/// Width of the ingest rate chart in the status bar, one column per second.
+const RATE_CHART_WIDTH: u16 = 20;
+
+/// The size of the screen drawn to with `--headless`.
+const HEADLESS_WIDTH: u16 = 80;
+const HEADLESS_HEIGHT: u16 = 24;
+
+/// The most pinned lines shown at the top of the view before the panel stops growing.
+const MAX_PINNED_ROWS: usize = 5;
+
+/// The most rows of the hex dump shown, which is enough for 128 bytes.
+const MAX_HEX_ROWS: usize = 8;
+
+/// The terminal device the UI is drawn to, which stays attached to the terminal while stdin and
+/// stdout are redirected. On Windows this is the console's output buffer.
+#[cfg(not(windows))]
+const TTY: &str = "/dev/tty";
+#[cfg(windows)]
+const TTY: &str = "CONOUT$";
+
+/// Draw a frame of the UI. Decorations such as highlighting are left out when `decorate` is
+/// false, for terminals struggling to keep up.
+fn draw_frame(frame: &mut Frame, app: &mut App, ui: &mut Ui, decorate: bool) {
+    ui.highlighter.update(&app.lines);
+    let area = frame.area();
+    let pinned_height = if app.show_pinned && !app.pinned.is_empty() {
+        app.pinned.len().min(MAX_PINNED_ROWS) as u16 + 1
+    } else {
+        0
+    };
 
-    fn scroll_to(&mut self, position: usize) {
-        self.scroll = position
+    // One row per level and for lines without one, plus one for each pane's filter
+    let filtered_panes = app.panes.iter().filter(|pane| pane.is_filtered()).count();
+    let levels_height = match app.levels {
+        Some(_) => (level::Level::ALL.len() + 1 + filtered_panes) as u16 + 1,
+        None => 0,
+    };
+
+    let hex_line = app.pane().cursor_line().filter(|&idx| app.show_hex && idx < app.lines.len());
+    let hex_height = match hex_line {
+        Some(idx) => hex::bytes(&app.lines[idx]).len().div_ceil(hex::BYTES_PER_ROW).clamp(1, MAX_HEX_ROWS) as u16 + 1,
+        None => 0,
+    };
+
+    // Create a layout with main content and status bar
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(pinned_height), // Pinned lines
+            Constraint::Min(1),     // Main content
+            Constraint::Length(hex_height), // Hex dump of the cursor line
+            Constraint::Length(levels_height), // Level histogram
+            Constraint::Length(if app.comparison.is_some() { 1 } else { 0 }), // Comparison panel
+            Constraint::Length(1),  // Status bar
+        ].as_ref())
+        .split(area);
+
+    if pinned_height > 0 {
+        let pinned: Vec<ListItem> = app.pinned.iter()
+            .filter_map(|idx| app.lines.get(*idx))
+            .map(|line| {
+                let spans = vec![ratatui::text::Span::raw(line.text.clone())];
+                ListItem::new(ratatui::text::Line::from(render::sanitize(spans, app.tab_width, app.control_chars)))
+            })
+            .collect();
+        frame.render_widget(
+            List::new(pinned)
+                .style(Style::default().fg(Color::Magenta))
+                .block(Block::default().borders(Borders::BOTTOM).title("Pinned")),
+            chunks[0],
+        );
     }
 
-    fn scroll_up(&mut self, amount: usize) {
-        self.scroll = self.scroll.saturating_sub(amount);
+    // The sources panel takes the right of the main area while it's open
+    let main_area = if let Mode::Sources = app.mode {
+        let width = app.sources.iter().map(|name| name.width()).max().unwrap_or(0) as u16 + 16;
+        let split = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(0), Constraint::Length(width.min(chunks[1].width / 3))])
+            .split(chunks[1]);
+        draw_sources(frame, app, split[1]);
+        split[0]
+    } else {
+        chunks[1]
+    };
+
+    if let Some(diff) = &mut app.diff {
+        draw_diff(frame, diff, &app.lines, &app.sources, main_area);
+    } else {
+        // Render each pane, each with its own filter, search and position
+        let pane_areas = Layout::default()
+            .direction(if app.side_by_side { Direction::Horizontal } else { Direction::Vertical })
+            .constraints(vec![Constraint::Ratio(1, app.panes.len() as u32); app.panes.len()])
+            .split(main_area);
+        for (pane_idx, &area) in pane_areas.iter().enumerate() {
+            // Once the screen is split, title each pane with its filter and mark the
+            // focused one
+            let area = if app.panes.len() > 1 {
+                let filter = app.panes[pane_idx].describe_filters();
+                let title = if filter.is_empty() { " all ".to_string() } else { format!(" {} ", filter) };
+                let style = if pane_idx == app.focus {
+                    Style::default().fg(Color::Yellow).bold()
+                } else {
+                    Style::default().fg(Color::DarkGray)
+                };
+                let block = Block::default().borders(Borders::TOP).border_style(style).title(title);
+                frame.render_widget(&block, area);
+                block.inner(area)
+            } else {
+                area
+            };
+
+            // Keep the table's header row in view, unless it's already the top row
+            let pane = &app.panes[pane_idx];
+            let header = app.table.as_ref().and_then(|table| {
+                let (id, text) = table.header.as_ref()?;
+                let top = pane.view.rows.get(pane.scroll).map(|row| app.lines[row.idx].id);
+                (top != Some(*id) && area.height > 1).then_some((table, text))
+            });
+            let area = if let Some((table, text)) = header {
+                let spans = table.layout(text, &[ratatui::text::Span::raw(text.clone())]);
+                frame.render_widget(
+                    Paragraph::new(Line::from(spans)).style(Style::default().bold().underlined()),
+                    Rect { height: 1, ..area },
+                );
+                Rect { y: area.y + 1, height: area.height - 1, ..area }
+            } else {
+                area
+            };
+            let view_height = area.height as usize;
+            app.panes[pane_idx].fit_viewport(view_height);
+            let pane = &app.panes[pane_idx];
+
+            // Show how far into the view the lines on screen are once they don't all fit,
+            // taking the rightmost column from the lines
+            let scrollbar = (app.show_scrollbar && pane.visible_len() > view_height && area.width > 1).then(|| {
+                let state = ratatui::widgets::ScrollbarState::new(pane.visible_len().saturating_sub(view_height))
+                    .position(pane.scroll)
+                    .viewport_content_length(view_height);
+                (Rect { x: area.right() - 1, width: 1, ..area }, state)
+            });
+            let area = if scrollbar.is_some() { Rect { width: area.width - 1, ..area } } else { area };
+
+            // Create a temporary vector of lines while holding the lock
+            // Only lines inside the viewport are rendered so frame time doesn't grow with the
+            // size of the buffer.
+            let window_end = (pane.scroll + view_height).min(pane.view.len());
+            let window = &pane.view.rows[pane.scroll.min(window_end)..window_end];
+            let matches = pane.search.matches();
+            let selection = app.selection.as_ref().filter(|_| matches!(app.mode, Mode::Block) && pane_idx == app.focus);
+            let items: Vec<ListItem> = window.iter()
+                .enumerate()
+                .map(|(i, row)| {
+                    let line_idx = row.idx;
+                    let source = app.lines[line_idx].source;
+                    let line = &app.lines[line_idx].text;
+                    // Get all matches for this line. They're ordered by line so we can binary
+                    // search for the first one.
+                    let first_match = matches.partition_point(|(idx, _, _)| *idx < line_idx);
+                    let mut others = Vec::new();
+                    let mut current = Vec::new();
+                    let line_matches = matches[first_match..].iter()
+                        .take_while(|(idx, _, _)| *idx == line_idx)
+                        .enumerate()
+                        .map(|(i, m)| (first_match + i, m));
+                    for (match_idx, &(_, start, end)) in line_matches {
+                        if match_idx == pane.current_match {
+                            current.push((start, end));
+                        } else {
+                            others.push((start, end));
+                        }
+                    }
+                    // Highlight the matches, making the current one stand out
+                    let mut spans = vec![ratatui::text::Span::raw(line.to_string())];
+                    spans = render::patch_ranges(spans, &others, Style::default().bg(Color::DarkGray).fg(Color::White));
+                    spans = render::patch_ranges(spans, &current, Style::default().bg(Color::Yellow).fg(Color::Black));
+
+                    // Underline URLs so it's clear `o` will open them
+                    spans = render::patch_ranges(spans, &url::find(line), Style::default().underlined());
+                    spans = rules::apply(&ui.rules, line, spans);
+                    spans = rules::paint(&app.scripts.colorize(line), spans);
+                    spans = groups::apply(&app.groups, line, spans);
+                    if app.highlight {
+                        spans = ui.highlighter.highlight(line, spans);
+                    }
+                    // Cut the line down to what the filter matched, or else the search, like
+                    // `grep -o`, leaving lines with nothing to cut to whole
+                    let fragments = if app.only_matching {
+                        let ranges = pane.matched_ranges(line);
+                        if ranges.is_empty() {
+                            let mut searched = [others.as_slice(), current.as_slice()].concat();
+                            searched.sort_unstable();
+                            searched
+                        } else {
+                            ranges
+                        }
+                    } else {
+                        Vec::new()
+                    };
+                    if !fragments.is_empty() {
+                        spans = render::extract(spans, &fragments, ratatui::text::Span::styled(" │ ", Style::default().fg(Color::DarkGray)));
+                    } else if let Some((fields, selected)) = app.fields.as_ref().map(|fields| (fields, fields.select(line))) {
+                        // Like `cut`, a line without any of the fields is left whole
+                        if !selected.is_empty() {
+                            spans = render::extract(spans, &selected, ratatui::text::Span::raw(fields.separator()));
+                        }
+                    } else if let Some(table) = &app.table {
+                        spans = table.layout(line, &spans);
+                    }
+                    spans = render::sanitize(spans, app.tab_width, app.control_chars);
+                    if let Some(selection) = selection {
+                        let (first, last) = selection.rows(pane.cursor);
+                        if (first..=last).contains(&(pane.scroll + i)) {
+                            let text: String = spans.iter().map(|span| span.content.as_ref()).collect();
+                            spans = render::patch_ranges(spans, &[selection.range(&text)], Style::default().reversed());
+                        }
+                    }
+
+                    match row.fold {
+                        view::Fold::None => {}
+                        view::Fold::Duplicates(count) => spans.push(ratatui::text::Span::styled(
+                            format!(" ×{}", count),
+                            Style::default().fg(Color::Cyan).italic(),
+                        )),
+                        view::Fold::Block(hidden) => spans.push(ratatui::text::Span::styled(
+                            format!(" [+{} lines]", hidden),
+                            Style::default().fg(Color::Cyan).italic(),
+                        )),
+                    }
+
+                    // Like `uniq -c`, how often each distinct line occurs goes in a column before it
+                    if let Some(occurrences) = pane.view.occurrences(line_idx) {
+                        spans.insert(0, ratatui::text::Span::styled(
+                            format!("{:>7} ", occurrences.count),
+                            Style::default().fg(Color::Cyan),
+                        ));
+                    }
+
+                    if app.picking || !app.marks.is_empty() {
+                        let marked = app.marks.contains(&app.lines[line_idx].id);
+                        spans.insert(0, ratatui::text::Span::styled(
+                            if marked { "▶ " } else { "  " },
+                            Style::default().fg(Color::Magenta).bold(),
+                        ));
+                    }
+
+                    // Once there's more than one source, tag each line with where it came from
+                    if app.sources.len() > 1 {
+                        spans.insert(0, ratatui::text::Span::styled(
+                            format!("[{}] ", app.sources[source]),
+                            source_style(source),
+                        ));
+                    }
+
+                    if app.show_timestamps {
+                        let arrived = chrono::DateTime::<chrono::Local>::from(app.lines[line_idx].arrived);
+                        spans.insert(0, ratatui::text::Span::styled(
+                            arrived.format("%H:%M:%S%.3f ").to_string(),
+                            Style::default().fg(Color::DarkGray),
+                        ));
+                    }
+
+                    let line = ratatui::text::Line::from(spans);
+                    let mut rows = if app.wrap { render::wrap(line, area.width as usize) } else { vec![line] };
+                    // Mark a stall, or the boundary between batches, with a rule above the line
+                    let previous = (pane.scroll + i).checked_sub(1).and_then(|row| pane.view.line_index(row));
+                    if let Some(gap) = app.gap.zip(previous).and_then(|(min, previous)| {
+                        gap_between(&app.lines[previous], &app.lines[line_idx]).filter(|gap| *gap > min)
+                    }) {
+                        let label = format!("──── {} ", format_gap(gap));
+                        let fill = (area.width as usize).saturating_sub(label.width());
+                        rows.insert(0, ratatui::text::Line::styled(
+                            format!("{}{}", label, "─".repeat(fill)),
+                            Style::default().fg(Color::DarkGray),
+                        ));
+                    }
+                    // Separate the groups of lines shown with context, like grep
+                    if row.gap {
+                        rows.insert(0, ratatui::text::Line::styled("--", Style::default().fg(Color::DarkGray)));
+                    }
+                    let item = ListItem::new(rows);
+                    if app.pinned.contains(&line_idx) {
+                        item.style(Style::default().fg(Color::Magenta))
+                    } else if app.anomalies.as_ref().is_some_and(|anomalies| anomalies.is_unusual(&app.lines[line_idx].text)) {
+                        item.style(Style::default().bg(Color::Indexed(52)))
+                    } else if row.context {
+                        item.style(Style::default().dim())
+                    } else {
+                        item
+                    }
+                })
+                .collect();
+
+            let list = List::new(items)
+                .style(Style::default())
+                .highlight_style(Style::default().bold().bg(Color::Indexed(236)));
+
+            frame.render_stateful_widget(
+                list,
+                area,
+                &mut ratatui::widgets::ListState::default()
+                    .with_selected(Some(pane.cursor - pane.scroll)),
+            );
+
+            if let Some((scrollbar_area, mut state)) = scrollbar {
+                frame.render_stateful_widget(
+                    ratatui::widgets::Scrollbar::new(ratatui::widgets::ScrollbarOrientation::VerticalRight)
+                        .begin_symbol(None)
+                        .end_symbol(None)
+                        .track_style(Style::default().fg(Color::DarkGray))
+                        .thumb_style(Style::default().fg(Color::Gray)),
+                    scrollbar_area,
+                    &mut state,
+                );
+            }
+        }
     }
 
-    fn scroll_down(&mut self, amount: usize, max_scroll: usize) {
-        self.scroll = (self.scroll + amount).min(max_scroll);
+    if let Some(line) = hex_line.and_then(|idx| app.lines.get(idx)) {
+        draw_hex(frame, hex::bytes(line), chunks[2]);
     }
 
-    fn len(&self) -> usize {
-        self.lines.lock().unwrap().len()
+    if let Some(levels) = &app.levels {
+        draw_levels(frame, levels, &app.panes, app.tally.as_ref().zip(app.last_session.as_ref()), chunks[3]);
     }
 
-    fn update_search(&mut self) {
-        if self.search_query.is_empty() {
-            self.matches.clear();
-            return;
+    if let Some(comparison) = &app.comparison {
+        let ratio = comparison
+            .ratio()
+            .map(|r| format!("{:.2}%", r * 100.0))
+            .unwrap_or_else(|| "-".to_string());
+        let summary = Line::from(vec![
+            ratatui::text::Span::styled(format!(" {} ", comparison.a), Style::default().fg(Color::Green)),
+            ratatui::text::Span::raw(format!("{}  ", comparison.count_a)),
+            ratatui::text::Span::styled(format!("{} ", comparison.b), Style::default().fg(Color::Red)),
+            ratatui::text::Span::raw(format!("{}  ratio {} ", comparison.count_b, ratio)),
+        ]);
+        let panel = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Length(summary.width() as u16), Constraint::Min(0)])
+            .split(chunks[4]);
+        frame.render_widget(Paragraph::new(summary), panel[0]);
+        // The trend changes every second, so leave it out on slow terminals
+        if decorate {
+            frame.render_widget(
+                Sparkline::default()
+                    .data(comparison.trend(panel[1].width as usize))
+                    .style(Style::default().fg(Color::Yellow)),
+                panel[1],
+            );
         }
+    }
+
+    if let (Mode::Frequency, Some(frequencies)) = (app.mode, &app.frequencies) {
+        let popup = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(20), Constraint::Percentage(60), Constraint::Percentage(20)])
+            .split(Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(10), Constraint::Percentage(80), Constraint::Percentage(10)])
+                .split(chunks[1])[1])[1];
+        let items: Vec<ListItem> = frequencies.top(freq::MAX_SHOWN)
+            .into_iter()
+            .map(|(template, count)| ListItem::new(format!("{:>8}  {}", count, template)))
+            .collect();
+        frame.render_widget(Clear, popup);
+        frame.render_stateful_widget(
+            List::new(items)
+                .block(Block::default().borders(Borders::ALL).title(" Most frequent messages "))
+                .highlight_style(Style::default().bold().bg(Color::Indexed(236))),
+            popup,
+            &mut ratatui::widgets::ListState::default().with_selected(Some(frequencies.selected)),
+        );
+    }
 
-        if let Ok(lines) = self.lines.lock() {
-            self.matches.clear();
-            for (line_idx, line) in lines.iter().enumerate() {
-                for (match_idx, _) in line.match_indices(&self.search_query) {
-                    self.matches.push((line_idx, match_idx, match_idx + self.search_query.len()));
+    if let (Mode::Words, Some(words)) = (app.mode, &app.words) {
+        draw_words(frame, words, chunks[1]);
+    }
+
+    if let (Mode::Captures, Some(captures), Some(regex)) = (app.mode, &app.captures, app.pane().search.regex()) {
+        draw_captures(frame, captures, regex, chunks[1]);
+    }
+
+    if let (Mode::Query, Some(query)) = (app.mode, &app.query) {
+        let results = &query.results;
+        let rows = match results.rows.len() {
+            1 => "1 row".to_string(),
+            n => format!("{} rows", n),
+        };
+        let title = format!(" {} ({}) ", query.sql, rows);
+        draw_table(frame, &title, &results.columns, &results.rows, results.selected, chunks[1]);
+    }
+
+    if let (Mode::Menu, Some(menu)) = (app.mode, &app.menu) {
+        draw_menu(frame, menu, chunks[1]);
+    }
+
+    if app.keymap.is_pending() {
+        draw_key_hints(frame, &app.keymap, chunks[1]);
+    }
+
+    // Render status bar. While a command is being typed it takes the place of everything on
+    // the left
+    let value = |field| status_field(app, ui, field);
+    let left: Vec<ratatui::text::Span> = match (app.mode, &app.message) {
+        (Mode::Command, _) => vec![format!(" {}  :{}", app.mode.status_text(), app.command).into()],
+        (Mode::Highlight, _) => vec![format!(" {}  #{}", app.mode.status_text(), app.command).into()],
+        (Mode::ConfirmQuit, Some(message)) => vec![format!(" {}  {}", app.mode.status_text(), message).into()],
+        _ => {
+            let mut spans = Vec::new();
+            for span in ui.status.left.iter().filter_map(|segment| segment.render(&value)) {
+                if !spans.is_empty() {
+                    spans.push(" ".into());
                 }
+                spans.push(span);
             }
+            spans
         }
+    };
 
-        // TODO: accept a current position and return the first search result after it so we can
-        // scroll directly to it.
+    // A recent alert turns the status bar red for a moment, to catch the eye
+    let status_bg = if app.alerts.flashing() { Color::Red } else { Color::DarkGray };
+    frame.render_widget(
+        Paragraph::new(Line::from(left))
+            .style(Style::default().bg(status_bg)),
+        chunks[5]
+    );
+
+    // Segments on the right of the status bar, with the rate chart at the very end
+    let chart_width = if app.show_rate_chart && decorate { RATE_CHART_WIDTH } else { 0 };
+    let status_right = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(chart_width)])
+        .split(chunks[5]);
+    if chart_width > 0 {
+        frame.render_widget(
+            Sparkline::default()
+                .data(app.rate.history(chart_width as usize))
+                .style(Style::default().fg(Color::Cyan).bg(status_bg)),
+            status_right[1],
+        );
+    }
+    let mut right = Vec::new();
+    for span in ui.status.right.iter().filter_map(|segment| segment.render(&value)) {
+        if !right.is_empty() {
+            right.push("  ".into());
+        }
+        right.push(span);
+    }
+    if !right.is_empty() {
+        right.push(" ".into());
+        frame.render_widget(
+            Paragraph::new(Line::from(right))
+                .alignment(ratatui::layout::Alignment::Right),
+            status_right[0]
+        );
     }
+}
 
-    fn next_match(&mut self) {
-        if !self.matches.is_empty() {
-            self.current_match = (self.current_match + 1) % self.matches.len();
-            if let Some((line_idx, _, _)) = self.matches.get(self.current_match) {
-                self.scroll = *line_idx;
-                self.mode = Mode::Normal;
-            }
+/// The text of a field in the status bar, empty if there's nothing to show.
+fn status_field(app: &App, ui: &Ui, field: status::Field) -> String {
+    let pane = app.pane();
+    let count = |n: usize| if n == 0 { String::new() } else { n.to_string() };
+    match field {
+        status::Field::Mode => app.mode.status_text().to_string(),
+        status::Field::Paused if app.paused => format!("PAUSED +{}", app.held.len()),
+        status::Field::Paused => String::new(),
+        status::Field::Search => pane.search_query.clone(),
+        status::Field::Filter => pane.describe_filters(),
+        status::Field::Message => app.message.clone().unwrap_or_default(),
+        status::Field::Match if pane.search.matches().is_empty() => String::new(),
+        status::Field::Match => (pane.current_match + 1).to_string(),
+        status::Field::Matches if pane.search.matches().is_empty() => String::new(),
+        status::Field::Matches => pane.search.matches().len().to_string(),
+        status::Field::Read => (app.next_id - 1).to_string(),
+        status::Field::Buffered => app.lines.len().to_string(),
+        status::Field::Filtered => pane.visible_len().to_string(),
+        status::Field::Counters => app.counters.describe(),
+        status::Field::Marked => count(app.marks.len()),
+        status::Field::Source => pane
+            .cursor_line()
+            .and_then(|idx| app.lines.get(idx))
+            .map(|line| app.sources[line.source].clone())
+            .unwrap_or_default(),
+        status::Field::Sampling => {
+            let mut samplers: Vec<_> = app.samplers.iter().collect();
+            samplers.sort_unstable_by_key(|(source, _)| **source);
+            samplers
+                .into_iter()
+                .map(|(&source, sampler)| {
+                    format!("{} {} ({} dropped)", app.sources[source], sampler.policy.describe(), sampler.dropped)
+                })
+                .collect::<Vec<_>>()
+                .join("  ")
         }
+        status::Field::Output if app.output == options::Output::Filtered => String::new(),
+        status::Field::Output => app.output.name().to_string(),
+        status::Field::Language => ui
+            .highlighter
+            .language()
+            .filter(|_| app.highlight)
+            .map(str::to_string)
+            .unwrap_or_default(),
+        status::Field::Rate if app.show_rate => app.rate.per_second().to_string(),
+        status::Field::Elapsed if app.show_elapsed => app::format_elapsed(app.session_start.elapsed().as_secs()),
+        status::Field::Clock if app.show_clock => chrono::Local::now().format("%H:%M:%S").to_string(),
+        status::Field::Rate | status::Field::Elapsed | status::Field::Clock => String::new(),
+        // Where the cursor is in the view, as in `42% 1234/56789`
+        status::Field::Position if pane.visible_len() == 0 => String::new(),
+        status::Field::Position => format!(
+            "{}% {}/{}",
+            (pane.cursor + 1) * 100 / pane.visible_len(),
+            pane.cursor + 1,
+            pane.visible_len()
+        ),
     }
+}
 
-    fn prev_match(&mut self) {
-        if !self.matches.is_empty() {
-            self.current_match = self.current_match.checked_sub(1).unwrap_or(self.matches.len() - 1);
-            if let Some((line_idx, _, _)) = self.matches.get(self.current_match) {
-                self.scroll = *line_idx;
-                self.mode = Mode::Normal;
+/// Press `keys` with nothing but an off-screen buffer to draw to, then print the state of the
+/// session followed by the final screen, for scripted tests and reproducing bugs exactly.
+fn headless(mut app: App, mut ui: Ui, keys: &[app::key::KeyEvent], options: &KeyOptions, yank_to: cli::YankTarget) -> anyhow::Result<()> {
+    let mut terminal = Terminal::new(TestBackend::new(HEADLESS_WIDTH, HEADLESS_HEIGHT))?;
+    let mut frame = |app: &mut App| {
+        while !app.incoming.is_empty() {
+            app.ingest();
+        }
+        app.refresh();
+        app.evict();
+        terminal.draw(|frame| draw_frame(frame, app, &mut ui, true)).map(|_| ())
+    };
+    frame(&mut app)?;
+    for key in keys {
+        if app.handle_key(*key, options) {
+            break;
+        }
+        yank(&mut app, yank_to);
+        frame(&mut app)?;
+    }
+
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    for (name, value) in app.snapshot() {
+        writeln!(stdout, "{}={}", name, value)?;
+    }
+    writeln!(stdout)?;
+    let buffer = terminal.backend().buffer();
+    for y in 0..buffer.area.height {
+        let row: String = (0..buffer.area.width).map(|x| buffer[(x, y)].symbol()).collect();
+        writeln!(stdout, "{}", row.trim_end())?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Stream the lines passing the filter to `output`, or stdout if not given, as they're read,
+/// until stdin is closed, for when there's no terminal to draw on. With `-q` nothing is printed.
+/// Returns how many lines passed the filter.
+async fn pass_through(
+    app: &mut App,
+    mut stdin: source::Stdin,
+    args: &cli::Args,
+    output: Option<File>,
+) -> anyhow::Result<usize> {
+    let mut out: io::BufWriter<Box<dyn Write>> = io::BufWriter::new(match output {
+        Some(file) => Box::new(file),
+        None => Box::new(io::stdout().lock()),
+    });
+    let mut matched = 0;
+    let mut closed = false;
+    while !closed {
+        tokio::select! {
+            Some(line) = app.incoming.recv() => {
+                if let Some(line) = app.admit(line) {
+                    app.push_line(line);
+                }
+            }
+            _ = &mut stdin.task => closed = true,
+        }
+        // Take whatever else has arrived, and once stdin is closed everything that's left
+        while let Ok(line) = app.incoming.try_recv() {
+            if let Some(line) = app.admit(line) {
+                app.push_line(line);
+            }
+        }
+        // Lines aren't kept, as there's nothing to show them in
+        for line in std::mem::take(&mut app.lines) {
+            if !app.pane().passes(&line, app.table.as_ref()) {
+                continue;
+            }
+            matched += 1;
+            if !args.quiet {
+                writeln!(out, "{}", app.annotated(&line, &args.annotate))?;
             }
         }
+        out.flush()?;
+    }
+    Ok(matched)
+}
+
+/// Open `$VISUAL` or `$EDITOR` at a location, handing it the terminal until it exits.
+fn edit(location: &quickfix::Location) -> io::Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let tty = OpenOptions::new().read(true).write(true).open(TTY)?;
+    restore_terminal()?;
+    // The path is passed as an argument rather than spliced into the command so it isn't
+    // interpreted by the shell
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} +{} \"$1\"", editor, location.line))
+        .arg("sh")
+        .arg(&location.path)
+        .stdin(tty.try_clone()?)
+        .stdout(tty.try_clone()?)
+        .stderr(tty.try_clone()?)
+        .status();
+    enable_raw_mode()?;
+    execute!(&tty, EnterAlternateScreen, EnableBracketedPaste)?;
+    match status? {
+        status if status.success() => Ok(()),
+        status => Err(io::Error::other(format!("{} exited with {}", editor, status))),
     }
 }
 
+/// Ring the terminal bell, which most terminals and multiplexers pass on as an urgency hint or
+/// a marker on the tab or window, even when it isn't focused.
+fn bell() -> io::Result<()> {
+    let mut tty = OpenOptions::new().write(true).open(TTY)?;
+    tty.write_all(b"\x07")?;
+    tty.flush()
+}
+
 fn restore_terminal() -> Result<(), io::Error> {
     disable_raw_mode()?;
-    let mut tty = OpenOptions::new().write(true).open("/dev/tty")?;
-    execute!(tty, LeaveAlternateScreen)
+    let mut tty = OpenOptions::new().write(true).open(TTY)?;
+    execute!(tty, DisableBracketedPaste, LeaveAlternateScreen)
+}
+
+/// Draw the most frequent words in a popup over the main view, most frequent first, with the
+/// commonest in bold and brighter colours.
+fn draw_words(frame: &mut Frame, words: &words::Words, area: Rect) {
+    let popup = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(20), Constraint::Percentage(60), Constraint::Percentage(20)])
+        .split(Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(10), Constraint::Percentage(80), Constraint::Percentage(10)])
+            .split(area)[1])[1];
+    let top = words.top(words::MAX_SHOWN);
+    let most = top.first().map_or(1, |(_, count)| *count);
+    let mut spans = Vec::new();
+    for (i, (word, count)) in top.iter().enumerate() {
+        let style = match count * 3 / most.max(1) {
+            3 => Style::default().fg(Color::Yellow).bold(),
+            2 => Style::default().fg(Color::Cyan).bold(),
+            1 => Style::default().fg(Color::White),
+            _ => Style::default().fg(Color::Gray),
+        };
+        let style = if i == words.selected { style.reversed() } else { style };
+        spans.push(ratatui::text::Span::styled(word.to_string(), style));
+        spans.push(ratatui::text::Span::raw("  "));
+    }
+    let title = match top.get(words.selected) {
+        Some((word, count)) => format!(" Most frequent words: {} ×{} ", word, count),
+        None => " Most frequent words ".to_string(),
+    };
+    frame.render_widget(Clear, popup);
+    frame.render_widget(
+        Paragraph::new(Line::from(spans))
+            .wrap(ratatui::widgets::Wrap { trim: true })
+            .block(Block::default().borders(Borders::ALL).title(title)),
+        popup,
+    );
+}
+
+/// Draw the values captured by the search's groups in a popup over the main view, a column for
+/// each group, with the most frequent first.
+fn draw_captures(frame: &mut Frame, captures: &captures::Captures, regex: &regex::Regex, area: Rect) {
+    let columns: Vec<String> = ["count".to_string()].into_iter().chain(captures::Captures::headings(regex)).collect();
+    let rows: Vec<Vec<String>> = captures
+        .top(captures::MAX_SHOWN)
+        .into_iter()
+        .map(|(values, count)| [count.to_string()].into_iter().chain(values.iter().cloned()).collect())
+        .collect();
+    draw_table(frame, &format!(" Captured by {} ", regex.as_str()), &columns, &rows, captures.selected, area);
+}
+
+/// Draw rows of values in aligned columns under a header in a popup over the main view.
+fn draw_table(frame: &mut Frame, title: &str, columns: &[String], rows: &[Vec<String>], selected: usize, area: Rect) {
+    let popup = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(20), Constraint::Percentage(60), Constraint::Percentage(20)])
+        .split(Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(10), Constraint::Percentage(80), Constraint::Percentage(10)])
+            .split(area)[1])[1];
+    let mut widths: Vec<usize> = columns.iter().map(|column| column.width()).collect();
+    for row in rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.width());
+        }
+    }
+    let line = |values: &[String]| {
+        let cells: Vec<String> = values.iter().zip(&widths).map(|(value, width)| format!("{:<1$}", value, width)).collect();
+        cells.join("  ")
+    };
+    let items: Vec<ListItem> = rows.iter().map(|row| ListItem::new(line(row))).collect();
+    let block = Block::default().borders(Borders::ALL).title(title.to_string());
+    let inner = block.inner(popup);
+    frame.render_widget(Clear, popup);
+    frame.render_widget(block, popup);
+    frame.render_widget(Paragraph::new(line(columns)).style(Style::default().bold().underlined()), Rect { height: 1, ..inner });
+    frame.render_stateful_widget(
+        List::new(items).highlight_style(Style::default().bold().bg(Color::Indexed(236))),
+        Rect { y: inner.y + 1, height: inner.height.saturating_sub(1), ..inner },
+        &mut ratatui::widgets::ListState::default().with_selected(Some(selected)),
+    );
+}
+
+/// Draw the sources, with how many lines each has sent, marking those whose lines the focused
+/// pane shows.
+fn draw_sources(frame: &mut Frame, app: &App, area: Rect) {
+    let items: Vec<ListItem> = app.sources.iter()
+        .enumerate()
+        .map(|(source, name)| {
+            let shown = !app.pane().hidden_sources.contains(&source);
+            let count = app.numbers.get(source).copied().unwrap_or(0);
+            ListItem::new(Line::from(vec![
+                ratatui::text::Span::raw(if shown { "[x] " } else { "[ ] " }),
+                ratatui::text::Span::styled(name.clone(), if shown { source_style(source) } else { Style::default().fg(Color::DarkGray) }),
+                ratatui::text::Span::styled(format!(" {}", count), Style::default().fg(Color::DarkGray)),
+            ]))
+        })
+        .collect();
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(
+        List::new(items)
+            .block(Block::default().borders(Borders::LEFT).title(" Sources "))
+            .highlight_style(Style::default().bold().bg(Color::Indexed(236))),
+        area,
+        &mut ratatui::widgets::ListState::default().with_selected(Some(app.selected_source)),
+    );
+}
+
+/// Draw the actions for the line under the cursor in a popup at the top right of the panes,
+/// where it covers the least of the lines.
+fn draw_menu(frame: &mut Frame, menu: &menu::Menu, area: Rect) {
+    let items: Vec<ListItem> = menu.actions.iter().map(|action| ListItem::new(action.label())).collect();
+    let width = menu.actions.iter().map(|action| action.label().width()).max().unwrap_or(0) as u16 + 4;
+    let width = width.min(area.width);
+    let popup = Rect::new(area.right() - width, area.y, width, (items.len() as u16 + 2).min(area.height));
+    frame.render_widget(Clear, popup);
+    frame.render_stateful_widget(
+        List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(" Line "))
+            .highlight_style(Style::default().bold().bg(Color::Indexed(236))),
+        popup,
+        &mut ratatui::widgets::ListState::default().with_selected(Some(menu.selected)),
+    );
+}
+
+/// Draw what each key that could come next in a bound sequence does, along the bottom of the
+/// panes.
+fn draw_key_hints(frame: &mut Frame, keymap: &keymap::Keymap, area: Rect) {
+    let hints = keymap.hints();
+    let height = (hints.len() as u16 + 2).min(area.height);
+    let popup = Rect::new(area.x, area.bottom() - height, area.width, height);
+    let width = hints.iter().map(|(key, _)| key.width()).max().unwrap_or(0);
+    let items: Vec<ListItem> = hints
+        .into_iter()
+        .map(|(key, does)| {
+            ListItem::new(Line::from(vec![
+                ratatui::text::Span::styled(format!("{:>width$}", key), Style::default().fg(Color::Yellow).bold()),
+                ratatui::text::Span::raw(format!("  {}", does)),
+            ]))
+        })
+        .collect();
+    frame.render_widget(Clear, popup);
+    frame.render_widget(
+        List::new(items).block(Block::default().borders(Borders::TOP).title(format!(" {} ", keymap.typed()))),
+        popup,
+    );
+}
+
+/// Draw two sources side by side, with removed lines in red on the left, added lines in green on
+/// the right and changed lines in yellow on both sides.
+fn draw_diff(frame: &mut Frame, diff: &mut diff::Diff, lines: &[line::Line], sources: &[String], area: Rect) {
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)])
+        .split(area);
+    diff.height = columns[0].height.saturating_sub(1) as usize;
+    let window_end = (diff.scroll + diff.height).min(diff.rows.len());
+    let window = &diff.rows[diff.scroll.min(window_end)..window_end];
+
+    for (column, source, is_left) in [(columns[0], diff.left, true), (columns[1], diff.right, false)] {
+        let items: Vec<ListItem> = window
+            .iter()
+            .map(|row| {
+                let (idx, highlight) = if is_left {
+                    (row.left, diff::Change::Removed)
+                } else {
+                    (row.right, diff::Change::Added)
+                };
+                let text = idx.map(|idx| lines[idx].text.as_str()).unwrap_or_default();
+                let style = match row.change {
+                    diff::Change::Changed => Style::default().fg(Color::Yellow),
+                    change if change == highlight && is_left => Style::default().fg(Color::Red),
+                    change if change == highlight => Style::default().fg(Color::Green),
+                    _ => Style::default(),
+                };
+                ListItem::new(text).style(style)
+            })
+            .collect();
+        frame.render_widget(
+            List::new(items).block(Block::default().borders(Borders::TOP).title(format!(" {} ", sources[source]))),
+            column,
+        );
+    }
+}
+
+/// Draw a bar for the number of lines at each level, and for the number of rows passing each
+/// pane's filter, scaled to the largest.
+fn draw_levels(
+    frame: &mut Frame,
+    levels: &level::Histogram,
+    panes: &[pane::Pane],
+    history: Option<(&history::Tally, &history::Session)>,
+    area: Rect,
+) {
+    // How this session's totals compare to the last one's, when keeping a history
+    let delta = |now: u64, last: u64| format!(" ({:+} vs last)", now as i64 - last as i64);
+    let mut bars: Vec<(String, u64, Color, String)> = level::Level::ALL
+        .iter()
+        .map(|&level| {
+            let color = match level {
+                level::Level::Error => Color::Red,
+                level::Level::Warn => Color::Yellow,
+                level::Level::Info => Color::Green,
+                level::Level::Debug => Color::Blue,
+                level::Level::Trace => Color::DarkGray,
+            };
+            let change = history
+                .map(|(tally, last)| delta(tally.counts[level as usize], last.counts[level as usize]))
+                .unwrap_or_default();
+            (level.name().to_string(), levels.counts[level as usize], color, change)
+        })
+        .collect();
+    let change = history.map(|(tally, last)| delta(tally.unknown, last.unknown)).unwrap_or_default();
+    bars.push(("other".to_string(), levels.unknown, Color::Gray, change));
+    for pane in panes.iter().filter(|pane| pane.is_filtered()) {
+        bars.push((format!("/{}", pane.describe_filters()), pane.visible_len() as u64, Color::Cyan, String::new()));
+    }
+
+    let label_width = bars.iter().map(|(label, _, _, _)| label.width()).max().unwrap_or(0).min(20);
+    let change_width = bars.iter().map(|(_, _, _, change)| change.len()).max().unwrap_or(0);
+    let max = bars.iter().map(|(_, count, _, _)| *count).max().unwrap_or(0).max(1);
+    let bar_width = (area.width as usize).saturating_sub(label_width + 12 + change_width);
+    let rows: Vec<ListItem> = bars
+        .into_iter()
+        .map(|(label, count, color, change)| {
+            let len = (count as usize * bar_width).div_ceil(max as usize);
+            ListItem::new(Line::from(vec![
+                ratatui::text::Span::raw(format!(" {:<label_width$.label_width$} ", label)),
+                ratatui::text::Span::styled("█".repeat(len), Style::default().fg(color)),
+                ratatui::text::Span::raw(format!(" {}", count)),
+                ratatui::text::Span::styled(change, Style::default().fg(Color::DarkGray)),
+            ]))
+        })
+        .collect();
+    let title = match history {
+        Some((_, last)) => format!(
+            " Levels, last session {} for {}m with {} lines ",
+            last.started.format("%Y-%m-%d %H:%M"),
+            last.seconds / 60,
+            last.lines,
+        ),
+        None => " Levels ".to_string(),
+    };
+    frame.render_widget(
+        List::new(rows).block(Block::default().borders(Borders::TOP).title(title)),
+        area,
+    );
+}
+
+/// A hex dump of the line under the cursor, so bytes that aren't valid UTF-8 and are shown as
+/// `�` can still be inspected.
+fn draw_hex(frame: &mut Frame, bytes: &[u8], area: Rect) {
+    let rows = hex::dump(bytes);
+    let title = if rows.len() > area.height.saturating_sub(1) as usize {
+        format!(" Hex: first {} of {} bytes ", (area.height.saturating_sub(1) as usize * hex::BYTES_PER_ROW).min(bytes.len()), bytes.len())
+    } else {
+        format!(" Hex: {} bytes ", bytes.len())
+    };
+    let items: Vec<ListItem> = rows.into_iter().map(ListItem::new).collect();
+    frame.render_widget(List::new(items).block(Block::default().borders(Borders::TOP).title(title)), area);
+}
+
+/// A distinct colour for each source's label.
+fn source_style(source: usize) -> Style {
+    const COLORS: [Color; 6] = [Color::Cyan, Color::Green, Color::Magenta, Color::Blue, Color::Yellow, Color::Red];
+    Style::default().fg(COLORS[source % COLORS.len()])
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Exit if stdin is not a pipe
-    if io::stdin().is_terminal() {
+    let mut args = cli::Args::parse();
+    let mut config = config::load()?;
+    let profile = match &args.profile {
+        Some(name) => config.take_profile(name)?,
+        None => config::Profile::default(),
+    };
+    profile.apply(&mut args);
+    if args.timestamps && !args.annotate.contains(&options::Annotation::Time) {
+        args.annotate.insert(0, options::Annotation::Time);
+    }
+
+    // Exit if stdin is not a pipe, unless comparing two files or there's another source
+    let read_stdin = !io::stdin().is_terminal();
+    if !read_stdin && args.diff.len() < 2 && args.url.is_empty() && args.ws.is_empty() && args.listen.is_empty() && args.syslog.is_empty() && args.docker.is_empty() && args.kube.is_empty() && args.journal.is_none() {
         return Ok(());
     }
 
-    let mut app = App::new();
-    let lines = app.lines.clone();
-    
-    // Spawn an async task to read from stdin continuously
-    tokio::spawn(async move {
-        let stdin = tokio::io::stdin();
-        let reader = BufReader::new(stdin);
-        let mut lines_stream = reader.lines();
-        
-        while let Ok(Some(line)) = lines_stream.next_line().await {
-            if let Ok(mut lines_vec) = lines.lock() {
-                lines_vec.push(line);
-            }
+    // Open the export before touching the terminal so a bad path is reported plainly.
+    let exporter = match args.export_options() {
+        Some(opts) => {
+            let path = opts.path.clone();
+            let exporter = export::Exporter::open(opts)
+                .with_context(|| format!("failed to open export file {}", path.display()))?;
+            Some(Arc::new(Mutex::new(exporter)))
         }
-    });
+        None => None,
+    };
+
+    // Likewise the destination for the output, which takes stdout's place
+    let mut destination = dump::destination(args.output_fd, args.output_file.as_deref()).context(match args.output_fd {
+        Some(fd) => format!("can't write output to file descriptor {}", fd),
+        None => "failed to create output file".to_string(),
+    })?;
+    let tee = destination.is_some();
+    let output_is_terminal = match &destination {
+        Some(file) => file.is_terminal(),
+        None => io::stdout().is_terminal(),
+    };
+
+    let mut action_log = match &args.log_actions {
+        Some(path) => Some(
+            actions::ActionLog::create(path)
+                .with_context(|| format!("failed to create action log {}", path.display()))?,
+        ),
+        None => None,
+    };
+
+    if let Some(path) = &args.debug_log {
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("failed to create debug log {}", path.display()))?;
+        tracing_subscriber::fmt()
+            .with_writer(Mutex::new(file))
+            .with_ansi(false)
+            .with_max_level(tracing::Level::DEBUG)
+            .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+            .init();
+    }
+
+    // Only a terminal can be flooded by the output printed on exit
+    let confirm_quit_over = (output_is_terminal && args.max_output.is_none()).then_some(args.confirm_quit_over);
+
+    let mut app = App::new(args.max_lines, exporter);
+    app.join_on = args.join_on.clone();
+    app.picking = args.pick;
+    app.output = args.output;
+    if let Some(fd) = args.output_fd {
+        app.destination = format!("fd {}", fd);
+    } else if let Some(path) = &args.output_file {
+        app.destination = path.display().to_string();
+    }
+    app.show_timestamps = args.timestamps;
+    app.gap = args.gap;
+    app.only_matching = args.only_matching;
+    app.panes[0].set_search_all(args.search_all);
+    app.panes[0].set_unique(args.unique);
+    app.merge = args.merge;
+    app.panes[0].context = view::Context {
+        before: args.before_context.or(args.context).unwrap_or(0),
+        after: args.after_context.or(args.context).unwrap_or(0),
+    };
+    app.tab_width = args.tab_width as usize;
+    app.control_chars = args.control_chars;
+    if let Some(path) = &args.control {
+        app.control = Some(
+            control::listen(path).with_context(|| format!("failed to listen on {}", path.display()))?,
+        );
+    }
+    if let Some(dir) = config::plugins_dir().filter(|_| !args.no_plugins) {
+        app.plugins = plugin::load_all(&dir).map_err(|e| anyhow::anyhow!(e))?;
+    }
+    if let Some(dir) = config::scripts_dir().filter(|_| !args.no_plugins) {
+        app.scripts = script::Scripts::load_all(&dir).map_err(|e| anyhow::anyhow!(e))?;
+    }
+    let history_path = history::path().filter(|_| args.history);
+    if let Some(path) = &history_path {
+        let profile = args.profile.clone().unwrap_or_else(|| history::DEFAULT_PROFILE.to_string());
+        app.last_session = history::last(path, &profile);
+        app.tally = Some(history::Tally::new(profile));
+    }
+    app.presets = config.filters.clone();
+    app.keymap = keymap::Keymap::new(config.chord_timeout.map_or(keymap::DEFAULT_TIMEOUT, Duration::from_millis));
+    let leader = config.leader.as_deref().unwrap_or(config::DEFAULT_LEADER);
+    for binding in &config.bindings {
+        app.keymap
+            .bind(&binding.key, leader, binding.cmd.clone())
+            .map_err(|e| anyhow::anyhow!("config: bind '{}': {}", binding.key, e))?;
+    }
+    app.stopwords = config.stopwords.clone();
+    for rule in &config.alerts {
+        app.alerts.add(rule).map_err(|e| anyhow::anyhow!("config: alert '{}': {}", rule.pattern, e))?;
+    }
+    let mut ui = Ui {
+        rules: config
+            .highlights
+            .iter()
+            .map(rules::Rule::new)
+            .collect::<Result<_, _>>()
+            .map_err(|e| anyhow::anyhow!("config: highlight: {}", e))?,
+        status: status::Layout::new(config.status.left.as_deref(), config.status.right.as_deref())
+            .map_err(|e| anyhow::anyhow!("config: status: {}", e))?,
+        ..Ui::default()
+    };
+    if let Some(language) = &args.language {
+        ui.highlighter = highlight::Highlighter::new(language).map_err(|e| anyhow::anyhow!("--language: {}", e))?;
+    }
+    let stdin = read_stdin.then(|| source::spawn_stdin(app.sink(), app::STDIN_SOURCE, tee));
+    for path in &profile.open {
+        app.open(path).map_err(|e| anyhow::anyhow!("profile: {}", e))?;
+    }
+    for url in &args.url {
+        app.follow_url(url);
+    }
+    for url in &args.ws {
+        app.follow_websocket(url);
+    }
+    for container in &args.docker {
+        app.follow_container(container);
+    }
+    for target in &args.kube {
+        app.follow_pods(target);
+    }
+    for address in &args.syslog {
+        app.receive_syslog(address).map_err(|e| anyhow::anyhow!("--syslog: {}", e))?;
+    }
+    if let Some(unit) = &args.journal {
+        app.follow_journal(unit.as_deref());
+    }
+    for path in &args.listen {
+        app.listen(path).map_err(|e| anyhow::anyhow!("--listen: {}", e))?;
+    }
+    if !args.diff.is_empty() {
+        let sources = args
+            .diff
+            .iter()
+            .map(|path| app.open(path))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow::anyhow!("--diff: {}", e))?;
+        let (left, right) = match sources[..] {
+            [left, right] => (left, right),
+            [left] => (left, app::STDIN_SOURCE),
+            _ => unreachable!("clap allows one or two paths"),
+        };
+        app.diff = Some(diff::Diff::new(left, right));
+    }
+
+    if let Some(filter) = &args.filter {
+        app.pane_mut().filter = filter.clone();
+    }
+    for pattern in &args.alert {
+        app.run(command::Command::Alert(Some(pattern.clone()))).map_err(|e| anyhow::anyhow!("--alert: {}", e))?;
+    }
+    for pattern in &args.count {
+        app.run(command::Command::Count(Some(pattern.clone()))).map_err(|e| anyhow::anyhow!("--count: {}", e))?;
+    }
+    if let Some(query) = &args.search {
+        app.run(command::Command::Search(query.clone())).map_err(|e| anyhow::anyhow!("--search: {}", e))?;
+    }
+    if args.no_tail {
+        app.pane_mut().tailing = false;
+    }
+    if let Some(line) = args.line {
+        app.run(command::Command::Goto(line)).map_err(|e| anyhow::anyhow!("--line: {}", e))?;
+    }
+    for input in &args.cmd {
+        for input in command::split_commands(input) {
+            command::Command::parse(input)
+                .and_then(|command| app.run(command))
+                .map_err(|e| anyhow::anyhow!("--cmd '{}': {}", input.trim(), e))?;
+        }
+    }
+
+    if let Some(script) = &args.headless {
+        let keys = actions::parse_keys(script).map_err(|e| anyhow::anyhow!("--headless: {}", e))?;
+        if let Some(stdin) = stdin {
+            let _ = stdin.task.await;
+        }
+        let options = KeyOptions {
+            yank_context: args.yank_context,
+            collapse_duplicates: args.collapse_duplicates,
+            confirm_quit_over: None,
+        };
+        return headless(app, ui, &keys, &options, args.yank_to);
+    }
 
     // Set up terminal. We need to render directly to the tty device so we don't disrupt stderr and
     // stdout
-    // TODO: Make this work on Windows.
-    let tty = OpenOptions::new().write(true).open("/dev/tty")?;
+    let tty = match OpenOptions::new().write(true).open(TTY) {
+        Ok(tty) => tty,
+        // Without a terminal, as under cron or in CI, work like grep instead
+        Err(e) => match stdin {
+            Some(stdin) => {
+                tracing::debug!("no terminal ({}), passing input through", e);
+                let matched = pass_through(&mut app, stdin, &args, destination.take()).await?;
+                if let Some(exporter) = &app.exporter {
+                    if let Err(e) = exporter.lock().unwrap().finish() {
+                        eprintln!("carve: export failed: {}", e);
+                    }
+                }
+                if let Some(format) = &args.summary {
+                    eprintln!("{}", app.summary(format, matched, if args.quiet { 0 } else { matched }));
+                }
+                if args.quiet {
+                    std::process::exit(if matched > 0 { 0 } else { 1 });
+                }
+                return Ok(());
+            }
+            None => return Err(e).context("no terminal to draw on"),
+        },
+    };
     
-    // Replace panic handler to reset the terminal in case of panic.
+    // Replace panic handler to reset the terminal in case of panic, and to keep the panic in the
+    // session's logs where it won't scroll away with the restored terminal.
     let hook = std::panic::take_hook();
+    let action_log_path = args.log_actions.clone();
     std::panic::set_hook(Box::new(move |info| {
         if let Err(res) = restore_terminal() {
             eprintln!("failed to restore terminal: {}", res)
         }
+        let backtrace = std::backtrace::Backtrace::force_capture();
+        tracing::error!("{}\n{}", info, backtrace);
+        if let Some(path) = &action_log_path {
+            if let Err(e) = actions::record_panic(path, info, &backtrace) {
+                eprintln!("failed to write panic to {}: {}", path.display(), e);
+            }
+        }
         hook(info);
     }));
 
     enable_raw_mode()?;
-    execute!(tty.try_clone()?, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(tty.try_clone()?);
+    execute!(tty.try_clone()?, EnterAlternateScreen, EnableBracketedPaste)?;
+    let backend = backend::QuietBackend::new(CrosstermBackend::new(tty.try_clone()?));
     let mut terminal = Terminal::new(backend)?;
 
+    let key_options = KeyOptions {
+        yank_context: args.yank_context,
+        collapse_duplicates: args.collapse_duplicates,
+        confirm_quit_over,
+    };
+    let mut pacer = pacing::Pacer::new();
+    let mut input = false;
     loop {
-        terminal.draw(|frame| {
-            let area = frame.area();
-            // Create a temporary vector of lines while holding the lock
-            let view_height = area.height as usize;
-            let items: Vec<ListItem> = app.lines
-                .lock()
-                .map(|lines| {
-                    lines.iter()
-                        .filter(|line| {
-                            app.filter == "" || line.contains(&app.filter)
-                        })
-                        .enumerate()
-                        .map(|(idx, line)| {
-                            // Only process lines that are visible in the viewport
-                            if idx < app.scroll || idx >= app.scroll + view_height {
-                                return ListItem::new(ratatui::text::Line::raw(""));
-                            }
-                            let mut spans = Vec::new();
-                            let mut last_end = 0;
-
-                            // Get all matches for this line
-                            let line_matches: Vec<_> = app.matches.iter()
-                                .enumerate()
-                                .filter(|(_, (line_idx, _, _))| *line_idx == idx)
-                                .collect();
-
-                            for (match_idx, (_, start, end)) in line_matches {
-                                // Add non-matching text before this match
-                                if last_end < *start {
-                                    spans.push(ratatui::text::Span::raw(
-                                        line[last_end..*start].to_string()
-                                    ));
-                                }
-
-                                // Add the matching text with highlight
-                                let style = if match_idx == app.current_match {
-                                    Style::default().bg(ratatui::style::Color::Yellow)
-                                        .fg(ratatui::style::Color::Black)
-                                } else {
-                                    Style::default().bg(ratatui::style::Color::DarkGray)
-                                        .fg(ratatui::style::Color::White)
-                                };
-
-                                spans.push(ratatui::text::Span::styled(
-                                    line[*start..*end].to_string(),
-                                    style,
-                                ));
-                                last_end = *end;
-                            }
-
-                            // Add remaining text after last match
-                            if last_end < line.len() {
-                                spans.push(ratatui::text::Span::raw(
-                                    line[last_end..].to_string()
-                                ));
-                            }
-
-                            // If no matches were found, just show the plain line
-                            if spans.is_empty() {
-                                spans.push(ratatui::text::Span::raw(line.to_string()));
-                            }
-
-                            ListItem::new(ratatui::text::Line::from(spans))
-                        })
-                        .collect()
-                })
-                .unwrap_or_default();
+        app.ingest();
+        app.refresh();
+        app.evict();
+        input |= app.serve_control();
 
-            let list = List::new(items)
-                .style(Style::default())
-                .highlight_style(Style::default().bold());
-
-            // Create a layout with main content and status bar
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([
-                    Constraint::Min(1),     // Main content
-                    Constraint::Length(1),  // Status bar
-                ].as_ref())
-                .split(area);
-
-            // Render main content
-            frame.render_stateful_widget(
-                list,
-                chunks[0],
-                &mut ratatui::widgets::ListState::default().with_offset(app.scroll),
-            );
-
-            // Render status bar
-            let mode_text = format!(" {} ", app.mode.status_text());
-            
-            let status = Line::from(vec![
-                ratatui::text::Span::from(mode_text),
-                if !app.search_query.is_empty() {
-                    ratatui::text::Span::raw(format!(" [Search: {}]", app.search_query))
-                } else if !app.filter.is_empty() {
-                    ratatui::text::Span::raw(format!(" [Filter: {}]", app.filter))
-                } else {
-                    ratatui::text::Span::raw("")
-                },
-            ]);
+        // Catch up with keys typed ahead before drawing, and hold back frames that are only for
+        // new lines if the terminal is struggling to keep up.
+        let typed_ahead = input && event::poll(Duration::ZERO)?;
+        let draw = !typed_ahead && pacer.due(input);
+        let decorate = !pacer.is_slow();
+        let started = Instant::now();
 
-            frame.render_widget(
-                Paragraph::new(status)
-                    .style(Style::default().bg(Color::DarkGray)),
-                chunks[1]
-            );
-        })?;
+        if draw {
+            let _span = tracing::debug_span!("draw", decorate).entered();
+            terminal.draw(|frame| draw_frame(frame, &mut app, &mut ui, decorate))?;
+            pacer.drew(started.elapsed());
+        }
+        if app.alerts.take_ring() {
+            if let Err(e) = bell() {
+                app.message = Some(format!("Failed to ring the bell: {}", e));
+            }
+        }
 
         // Handle input
-        if event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                match (app.mode, key.code) {
-                    // Quit only works in normal mode
-                    (Mode::Normal, KeyCode::Char('q')) => break,
-                    
-                    // Esc always returns to tail mode
-                    (_, KeyCode::Esc) => app.mode = Mode::Normal,
-                    
-                    // Normal mode commands
-                    //(Mode::Normal, KeyCode::Char('/')) => {
-                    //    app.search_query.clear();
-                    //},
-                    (Mode::Normal, KeyCode::Char('n')) if !app.matches.is_empty() => app.next_match(),
-                    (Mode::Normal, KeyCode::Char('N')) if !app.matches.is_empty() => app.prev_match(),
-                    (Mode::Normal, KeyCode::Char('j')) => {
-                        let view_height = terminal.size()?.height as usize;
-                        if app.len() > view_height {
-                            app.scroll_down(1, app.len().saturating_sub(view_height));
-                        }
-                        app.tailing = false;
-                    },
-                    (Mode::Normal, KeyCode::Char('k')) => {
-                        let view_height = terminal.size()?.height as usize;
-                        if app.len() > view_height {
-                            app.scroll_up(1);
-                        }
-                        app.tailing = false;
-                    },
-                    (Mode::Normal, KeyCode::Char('d')) => {
-                        let view_height = terminal.size()?.height as usize;
-                        if app.len() > view_height {
-                            let amount = view_height / 2;
-                            app.scroll_down(amount, app.len().saturating_sub(view_height));
-                        }
-                        app.tailing = false;
-                    },
-                    (Mode::Normal, KeyCode::Char('u')) => {
-                        let view_height = terminal.size()?.height as usize;
-                        if app.len() > view_height {
-                            let amount = view_height / 2;
-                            app.scroll_up(amount);
-                        }
-                        app.tailing = false;
-                    },
-                    (Mode::Normal, KeyCode::Char('g')) => {
-                        app.scroll_to(0);
-                        app.tailing = false;
-                    },
-                    (Mode::Normal, KeyCode::Char('G')) => {
-                        let view_height = terminal.size()?.height as usize;
-                        app.scroll_to(app.len().saturating_sub(view_height));
-                        app.tailing = true;
-                    },
-                    // Handle all characters in normal mode (for search)
-                    (Mode::Normal, KeyCode::Char('f')) => {
-                        app.search_query.clear();
-                        app.update_search();
-                        app.mode = Mode::Search;
-                    },
-                    (Mode::Search, KeyCode::Char(c)) => {
-                        app.search_query.push(c);
-                        app.update_search();
-                    },
-                    (Mode::Search, KeyCode::Backspace) => {
-                        app.search_query.pop();
-                        app.update_search();
-                    },
-                    (Mode::Search, KeyCode::Enter) => {
-                        if !app.matches.is_empty() {
-                            if let Some((line_idx, _, _)) = app.matches.get(app.current_match) {
-                                app.scroll = *line_idx;
-                            }
-                        }
-                        app.search_query.clear();
-                        app.mode = Mode::Normal;
-                    },
-                    (Mode::Normal, KeyCode::Char('/')) => {
-                        app.filter.clear();
-                        app.mode = Mode::Filter;
-                    },
-                    (Mode::Filter, KeyCode::Char(c)) => {
-                        app.filter.push(c);
-                    },
-                    (Mode::Filter, KeyCode::Backspace) => {
-                        app.filter.pop();
-                    },
-                    (Mode::Filter, KeyCode::Enter) => {
-                        app.mode = Mode::Normal;
-                    },
-                    // Handle all characters in normal mode (for search)
-                    _ => {}
+        input = event::poll(Duration::from_millis(100))?;
+        if input {
+            let event = event::read()?;
+            if let Event::Resize(width, height) = event {
+                // Throw away the previous frame so nothing drawn for the old size lingers. The
+                // viewport itself is refitted to the new height as part of the next draw.
+                terminal.resize(Rect::new(0, 0, width, height))?;
+            }
+            if let Event::Paste(text) = &event {
+                if let Mode::Normal = app.mode {
+                    let count = app.append_paste(text);
+                    app.message = Some(format!("Appended {} pasted lines", count));
+                } else {
+                    app.paste_into_prompt(text);
+                }
+            }
+            if let Some(key) = if let Event::Key(key) = event { key_event(key) } else { None } {
+                tracing::debug!(?key.code, ?key.modifiers, "key");
+                let before = action_log.as_ref().map(|_| app.snapshot());
+                let handled_in = app.mode.status_text();
+                if app.handle_key(key, &key_options) {
+                    break;
                 }
+                yank(&mut app, args.yank_to);
+                if let (Some(log), Some(before)) = (&mut action_log, before) {
+                    if let Err(e) = log.record(&key, handled_in, &before, &app.snapshot()) {
+                        app.message = Some(format!("Action log failed: {}", e));
+                    }
+                }
+            }
+        }
+        if let Some(location) = app.edit.take() {
+            if let Err(e) = edit(&location) {
+                app.message = Some(format!("Failed to edit {}: {}", location, e));
             }
+            terminal.clear()?;
+            input = true;
+        }
+        if let Some(outcome) = app.keymap.expire() {
+            input = true;
+            if app.settle_keys(outcome, &key_options) {
+                break;
+            }
+            yank(&mut app, args.yank_to);
         }
     }
 
+    // Wind down in a fixed order so nothing printed on exit is interleaved: hand the terminal
+    // back, finish copying stdin to stdout, finish the export, and only then print the output
+    // followed by the summary.
     restore_terminal()?;
+    if let Some(stdin) = stdin {
+        stdin.stop().await;
+    }
+
+    if let Some(path) = &args.control {
+        let _ = std::fs::remove_file(path);
+    }
+    for path in &args.listen {
+        source::stop_listening(path);
+    }
+
+    if let (Some(path), Some(tally)) = (&history_path, &app.tally) {
+        if let Err(e) = history::append(path, &tally.finish()) {
+            eprintln!("carve: failed to record session in {}: {}", path.display(), e);
+        }
+    }
+
+    // Pick up anything that arrived after the last frame, or was held back while paused.
+    app.set_paused(false);
+    while let Ok(line) = app.incoming.try_recv() {
+        if let Some(line) = app.admit(line) {
+            app.push_line(line);
+        }
+    }
+
+    // Sources are still running, so stop them exporting too, leaving the export with the same
+    // lines as were taken in
+    if let Some(exporter) = &app.exporter {
+        if let Err(e) = exporter.lock().unwrap().finish() {
+            eprintln!("carve: export failed: {}", e);
+        }
+    }
+
+    if args.quiet {
+        if let Some(format) = &args.summary {
+            eprintln!("{}", app.summary(format, app.pane().visible_len(), 0));
+        }
+        std::process::exit(if app.matched() { 0 } else { 1 });
+    }
 
     // Print the filtered lines after exiting
-    if let Ok(lines) = app.lines.lock() {
-        let lines = lines.iter().filter(|line| {
-            app.filter == "" || line.contains(&app.filter)
-        });
-        for line in lines {
-            println!("{}", line);
+    let hyperlinks = match args.hyperlinks {
+        cli::Hyperlinks::Auto => output_is_terminal,
+        cli::Hyperlinks::Always => true,
+        cli::Hyperlinks::Never => false,
+    };
+    let output = if !app.picking {
+        app.output(args.collapse_duplicates)
+    } else if app.picked {
+        app.picks()
+    } else {
+        // Nothing was picked, so whatever the output was going to would be better off not running
+        std::process::exit(130);
+    };
+    let count = output.len();
+    let lines = output.into_iter().map(|line| {
+        let line = app.annotated(line, &args.annotate);
+        if hyperlinks {
+            hyperlink::linkify(&line).into_owned()
+        } else {
+            line
         }
+    });
+    dump::write(destination, lines, count, args.max_output, args.on_max_output).context("failed to write output")?;
+    if let Some(format) = &args.summary {
+        eprintln!("{}", app.summary(format, app.pane().visible_len(), count));
     }
 
-    Ok(())
+    // Everything has been written, so exit now rather than when the runtime has finished waiting
+    // for reads that may never return, such as from a stdin that's still open.
+    std::process::exit(0)
 }
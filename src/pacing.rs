@@ -0,0 +1,50 @@
+use std::time::{Duration, Instant};
+
+/// Average time to draw a frame above which the terminal is treated as slow, e.g. over SSH on a
+/// high latency link.
+const SLOW_DRAW: Duration = Duration::from_millis(50);
+/// The longest a slow terminal goes between redraws for new input when no keys are pressed.
+const MAX_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Decides when to redraw, based on how long recent frames took to reach the terminal.
+///
+/// Key presses are always redrawn straight away so navigation stays responsive. Redraws for
+/// lines arriving in the background are spaced out in proportion to the draw time, so a slow
+/// link isn't saturated by frames the user can't keep up with anyway.
+pub struct Pacer {
+    average: Duration, // Moving average of the time taken to draw a frame
+    last_draw: Option<Instant>,
+}
+
+impl Pacer {
+    pub fn new() -> Self {
+        Self {
+            average: Duration::ZERO,
+            last_draw: None,
+        }
+    }
+
+    /// Whether frames are taking long enough to draw that non-essential updates should be
+    /// skipped.
+    pub fn is_slow(&self) -> bool {
+        self.average >= SLOW_DRAW
+    }
+
+    /// Whether a frame should be drawn now. `input` is whether the user did something since the
+    /// last frame.
+    pub fn due(&self, input: bool) -> bool {
+        let Some(last_draw) = self.last_draw else {
+            return true;
+        };
+        if input || !self.is_slow() {
+            return true;
+        }
+        last_draw.elapsed() >= (self.average * 4).min(MAX_INTERVAL)
+    }
+
+    /// Record how long the frame just drawn took.
+    pub fn drew(&mut self, took: Duration) {
+        self.average = (self.average * 7 + took) / 8;
+        self.last_draw = Some(Instant::now());
+    }
+}
@@ -0,0 +1,160 @@
+use ratatui::style::{Style, Stylize};
+use ratatui::text::{Line, Span};
+use unicode_width::UnicodeWidthChar;
+
+use carve_core::app::options::ControlChars;
+
+/// Break a styled line into rows at most `width` columns wide, keeping each span's style. Wide
+/// characters, such as CJK text and emoji, take two columns and are moved to the next row
+/// rather than split.
+pub fn wrap(line: Line<'static>, width: usize) -> Vec<Line<'static>> {
+    if width == 0 {
+        return vec![line];
+    }
+
+    let mut rows = vec![Line::default()];
+    let mut row_width = 0;
+    for span in line.spans {
+        let mut rest: &str = &span.content;
+        while !rest.is_empty() {
+            let mut take = 0;
+            for c in rest.chars() {
+                let c_width = c.width().unwrap_or(0);
+                if row_width + c_width > width && (row_width > 0 || take > 0) {
+                    break;
+                }
+                row_width += c_width;
+                take += c.len_utf8();
+            }
+            if take == 0 {
+                rows.push(Line::default());
+                row_width = 0;
+                continue;
+            }
+            let (head, tail) = rest.split_at(take);
+            rows.last_mut().unwrap().spans.push(Span::styled(head.to_string(), span.style));
+            rest = tail;
+        }
+    }
+    rows
+}
+
+/// Add `style` to the parts of `spans` covering the given byte ranges of their combined text,
+/// splitting spans where a range starts or ends part way through.
+pub fn patch_ranges(spans: Vec<Span<'static>>, ranges: &[(usize, usize)], style: Style) -> Vec<Span<'static>> {
+    map_ranges(spans, ranges, |existing| existing.patch(style))
+}
+
+/// Restyle the parts of `spans` covering the given byte ranges of their combined text with `f`,
+/// splitting spans where a range starts or ends part way through.
+pub fn map_ranges(
+    spans: Vec<Span<'static>>,
+    ranges: &[(usize, usize)],
+    f: impl Fn(Style) -> Style,
+) -> Vec<Span<'static>> {
+    if ranges.is_empty() {
+        return spans;
+    }
+
+    let mut out = Vec::new();
+    let mut offset = 0;
+    for span in spans {
+        let (start, end) = (offset, offset + span.content.len());
+        offset = end;
+        // Cut points inside this span, relative to its start. A cut part way through a
+        // character is moved back to its start, so it can't split a multi-byte character.
+        let mut cuts: Vec<usize> = ranges
+            .iter()
+            .flat_map(|&(a, b)| [a, b])
+            .filter(|&cut| cut > start && cut < end)
+            .map(|cut| floor_char_boundary(&span.content, cut - start))
+            .filter(|&cut| cut > 0)
+            .collect();
+        cuts.sort_unstable();
+        cuts.dedup();
+
+        let mut last = 0;
+        for cut in cuts.into_iter().chain([end - start]) {
+            let piece = &span.content[last..cut];
+            let inside = ranges.iter().any(|&(a, b)| start + last >= a && start + last < b);
+            let piece_style = if inside { f(span.style) } else { span.style };
+            out.push(Span::styled(piece.to_string(), piece_style));
+            last = cut;
+        }
+    }
+    out
+}
+
+/// Keep only the parts of `spans` covering the given byte ranges of their combined text, in their
+/// styles, with `separator` between each range's part. The ranges must be in order and not
+/// overlap.
+pub fn extract(spans: Vec<Span<'static>>, ranges: &[(usize, usize)], separator: Span<'static>) -> Vec<Span<'static>> {
+    let mut out = Vec::new();
+    let mut offset = 0;
+    let mut last_range = None;
+    // Splitting at each range's ends leaves every piece wholly inside or outside a range
+    for piece in map_ranges(spans, ranges, |style| style) {
+        let start = offset;
+        offset += piece.content.len();
+        let Some(range) = ranges.iter().position(|&(a, b)| start >= a && start < b) else {
+            continue;
+        };
+        if last_range.is_some_and(|last| last != range) {
+            out.push(separator.clone());
+        }
+        last_range = Some(range);
+        out.push(piece);
+    }
+    out
+}
+
+/// The largest index no greater than `idx` that's at the start of a character in `text`.
+fn floor_char_boundary(text: &str, mut idx: usize) -> usize {
+    while !text.is_char_boundary(idx) {
+        idx -= 1;
+    }
+    idx
+}
+
+/// Expand tabs in `spans` to the next multiple of `tab_width` columns, and show any other
+/// control characters, which would otherwise move the cursor or change the terminal's state,
+/// in caret notation like `^[` and `^M`, or as `\x85` above DEL, or else drop them.
+pub fn sanitize(spans: Vec<Span<'static>>, tab_width: usize, control: ControlChars) -> Vec<Span<'static>> {
+    if !spans.iter().any(|span| span.content.chars().any(char::is_control)) {
+        return spans;
+    }
+
+    let mut out = Vec::new();
+    let mut column = 0;
+    for span in spans {
+        let mut text = String::new();
+        for c in span.content.chars() {
+            if c == '\t' {
+                let spaces = tab_width - column % tab_width;
+                text.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            } else if c.is_control() {
+                if control == ControlChars::Strip {
+                    continue;
+                }
+                let shown = match c as u32 {
+                    0x7f => "^?".to_string(),
+                    code @ 0..=0x1f => format!("^{}", char::from(code as u8 + 0x40)),
+                    code => format!("\\x{:02x}", code),
+                };
+                if !text.is_empty() {
+                    out.push(Span::styled(std::mem::take(&mut text), span.style));
+                }
+                column += shown.len();
+                out.push(Span::styled(shown, span.style.dim()));
+            } else {
+                column += c.width().unwrap_or(0);
+                text.push(c);
+            }
+        }
+        if !text.is_empty() {
+            out.push(Span::styled(text, span.style));
+        }
+    }
+    out
+}
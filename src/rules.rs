@@ -0,0 +1,84 @@
+use std::str::FromStr;
+
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Span;
+use regex::Regex;
+
+use carve_core::app::script::Paint;
+
+use crate::config::HighlightRule;
+use crate::render;
+
+/// A highlight rule from the config file, ready to apply to lines as they're drawn.
+pub struct Rule {
+    pattern: Regex,
+    style: Style,
+    line: bool, // Style the whole line rather than just the matches
+}
+
+impl Rule {
+    pub fn new(rule: &HighlightRule) -> Result<Self, String> {
+        let pattern = Regex::new(&rule.pattern).map_err(|e| format!("invalid pattern '{}': {}", rule.pattern, e))?;
+        let color = |name: &str| Color::from_str(name).map_err(|_| format!("unknown colour '{}'", name));
+        let mut style = Style::default();
+        if let Some(fg) = &rule.fg {
+            style = style.fg(color(fg)?);
+        }
+        if let Some(bg) = &rule.bg {
+            style = style.bg(color(bg)?);
+        }
+        if rule.bold {
+            style = style.add_modifier(Modifier::BOLD);
+        }
+        if rule.underline {
+            style = style.add_modifier(Modifier::UNDERLINED);
+        }
+        Ok(Self {
+            pattern,
+            style,
+            line: rule.line,
+        })
+    }
+}
+
+/// Style the parts of a line matched by each rule, underneath any styling `spans` already has so
+/// search matches stay distinct. `spans` must cover `text` exactly. Earlier rules win where
+/// rules overlap.
+pub fn apply(rules: &[Rule], text: &str, mut spans: Vec<Span<'static>>) -> Vec<Span<'static>> {
+    for rule in rules {
+        let ranges: Vec<(usize, usize)> = if rule.line {
+            if rule.pattern.is_match(text) {
+                vec![(0, text.len())]
+            } else {
+                Vec::new()
+            }
+        } else {
+            rule.pattern.find_iter(text).map(|m| (m.start(), m.end())).collect()
+        };
+        spans = render::map_ranges(spans, &ranges, |existing| rule.style.patch(existing));
+    }
+    spans
+}
+
+/// Style the parts of a line scripts picked out as it's drawn, in the same way as rules, which
+/// win where they overlap. Colours that aren't known are left out.
+pub fn paint(paints: &[Paint], mut spans: Vec<Span<'static>>) -> Vec<Span<'static>> {
+    let color = |name: &Option<String>| name.as_deref().and_then(|name| Color::from_str(name).ok());
+    for paint in paints {
+        let mut style = Style::default();
+        if let Some(fg) = color(&paint.fg) {
+            style = style.fg(fg);
+        }
+        if let Some(bg) = color(&paint.bg) {
+            style = style.bg(bg);
+        }
+        if paint.bold {
+            style = style.add_modifier(Modifier::BOLD);
+        }
+        if paint.underline {
+            style = style.add_modifier(Modifier::UNDERLINED);
+        }
+        spans = render::map_ranges(spans, &[paint.range], |existing| style.patch(existing));
+    }
+    spans
+}
@@ -0,0 +1,199 @@
+use std::str::FromStr;
+
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Span;
+
+use crate::config::StatusSegment;
+
+/// The segments on the left of the status bar unless the config file says otherwise.
+/// Pausing stands out, so it's clear why new lines aren't appearing.
+const DEFAULT_LEFT: [(&str, Style); 5] = [
+    (" {mode} ", Style::new()),
+    (" {paused} ", Style::new().fg(Color::Black).bg(Color::Yellow).add_modifier(Modifier::BOLD)),
+    ("[Search: {search}]", Style::new()),
+    ("[Filter: {filter}]", Style::new()),
+    ("{message}", Style::new()),
+];
+
+/// The segments on the right of the status bar unless the config file says otherwise.
+const DEFAULT_RIGHT: [(&str, Style); 9] = [
+    ("{counters}", Style::new()),
+    ("{sampling}", Style::new()),
+    ("{marked} marked", Style::new()),
+    ("output: {output}", Style::new()),
+    ("{language}", Style::new()),
+    ("{rate} lines/s", Style::new()),
+    ("{elapsed} elapsed", Style::new()),
+    ("{clock}", Style::new()),
+    ("{position}", Style::new()),
+];
+
+/// Something about the session that a segment can show, written `{name}` in its format.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Field {
+    Mode,
+    Paused,
+    Search,
+    Filter,
+    Message,
+    Match,
+    Matches,
+    Read,
+    Buffered,
+    Filtered,
+    Marked,
+    Counters,
+    Source,
+    Sampling,
+    Output,
+    Language,
+    Rate,
+    Elapsed,
+    Clock,
+    Position,
+}
+
+impl FromStr for Field {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Ok(match name {
+            "mode" => Field::Mode,
+            "paused" => Field::Paused,
+            "search" => Field::Search,
+            "filter" => Field::Filter,
+            "message" => Field::Message,
+            "match" => Field::Match,
+            "matches" => Field::Matches,
+            "read" => Field::Read,
+            "buffered" => Field::Buffered,
+            "filtered" => Field::Filtered,
+            "marked" => Field::Marked,
+            "counters" => Field::Counters,
+            "source" => Field::Source,
+            "sampling" => Field::Sampling,
+            "output" => Field::Output,
+            "language" => Field::Language,
+            "rate" => Field::Rate,
+            "elapsed" => Field::Elapsed,
+            "clock" => Field::Clock,
+            "position" => Field::Position,
+            _ => return Err(format!("unknown field '{{{}}}'", name)),
+        })
+    }
+}
+
+enum Piece {
+    Text(String),
+    Field(Field),
+}
+
+/// A part of the status bar: text with fields filled in, in its own style.
+pub struct Segment {
+    pieces: Vec<Piece>,
+    style: Style,
+}
+
+impl Segment {
+    pub fn new(segment: &StatusSegment) -> Result<Self, String> {
+        let color = |name: &str| Color::from_str(name).map_err(|_| format!("unknown colour '{}'", name));
+        let mut style = Style::default();
+        if let Some(fg) = &segment.fg {
+            style = style.fg(color(fg)?);
+        }
+        if let Some(bg) = &segment.bg {
+            style = style.bg(color(bg)?);
+        }
+        if segment.bold {
+            style = style.add_modifier(Modifier::BOLD);
+        }
+        Ok(Self {
+            pieces: parse(&segment.format)?,
+            style,
+        })
+    }
+
+    /// The segment with its fields filled in by `value`, or nothing if it has fields and they're
+    /// all empty, so `[Search: {search}]` is only shown while searching.
+    pub fn render(&self, value: &impl Fn(Field) -> String) -> Option<Span<'static>> {
+        let mut text = String::new();
+        let mut fields = 0;
+        let mut empty = 0;
+        for piece in &self.pieces {
+            match piece {
+                Piece::Text(literal) => text.push_str(literal),
+                Piece::Field(field) => {
+                    let value = value(*field);
+                    fields += 1;
+                    empty += value.is_empty() as usize;
+                    text.push_str(&value);
+                }
+            }
+        }
+        (fields == 0 || empty < fields).then(|| Span::styled(text, self.style))
+    }
+}
+
+/// The segments either side of the status bar.
+pub struct Layout {
+    pub left: Vec<Segment>,
+    pub right: Vec<Segment>,
+}
+
+impl Layout {
+    /// The layout from the config file, which gives each side as a list of segments, or the
+    /// default for a side it leaves out.
+    pub fn new(left: Option<&[StatusSegment]>, right: Option<&[StatusSegment]>) -> Result<Self, String> {
+        fn side(segments: Option<&[StatusSegment]>, default: &[(&str, Style)]) -> Result<Vec<Segment>, String> {
+            match segments {
+                Some(segments) => segments.iter().map(Segment::new).collect(),
+                None => default
+                    .iter()
+                    .map(|&(format, style)| Ok(Segment { pieces: parse(format)?, style }))
+                    .collect(),
+            }
+        }
+        Ok(Self {
+            left: side(left, &DEFAULT_LEFT)?,
+            right: side(right, &DEFAULT_RIGHT)?,
+        })
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::new(None, None).expect("the default status bar is valid")
+    }
+}
+
+/// Split a format into text and `{field}`s. `{{` and `}}` stand for literal braces.
+fn parse(format: &str) -> Result<Vec<Piece>, String> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.next_if_eq(&'{').is_some() => text.push('{'),
+            '}' if chars.next_if_eq(&'}').is_some() => text.push('}'),
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(format!("unclosed '{{' in '{}'", format)),
+                    }
+                }
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(std::mem::take(&mut text)));
+                }
+                pieces.push(Piece::Field(name.parse()?));
+            }
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(pieces)
+}